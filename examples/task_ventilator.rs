@@ -2,7 +2,6 @@ mod async_helpers;
 
 use rand::Rng;
 use std::error::Error;
-use std::io::Read;
 
 use zeromq::{Socket, SocketSend};
 
@@ -17,7 +16,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     sink.connect("tcp://127.0.0.1:5558").await?;
 
     println!("Press Enter when the workers are ready: ");
-    let _ = std::io::stdin().bytes().next();
+    let _ = std::io::stdin().read_line(&mut String::new());
     println!("Sending tasks to workers…");
 
     // The first message is "0" and signals start of batch
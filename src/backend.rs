@@ -1,76 +1,181 @@
-use crate::codec::{FramedIo, Message, ZmqFramedRead, ZmqFramedWrite};
+use crate::async_rt;
+use crate::codec::{ConnectionId, FramedIo, Message, ZmqRecvQueue, ZmqSendQueue, ZmtpVersion};
 use crate::fair_queue::QueueInner;
 use crate::util::PeerIdentity;
-use crate::{MultiPeerBackend, SocketBackend, SocketEvent, SocketType, ZmqError, ZmqResult};
+use crate::{
+    MultiPeerBackend, SocketBackend, SocketEvent, SocketOptions, SocketType, ZmqError, ZmqMessage,
+    ZmqResult,
+};
 use crossbeam::queue::SegQueue;
 use dashmap::DashMap;
-use futures::channel::mpsc;
-use futures::SinkExt;
+use futures::channel::{mpsc, oneshot};
+use futures::task::Poll;
+use futures::{FutureExt, Sink, SinkExt, StreamExt};
 use parking_lot::Mutex;
+use std::pin::Pin;
 use std::sync::Arc;
 
 pub(crate) struct Peer {
-    pub(crate) send_queue: ZmqFramedWrite,
+    pub(crate) send_queue: ZmqSendQueue,
+    pub(crate) zmtp_version: ZmtpVersion,
+    connection: ConnectionId,
+    // Only set for sockets without a fair queue, see `add_peer`
+    _drain_coro_stop: Option<oneshot::Sender<()>>,
 }
 
 pub(crate) struct GenericSocketBackend {
     pub(crate) peers: DashMap<PeerIdentity, Peer>,
-    fair_queue_inner: Option<Arc<Mutex<QueueInner<ZmqFramedRead, PeerIdentity>>>>,
+    fair_queue_inner: Option<Arc<Mutex<QueueInner<ZmqRecvQueue, PeerIdentity>>>>,
     pub(crate) round_robin: SegQueue<PeerIdentity>,
     socket_type: SocketType,
+    socket_options: SocketOptions,
     pub(crate) socket_monitor: Mutex<Option<mpsc::Sender<SocketEvent>>>,
+    // Held while a peer is added, so that checks on the peers already there
+    // and the insert happen as one step
+    adding_peer: Mutex<()>,
 }
 
 impl GenericSocketBackend {
     pub(crate) fn new(
-        fair_queue_inner: Option<Arc<Mutex<QueueInner<ZmqFramedRead, PeerIdentity>>>>,
+        fair_queue_inner: Option<Arc<Mutex<QueueInner<ZmqRecvQueue, PeerIdentity>>>>,
         socket_type: SocketType,
+        socket_options: SocketOptions,
     ) -> Self {
         Self {
             peers: DashMap::new(),
             fair_queue_inner,
             round_robin: SegQueue::new(),
             socket_type,
+            socket_options,
             socket_monitor: Mutex::new(None),
+            adding_peer: Mutex::new(()),
         }
     }
 
+    /// Sends the message to the next peer in line. Peers that reached their
+    /// send HWM are skipped, only once all of them did this waits for the
+    /// first one to make room
     pub(crate) async fn send_round_robin(&self, message: Message) -> ZmqResult<PeerIdentity> {
-        // In normal scenario this will always be only 1 iteration
-        // There can be special case when peer has disconnected and his id is still in
-        // RR queue This happens because SegQueue don't have an api to delete
-        // items from queue. So in such case we'll just pop item and skip it if
-        // we don't have a matching peer in peers map
-        loop {
-            let next_peer_id = match self.round_robin.pop() {
-                Ok(peer) => peer,
-                Err(_) => match message {
-                    Message::Greeting(_) => panic!("Sending greeting is not supported"),
-                    Message::Command(_) => panic!("Sending commands is not supported"),
-                    Message::Message(m) => {
-                        return Err(ZmqError::ReturnToSender {
-                            reason: "Not connected to peers. Unable to send messages",
-                            message: m,
-                        })
-                    }
-                },
-            };
-            match self.peers.get_mut(&next_peer_id) {
-                Some(mut peer) => {
-                    let send_result = peer.send_queue.send(message).await;
-                    match send_result {
-                        Ok(()) => {
-                            self.round_robin.push(next_peer_id.clone());
-                            return Ok(next_peer_id);
-                        }
-                        Err(_) => {
-                            panic!("Don't know how to handle this now");
-                        }
-                    };
+        let mut message = Some(message);
+        let sent = futures::future::poll_fn(|cx| {
+            // Ids of disconnected peers can linger in the round robin queue,
+            // as SegQueue has no api to delete items. They are dropped when
+            // popped
+            for _ in 0..self.round_robin.len() {
+                let peer_id = match self.round_robin.pop() {
+                    Ok(peer_id) => peer_id,
+                    Err(_) => break,
+                };
+                let mut peer = match self.peers.get_mut(&peer_id) {
+                    Some(peer) => peer,
+                    None => continue,
+                };
+                self.round_robin.push(peer_id.clone());
+                // Full peers wake us up once they have room again
+                if let Poll::Ready(ready) = Pin::new(&mut peer.send_queue).poll_ready(cx) {
+                    let message = message.take().expect("Message already sent");
+                    let result =
+                        ready.and_then(|()| Pin::new(&mut peer.send_queue).start_send(message));
+                    return Poll::Ready(Some((peer_id, result)));
                 }
-                None => continue,
+            }
+            if self.round_robin.is_empty() {
+                Poll::Ready(None)
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+        match sent {
+            Some((peer_id, Ok(()))) => Ok(peer_id),
+            Some((peer_id, Err(e))) => {
+                self.peer_disconnected(&peer_id);
+                Err(e.into())
+            }
+            None => match message.take().expect("Message already sent") {
+                Message::Greeting(_) => panic!("Sending greeting is not supported"),
+                Message::Command(_) => panic!("Sending commands is not supported"),
+                Message::Message(m) => Err(ZmqError::ReturnToSender {
+                    reason: "Not connected to peers. Unable to send messages",
+                    message: m,
+                }),
+            },
+        }
+    }
+
+    /// Sends a copy of the message to every connected peer. Peers that fail
+    /// to accept it are dropped
+    pub(crate) async fn send_to_all(&self, message: ZmqMessage) -> ZmqResult<()> {
+        let mut dead_peers = Vec::new();
+        for mut peer in self.peers.iter_mut() {
+            let send_result = peer
+                .send_queue
+                .send(Message::Message(message.clone()))
+                .await;
+            if let Err(e) = send_result {
+                log::debug!("Failed to send to peer {:?}: {}", peer.key(), e);
+                dead_peers.push(peer.key().clone());
             }
         }
+        for peer_id in dead_peers {
+            self.peer_disconnected(&peer_id);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn add_peer(&self, peer_id: &PeerIdentity, io: FramedIo) -> ZmqResult<()> {
+        let _adding_peer = self.adding_peer.lock();
+        // PAIR sockets are exclusive: once a peer is attached any further
+        // connections are refused until it goes away
+        if self.socket_type == SocketType::PAIR && !self.peers.is_empty() {
+            return Err(ZmqError::Socket("PAIR socket already has a peer"));
+        }
+        if self.socket_type == SocketType::ROUTER && self.peers.contains_key(peer_id) {
+            if self.socket_options.router_handover {
+                log::debug!("Peer {:?} is taken over by a new connection", peer_id);
+                self.peer_disconnected(peer_id);
+            } else {
+                return Err(ZmqError::Socket("Peer identity already in use"));
+            }
+        }
+        let zmtp_version = io.zmtp_version;
+        let connection = io.connection_id;
+        let (mut recv_queue, send_queue) = io.into_queues(&self.socket_options);
+        let drain_coro_stop = match &self.fair_queue_inner {
+            None => {
+                // Nobody reads from this peer, but the connection still has
+                // to be polled for the socket to notice it going away
+                let (sender, stop_receiver) = oneshot::channel::<()>();
+                async_rt::task::spawn(async move {
+                    let mut stop_receiver = stop_receiver.fuse();
+                    loop {
+                        futures::select! {
+                            _ = stop_receiver => break,
+                            message = recv_queue.next().fuse() => match message {
+                                Some(Ok(_)) => {}
+                                _ => break,
+                            },
+                        }
+                    }
+                });
+                Some(sender)
+            }
+            Some(inner) => {
+                inner.lock().insert(peer_id.clone(), recv_queue);
+                None
+            }
+        };
+        self.peers.insert(
+            peer_id.clone(),
+            Peer {
+                send_queue,
+                zmtp_version,
+                connection,
+                _drain_coro_stop: drain_coro_stop,
+            },
+        );
+        self.round_robin.push(peer_id.clone());
+        Ok(())
     }
 }
 
@@ -86,22 +191,32 @@ impl SocketBackend for GenericSocketBackend {
     fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>> {
         &self.socket_monitor
     }
+
+    fn socket_options(&self) -> &SocketOptions {
+        &self.socket_options
+    }
 }
 
 impl MultiPeerBackend for GenericSocketBackend {
-    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) {
-        let (recv_queue, send_queue) = io.into_parts();
-        self.peers.insert(peer_id.clone(), Peer { send_queue });
-        self.round_robin.push(peer_id.clone());
-        match &self.fair_queue_inner {
-            None => {}
-            Some(inner) => {
-                inner.lock().insert(peer_id.clone(), recv_queue);
-            }
-        };
+    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) -> ZmqResult<()> {
+        self.add_peer(peer_id, io)
     }
 
     fn peer_disconnected(&self, peer_id: &PeerIdentity) {
         self.peers.remove(peer_id);
+        if let Some(inner) = &self.fair_queue_inner {
+            inner.lock().remove(peer_id);
+        }
+    }
+
+    fn connection_lost(&self, peer_id: &PeerIdentity, connection: ConnectionId) {
+        // Keeps a connection taking over the identity from slipping in
+        // between the check and the removal
+        let _adding_peer = self.adding_peer.lock();
+        let current =
+            matches!(self.peers.get(peer_id), Some(peer) if peer.connection == connection);
+        if current {
+            self.peer_disconnected(peer_id);
+        }
     }
 }
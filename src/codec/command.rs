@@ -1,81 +1,235 @@
-use super::error::CodecError;
-use crate::SocketType;
+use super::error::{CodecError, CodecResult};
+use crate::{SocketOptions, SocketType};
 
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::time::Duration;
 
-#[derive(Debug, Copy, Clone)]
+/// PING commands carry up to 16 bytes of context for the PONG to echo
+const MAX_PING_CONTEXT: usize = 16;
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ZmqCommandName {
     READY,
+    HELLO,
+    WELCOME,
+    INITIATE,
+    ERROR,
+    PING,
+    PONG,
+    SUBSCRIBE,
+    CANCEL,
+    JOIN,
+    LEAVE,
 }
 
 impl From<ZmqCommandName> for String {
     fn from(c_name: ZmqCommandName) -> Self {
         match c_name {
             ZmqCommandName::READY => "READY".into(),
+            ZmqCommandName::HELLO => "HELLO".into(),
+            ZmqCommandName::WELCOME => "WELCOME".into(),
+            ZmqCommandName::INITIATE => "INITIATE".into(),
+            ZmqCommandName::ERROR => "ERROR".into(),
+            ZmqCommandName::PING => "PING".into(),
+            ZmqCommandName::PONG => "PONG".into(),
+            ZmqCommandName::SUBSCRIBE => "SUBSCRIBE".into(),
+            ZmqCommandName::CANCEL => "CANCEL".into(),
+            ZmqCommandName::JOIN => "JOIN".into(),
+            ZmqCommandName::LEAVE => "LEAVE".into(),
         }
     }
 }
 
+/// A ZMTP command. The layout of `data` depends on the command and on the
+/// security mechanism in use, see [`ZmqCommand::properties`] for commands
+/// carrying metadata
 #[derive(Debug, Clone)]
 pub struct ZmqCommand {
     pub name: ZmqCommandName,
-    pub properties: HashMap<String, String>,
+    pub data: Bytes,
 }
 
 impl ZmqCommand {
-    pub fn ready(socket: SocketType) -> Self {
+    pub fn new(name: ZmqCommandName, data: Bytes) -> Self {
+        Self { name, data }
+    }
+
+    /// Metadata describing this end of the connection, sent in READY or
+    /// INITIATE commands
+    pub fn metadata(socket: SocketType, options: &SocketOptions) -> HashMap<String, Bytes> {
+        let mut properties: HashMap<String, Bytes> = options
+            .metadata()
+            .iter()
+            .map(|(name, value)| (name.to_owned(), value.clone()))
+            .collect();
+        properties.insert("Socket-Type".into(), Bytes::from(format!("{}", socket)));
+        if let Some(identity) = options.peer_identity() {
+            properties.insert("Identity".into(), identity.clone().into());
+        }
+        properties
+    }
+
+    pub fn ready(socket: SocketType, options: &SocketOptions) -> Self {
+        Self::with_properties(ZmqCommandName::READY, &Self::metadata(socket, options))
+    }
+
+    pub fn with_properties(name: ZmqCommandName, properties: &HashMap<String, Bytes>) -> Self {
+        let mut data = BytesMut::new();
+        for (prop, val) in properties.iter() {
+            data.put_u8(prop.len() as u8);
+            data.extend_from_slice(prop.as_ref());
+            data.put_u32(val.len() as u32);
+            data.extend_from_slice(val.as_ref());
+        }
+        Self::new(name, data.freeze())
+    }
+
+    /// Parses the command body as a list of metadata properties
+    pub fn properties(&self) -> CodecResult<HashMap<String, Bytes>> {
+        let mut buf = self.data.clone();
         let mut properties = HashMap::new();
-        properties.insert("Socket-Type".into(), format!("{}", socket));
-        Self {
-            name: ZmqCommandName::READY,
-            properties,
+        while !buf.is_empty() {
+            let property = take_short(&mut buf)?;
+            let property = String::from_utf8(property.to_vec())
+                .map_err(|_| CodecError::Command("Malformed property name"))?;
+            if buf.len() < 4 {
+                return Err(CodecError::Command("Malformed property value"));
+            }
+            let prop_val_len = buf.get_u32() as usize;
+            if buf.len() < prop_val_len {
+                return Err(CodecError::Command("Malformed property value"));
+            }
+            properties.insert(property, buf.split_to(prop_val_len));
         }
+        Ok(properties)
+    }
+
+    pub fn error(reason: &str) -> Self {
+        let reason = &reason.as_bytes()[..reason.len().min(255)];
+        let mut data = BytesMut::with_capacity(reason.len() + 1);
+        data.put_u8(reason.len() as u8);
+        data.extend_from_slice(reason);
+        Self::new(ZmqCommandName::ERROR, data.freeze())
+    }
+
+    /// The reason given in an ERROR command
+    pub fn error_reason(&self) -> String {
+        let mut buf = self.data.clone();
+        match take_short(&mut buf) {
+            Ok(reason) => String::from_utf8_lossy(&reason).into_owned(),
+            Err(_) => String::new(),
+        }
+    }
+
+    /// A heartbeat asking the peer to drop the connection if it hears nothing
+    /// from us for `ttl`, which is sent in tenths of a second
+    pub fn ping(ttl: Duration, context: &[u8]) -> Self {
+        let ttl = (ttl.as_millis() / 100).min(u128::from(u16::MAX)) as u16;
+        let context = &context[..context.len().min(MAX_PING_CONTEXT)];
+        let mut data = BytesMut::with_capacity(2 + context.len());
+        data.put_u16(ttl);
+        data.extend_from_slice(context);
+        Self::new(ZmqCommandName::PING, data.freeze())
+    }
+
+    /// The reply to `ping`, echoing its context
+    pub fn pong(ping: &ZmqCommand) -> Self {
+        let context = ping.data.slice(2.min(ping.data.len())..);
+        let context = context.slice(..context.len().min(MAX_PING_CONTEXT));
+        Self::new(ZmqCommandName::PONG, context)
+    }
+
+    /// The TTL requested in a PING command, zero if none
+    pub fn ping_ttl(&self) -> Duration {
+        match self.data.get(..2) {
+            Some(ttl) => {
+                Duration::from_millis(u64::from(u16::from_be_bytes([ttl[0], ttl[1]])) * 100)
+            }
+            None => Duration::from_secs(0),
+        }
+    }
+
+    /// ZMTP 3.1 form of a subscription, the command data is the topic
+    pub fn subscription(subscribe: bool, topic: &[u8]) -> Self {
+        let name = if subscribe {
+            ZmqCommandName::SUBSCRIBE
+        } else {
+            ZmqCommandName::CANCEL
+        };
+        Self::new(name, Bytes::copy_from_slice(topic))
+    }
+
+    /// A DISH joining or leaving a group, the command data is the group name
+    pub fn membership(join: bool, group: &[u8]) -> Self {
+        let name = if join {
+            ZmqCommandName::JOIN
+        } else {
+            ZmqCommandName::LEAVE
+        };
+        Self::new(name, Bytes::copy_from_slice(group))
+    }
+
+    /// The command as carried by a frame: its name followed by its data
+    pub(crate) fn to_bytes(&self) -> BytesMut {
+        let command_name: String = self.name.into();
+        let mut bytes = BytesMut::with_capacity(command_name.len() + 1 + self.data.len());
+        bytes.put_u8(command_name.len() as u8);
+        bytes.extend_from_slice(command_name.as_ref());
+        bytes.extend_from_slice(self.data.as_ref());
+        bytes
     }
 }
 
+/// Splits off a field prefixed by its length in a single octet
+pub(crate) fn take_short(buf: &mut Bytes) -> CodecResult<Bytes> {
+    if buf.is_empty() {
+        return Err(CodecError::Command("Malformed command"));
+    }
+    let len = buf.get_u8() as usize;
+    if buf.len() < len {
+        return Err(CodecError::Command("Malformed command"));
+    }
+    Ok(buf.split_to(len))
+}
+
 impl TryFrom<BytesMut> for ZmqCommand {
     type Error = CodecError;
 
-    fn try_from(mut buf: BytesMut) -> Result<Self, Self::Error> {
-        let command_len = buf.get_u8() as usize;
+    fn try_from(buf: BytesMut) -> Result<Self, Self::Error> {
+        let mut buf = buf.freeze();
+        // libzmq 4.3 writes the PLAIN error prefix as "\x05ERROR" in C, where
+        // the hex escape swallows the 'E'. Accept what it actually sends
+        if buf.starts_with(b"^RROR") {
+            buf.advance(5);
+            return Ok(Self::new(ZmqCommandName::ERROR, buf));
+        }
         // command-name-char = ALPHA according to https://rfc.zeromq.org/spec:23/ZMTP/
-        let command_name =
-            unsafe { String::from_utf8_unchecked(buf.split_to(command_len).to_vec()) };
-        let command = match command_name.as_str() {
-            "READY" => ZmqCommandName::READY,
+        let command_name = take_short(&mut buf)?;
+        let command = match command_name.as_ref() {
+            b"READY" => ZmqCommandName::READY,
+            b"HELLO" => ZmqCommandName::HELLO,
+            b"WELCOME" => ZmqCommandName::WELCOME,
+            b"INITIATE" => ZmqCommandName::INITIATE,
+            b"ERROR" => ZmqCommandName::ERROR,
+            b"PING" => ZmqCommandName::PING,
+            b"PONG" => ZmqCommandName::PONG,
+            b"SUBSCRIBE" => ZmqCommandName::SUBSCRIBE,
+            b"CANCEL" => ZmqCommandName::CANCEL,
+            b"JOIN" => ZmqCommandName::JOIN,
+            b"LEAVE" => ZmqCommandName::LEAVE,
             _ => return Err(CodecError::Command("Uknown command received")),
         };
-        let mut properties = HashMap::new();
-
-        while !buf.is_empty() {
-            // Collect command properties
-            let prop_len = buf.get_u8() as usize;
-            let property = unsafe { String::from_utf8_unchecked(buf.split_to(prop_len).to_vec()) };
-
-            let prop_val_len = buf.get_u32() as usize;
-            let prop_value =
-                unsafe { String::from_utf8_unchecked(buf.split_to(prop_val_len).to_vec()) };
-            properties.insert(property, prop_value);
-        }
-        Ok(Self {
-            name: command,
-            properties,
-        })
+        Ok(Self::new(command, buf))
     }
 }
 
 impl From<ZmqCommand> for BytesMut {
     fn from(command: ZmqCommand) -> Self {
-        let mut message_len = 0;
-
-        let command_name: String = command.name.into();
-        message_len += command_name.len() + 1;
-        for (prop, val) in command.properties.iter() {
-            message_len += prop.len() + 1;
-            message_len += val.len() + 4;
-        }
+        let body = command.to_bytes();
+        let message_len = body.len();
 
         let long_message = message_len > 255;
 
@@ -89,14 +243,7 @@ impl From<ZmqCommand> for BytesMut {
             bytes.put_u8(0x04);
             bytes.put_u8(message_len as u8);
         };
-        bytes.put_u8(command_name.len() as u8);
-        bytes.extend_from_slice(command_name.as_ref());
-        for (prop, val) in command.properties.iter() {
-            bytes.put_u8(prop.len() as u8);
-            bytes.extend_from_slice(prop.as_ref());
-            bytes.put_u32(val.len() as u32);
-            bytes.extend_from_slice(val.as_ref());
-        }
+        bytes.extend_from_slice(&body);
         bytes
     }
 }
@@ -1,5 +1,14 @@
-use crate::codec::ZmqCodec;
+use crate::codec::{
+    CodecError, CodecResult, Liveness, Message, ZmqCodec, ZmqCommand, ZmqGreeting, ZmtpVersion,
+};
+use crate::metadata::PeerMetadata;
+use futures::channel::{mpsc, oneshot};
+use futures::task::{Context, Poll};
+use futures::{Sink, Stream, StreamExt};
 use futures_codec::{FramedRead, FramedWrite};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 // Enables us to have multiple bounds on the dyn trait in `InnerFramed`
 pub trait FrameableRead: futures::AsyncRead + Unpin + Send + Sync {}
@@ -7,26 +16,195 @@ impl<T> FrameableRead for T where T: futures::AsyncRead + Unpin + Send + Sync {}
 pub trait FrameableWrite: futures::AsyncWrite + Unpin + Send + Sync {}
 impl<T> FrameableWrite for T where T: futures::AsyncWrite + Unpin + Send + Sync {}
 
-pub(crate) type ZmqFramedRead = futures_codec::FramedRead<Box<dyn FrameableRead>, ZmqCodec>;
-pub(crate) type ZmqFramedWrite = futures_codec::FramedWrite<Box<dyn FrameableWrite>, ZmqCodec>;
+/// Incoming half of a connection: frames decoded from a byte stream, or
+/// messages handed over as is by an inproc peer or a datagram transport
+pub(crate) enum ZmqFramedRead {
+    Bytes(FramedRead<Box<dyn FrameableRead>, ZmqCodec>),
+    // The codec only keeps the state of the connection, such as the
+    // metadata of the peer
+    #[cfg_attr(
+        not(any(feature = "inproc-transport", feature = "udp-transport")),
+        allow(dead_code)
+    )]
+    Channel(mpsc::Receiver<Message>, ZmqCodec),
+}
+
+/// Outgoing half of a connection, see [`ZmqFramedRead`]
+pub(crate) enum ZmqFramedWrite {
+    Bytes(FramedWrite<Box<dyn FrameableWrite>, ZmqCodec>),
+    #[cfg_attr(
+        not(any(feature = "inproc-transport", feature = "udp-transport")),
+        allow(dead_code)
+    )]
+    Channel(mpsc::Sender<Message>, ZmqCodec),
+}
+
+impl ZmqFramedRead {
+    pub(crate) fn decoder_mut(&mut self) -> &mut ZmqCodec {
+        match self {
+            ZmqFramedRead::Bytes(framed) => framed.decoder_mut(),
+            ZmqFramedRead::Channel(_, codec) => codec,
+        }
+    }
+}
+
+impl ZmqFramedWrite {
+    pub(crate) fn encoder_mut(&mut self) -> &mut ZmqCodec {
+        match self {
+            ZmqFramedWrite::Bytes(framed) => framed.encoder_mut(),
+            ZmqFramedWrite::Channel(_, codec) => codec,
+        }
+    }
+}
+
+fn disconnected() -> CodecError {
+    CodecError::Io(std::io::ErrorKind::BrokenPipe.into())
+}
+
+impl Stream for ZmqFramedRead {
+    type Item = CodecResult<Message>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut() {
+            ZmqFramedRead::Bytes(framed) => framed.poll_next_unpin(cx),
+            ZmqFramedRead::Channel(receiver, codec) => {
+                receiver.poll_next_unpin(cx).map(|message| {
+                    message.map(|mut message| {
+                        if let Message::Message(message) = &mut message {
+                            message.set_metadata(codec.metadata());
+                        }
+                        Ok(message)
+                    })
+                })
+            }
+        }
+    }
+}
+
+impl Sink<Message> for ZmqFramedWrite {
+    type Error = CodecError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            ZmqFramedWrite::Bytes(framed) => Pin::new(framed).poll_ready(cx),
+            ZmqFramedWrite::Channel(sender, _) => sender.poll_ready(cx).map_err(|_| disconnected()),
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        match self.get_mut() {
+            ZmqFramedWrite::Bytes(framed) => Pin::new(framed).start_send(item),
+            ZmqFramedWrite::Channel(sender, _) => {
+                sender.start_send(item).map_err(|_| disconnected())
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            ZmqFramedWrite::Bytes(framed) => Pin::new(framed).poll_flush(cx),
+            ZmqFramedWrite::Channel(sender, _) => {
+                Pin::new(sender).poll_flush(cx).map_err(|_| disconnected())
+            }
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.get_mut() {
+            ZmqFramedWrite::Bytes(framed) => Pin::new(framed).poll_close(cx),
+            ZmqFramedWrite::Channel(sender, _) => {
+                Pin::new(sender).poll_close(cx).map_err(|_| disconnected())
+            }
+        }
+    }
+}
+
+/// Tells connections apart. A peer identity can move from one connection to
+/// another, tearing down the old one must leave the new one alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionId(u64);
+
+impl ConnectionId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
 
 /// Equivalent to [`futures_codec::Framed<T, ZmqCodec>`]
 pub struct FramedIo {
-    pub read_half: ZmqFramedRead,
-    pub write_half: ZmqFramedWrite,
+    pub(crate) read_half: ZmqFramedRead,
+    pub(crate) write_half: ZmqFramedWrite,
+    pub(crate) close_notify: Option<oneshot::Sender<()>>,
+    /// Protocol version agreed on with the peer during the greeting
+    pub(crate) zmtp_version: ZmtpVersion,
+    pub(crate) liveness: Arc<Liveness>,
+    pub(crate) commands: mpsc::UnboundedReceiver<ZmqCommand>,
+    /// Properties of the peer known to the transport, such as the subject
+    /// of its TLS certificate
+    pub(crate) transport_metadata: PeerMetadata,
+    /// Set by connectionless transports such as UDP, whose peers never say
+    /// what they are interested in
+    pub(crate) connectionless: bool,
+    pub(crate) connection_id: ConnectionId,
 }
 
 impl FramedIo {
     pub fn new(read_half: Box<dyn FrameableRead>, write_half: Box<dyn FrameableWrite>) -> Self {
-        let read_half = FramedRead::new(read_half, ZmqCodec::new());
-        let write_half = FramedWrite::new(write_half, ZmqCodec::new());
+        Self::from_halves(
+            ZmqFramedRead::Bytes(FramedRead::new(read_half, ZmqCodec::new())),
+            ZmqFramedWrite::Bytes(FramedWrite::new(write_half, ZmqCodec::new())),
+        )
+    }
+
+    /// Both ends of an inproc connection. Messages go through the channels
+    /// as they are, without being encoded
+    #[cfg(feature = "inproc-transport")]
+    pub(crate) fn inproc_pair() -> (Self, Self) {
+        // Sending waits for room in the channel, both ends send part of the
+        // handshake before reading the other's
+        const BUFFER: usize = 16;
+        let (to_accepted, from_connected) = mpsc::channel(BUFFER);
+        let (to_connected, from_accepted) = mpsc::channel(BUFFER);
+        let connected = Self::from_channels(from_accepted, to_accepted);
+        let accepted = Self::from_channels(from_connected, to_connected);
+        (connected, accepted)
+    }
+
+    /// Connection whose messages go through channels as they are, the other
+    /// ends of which are served by the transport
+    #[cfg(any(feature = "inproc-transport", feature = "udp-transport"))]
+    pub(crate) fn from_channels(
+        receiver: mpsc::Receiver<Message>,
+        sender: mpsc::Sender<Message>,
+    ) -> Self {
+        Self::from_halves(
+            ZmqFramedRead::Channel(receiver, ZmqCodec::new()),
+            ZmqFramedWrite::Channel(sender, ZmqCodec::new()),
+        )
+    }
+
+    fn from_halves(read_half: ZmqFramedRead, write_half: ZmqFramedWrite) -> Self {
+        let (liveness, commands) = Liveness::new();
         Self {
             read_half,
             write_half,
+            close_notify: None,
+            zmtp_version: ZmqGreeting::default().version,
+            liveness,
+            commands,
+            transport_metadata: PeerMetadata::default(),
+            connectionless: false,
+            connection_id: ConnectionId::next(),
         }
     }
 
-    pub fn into_parts(self) -> (ZmqFramedRead, ZmqFramedWrite) {
-        (self.read_half, self.write_half)
+    /// Returns a receiver that completes once the connection is gone: the
+    /// socket read everything the peer sent before closing the connection
+    /// or failing, or it dropped the peer. See [`super::ZmqRecvQueue`]
+    pub(crate) fn notify_on_close(mut self) -> (Self, oneshot::Receiver<()>) {
+        let (sender, receiver) = oneshot::channel();
+        self.close_notify = Some(sender);
+        (self, receiver)
     }
 }
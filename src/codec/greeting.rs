@@ -1,7 +1,9 @@
-use super::error::CodecError;
+use super::error::{CodecError, CodecResult};
 use super::mechanism::ZmqMechanism;
+use crate::SocketType;
 
-use bytes::{Bytes, BytesMut};
+use bytes::{Buf, BufMut, BytesMut};
+use num_traits::{FromPrimitive, ToPrimitive};
 use std::convert::TryFrom;
 
 pub type ZmtpVersion = (u8, u8);
@@ -11,45 +13,100 @@ pub struct ZmqGreeting {
     pub version: ZmtpVersion,
     pub mechanism: ZmqMechanism,
     pub as_server: bool,
+    /// ZMTP 1.0 and 2.0 greetings end with the socket type instead of the
+    /// security mechanism
+    pub socket_type: Option<SocketType>,
 }
 
 impl Default for ZmqGreeting {
     fn default() -> Self {
         Self {
-            version: (3, 0),
+            version: (3, 1),
             mechanism: ZmqMechanism::NULL,
             as_server: false,
+            socket_type: None,
         }
     }
 }
 
-impl TryFrom<Bytes> for ZmqGreeting {
-    type Error = CodecError;
+/// Greetings are exchanged part by part, each side waiting for the other's
+/// before sending the next one, so that peers speaking older protocol
+/// versions can be detected, see
+/// https://rfc.zeromq.org/spec/23/#backwards-interoperability
+#[derive(Debug, Copy, Clone)]
+pub enum GreetingPart {
+    /// `0xff`, padding and `0x7f`. ZMTP 1.0 peers read it as the header of
+    /// our identity frame, the padding holds its length
+    Signature { identity_len: usize },
+    /// Read instead of a signature from ZMTP 1.0 peers, which start with
+    /// their identity frame right away
+    Unversioned,
+    /// Major version, called revision in ZMTP 2.0
+    Major(u8),
+    /// Everything after the major version
+    Rest(ZmqGreeting),
+}
 
-    fn try_from(value: Bytes) -> Result<Self, Self::Error> {
-        if !(value[0] == 0xff && value[9] == 0x7f) {
-            return Err(CodecError::Greeting("Failed to parse greeting"));
+/// Major version byte of ZMTP 2.0, the one of ZMTP 1.0 is 0
+const ZMTP_2_0: u8 = 1;
+
+impl GreetingPart {
+    /// Length of the rest of the greeting, given the peer's major version
+    pub(crate) fn rest_len(major: u8) -> usize {
+        if major <= ZMTP_2_0 {
+            1
+        } else {
+            53
         }
-        Ok(ZmqGreeting {
-            version: (value[10], value[11]),
-            mechanism: ZmqMechanism::try_from(value[12..32].to_vec())?,
-            as_server: value[32] == 0x01,
-        })
     }
-}
 
-impl From<ZmqGreeting> for BytesMut {
-    fn from(greet: ZmqGreeting) -> Self {
-        let mut data: [u8; 64] = [0; 64];
-        data[0] = 0xff;
-        data[9] = 0x7f;
-        data[10] = greet.version.0;
-        data[11] = greet.version.1;
-        let mech = format!("{}", greet.mechanism);
-        data[12..12 + mech.len()].copy_from_slice(mech.as_bytes());
-        data[32] = greet.as_server.into();
-        let mut bytes = BytesMut::new();
-        bytes.extend_from_slice(&data);
-        bytes
+    /// Parses the rest of the greeting of a peer using `major` version
+    pub(crate) fn parse_rest(major: u8, mut data: BytesMut) -> CodecResult<Self> {
+        if major <= ZMTP_2_0 {
+            let socket_type = SocketType::from_u8(data.get_u8())
+                .ok_or(CodecError::Greeting("Unknown socket type"))?;
+            return Ok(GreetingPart::Rest(ZmqGreeting {
+                version: (major + 1, 0),
+                socket_type: Some(socket_type),
+                ..ZmqGreeting::default()
+            }));
+        }
+        Ok(GreetingPart::Rest(ZmqGreeting {
+            version: (major, data[0]),
+            mechanism: ZmqMechanism::try_from(data[1..21].to_vec())?,
+            as_server: data[21] == 0x01,
+            socket_type: None,
+        }))
+    }
+
+    pub(crate) fn write_to(self, dst: &mut BytesMut) -> CodecResult<()> {
+        match self {
+            GreetingPart::Signature { identity_len } => {
+                dst.reserve(10);
+                dst.put_u8(0xff);
+                dst.put_u64(identity_len as u64 + 1);
+                dst.put_u8(0x7f);
+            }
+            GreetingPart::Unversioned => {
+                return Err(CodecError::Greeting("Unversioned greetings are never sent"))
+            }
+            GreetingPart::Major(major) => dst.put_u8(major),
+            GreetingPart::Rest(greeting) if greeting.version.0 < 3 => {
+                let socket_type = greeting
+                    .socket_type
+                    .and_then(|t| t.to_u8())
+                    .ok_or(CodecError::Greeting("Missing socket type"))?;
+                dst.put_u8(socket_type);
+            }
+            GreetingPart::Rest(greeting) => {
+                let mut data: [u8; 53] = [0; 53];
+                data[0] = greeting.version.1;
+                let mech = format!("{}", greeting.mechanism);
+                data[1..1 + mech.len()].copy_from_slice(mech.as_bytes());
+                data[21] = greeting.as_server.into();
+                dst.extend_from_slice(&data);
+            }
+        }
+        Ok(())
     }
 }
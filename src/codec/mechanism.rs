@@ -3,7 +3,8 @@ use super::error::CodecError;
 use std::convert::TryFrom;
 use std::fmt::Display;
 
-#[derive(Debug, Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ZmqMechanism {
     NULL,
     PLAIN,
@@ -0,0 +1,304 @@
+use super::{
+    CodecError, CodecResult, FramedIo, Message, TrySend, ZmqCommand, ZmqCommandName, ZmqFramedRead,
+    ZmqFramedWrite,
+};
+use crate::async_rt;
+use crate::{SocketOptions, ZmqError, ZmqResult};
+
+use futures::channel::{mpsc, oneshot};
+use futures::task::{Context, Poll};
+use futures::{FutureExt, Sink, SinkExt, Stream, StreamExt};
+use parking_lot::Mutex;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Outgoing half of a peer connection. Holds at most `SNDHWM` messages,
+/// a background task writes them to the connection. Once dropped, messages
+/// still queued get written for up to `linger`
+pub(crate) struct ZmqSendQueue {
+    sender: mpsc::Sender<Message>,
+    control: mpsc::UnboundedSender<Message>,
+    _closed: oneshot::Sender<()>,
+}
+
+/// Incoming half of a peer connection. A background task reads ahead up to
+/// `RCVHWM` messages, beyond that the peer is left to TCP backpressure.
+/// Messages read ahead are still delivered after the connection is gone,
+/// the close notification only fires once they are consumed
+pub(crate) struct ZmqRecvQueue {
+    receiver: mpsc::Receiver<CodecResult<Message>>,
+    close_notify: Option<oneshot::Sender<()>>,
+    _recv_coro_stop: oneshot::Sender<()>,
+}
+
+/// Traffic on a peer connection, shared by its queues and the heartbeat
+/// task of the peer. PING and PONG commands are handled by the queues and
+/// never reach the socket
+pub(crate) struct Liveness {
+    last_received: Mutex<Instant>,
+    commands: mpsc::UnboundedSender<ZmqCommand>,
+}
+
+impl Liveness {
+    pub(crate) fn new() -> (Arc<Self>, mpsc::UnboundedReceiver<ZmqCommand>) {
+        let (commands, receiver) = mpsc::unbounded();
+        let liveness = Self {
+            last_received: Mutex::new(Instant::now()),
+            commands,
+        };
+        (Arc::new(liveness), receiver)
+    }
+
+    fn received(&self) {
+        *self.last_received.lock() = Instant::now();
+    }
+
+    pub(crate) fn last_received(&self) -> Instant {
+        *self.last_received.lock()
+    }
+
+    /// Sends a command to the peer ahead of queued messages. Fails once the
+    /// connection is gone
+    pub(crate) fn send_command(&self, command: ZmqCommand) -> bool {
+        self.commands.unbounded_send(command).is_ok()
+    }
+}
+
+impl FramedIo {
+    pub(crate) fn into_queues(self, options: &SocketOptions) -> (ZmqRecvQueue, ZmqSendQueue) {
+        let Self {
+            read_half,
+            write_half,
+            close_notify,
+            liveness,
+            commands,
+            ..
+        } = self;
+        (
+            ZmqRecvQueue::new(read_half, close_notify, liveness, options.recv_hwm),
+            ZmqSendQueue::new(write_half, commands, options.send_hwm, options.linger),
+        )
+    }
+}
+
+/// mpsc channels hold `buffer + 1` messages with a single sender
+fn channel_buffer(hwm: usize) -> usize {
+    match hwm {
+        0 => usize::MAX >> 2,
+        hwm => hwm - 1,
+    }
+}
+
+fn disconnected() -> CodecError {
+    CodecError::Io(std::io::ErrorKind::BrokenPipe.into())
+}
+
+impl ZmqSendQueue {
+    fn new(
+        write_half: ZmqFramedWrite,
+        commands: mpsc::UnboundedReceiver<ZmqCommand>,
+        hwm: usize,
+        linger: Option<Duration>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(channel_buffer(hwm));
+        let (control, control_receiver) = mpsc::unbounded();
+        let (closed_sender, closed) = oneshot::channel();
+        async_rt::task::spawn(Self::write_loop(
+            write_half,
+            receiver,
+            control_receiver,
+            commands,
+            closed,
+            linger,
+        ));
+        Self {
+            sender,
+            control,
+            _closed: closed_sender,
+        }
+    }
+
+    /// Queues a message regardless of `SNDHWM`, ahead of the messages
+    /// already queued. Meant for subscriptions and other traffic the socket
+    /// can't afford to drop. Fails once the connection is gone
+    pub(crate) fn send_control(&self, message: Message) -> ZmqResult<()> {
+        self.control
+            .unbounded_send(message)
+            .map_err(|_| disconnected().into())
+    }
+
+    async fn write_loop(
+        mut write_half: ZmqFramedWrite,
+        mut receiver: mpsc::Receiver<Message>,
+        mut control: mpsc::UnboundedReceiver<Message>,
+        mut commands: mpsc::UnboundedReceiver<ZmqCommand>,
+        closed: oneshot::Receiver<()>,
+        linger: Option<Duration>,
+    ) {
+        let mut closed = closed.fuse();
+        loop {
+            futures::select! {
+                _ = closed => break,
+                command = commands.next() => {
+                    if let Some(command) = command {
+                        if write_half.send(Message::Command(command)).await.is_err() {
+                            return;
+                        }
+                    }
+                },
+                message = control.select_next_some() => {
+                    if write_half.send(message).await.is_err() {
+                        return;
+                    }
+                },
+                message = receiver.next() => match message {
+                    Some(message) => {
+                        if write_half.send(message).await.is_err() {
+                            return;
+                        }
+                    }
+                    None => break,
+                },
+            }
+        }
+        // The queue is gone, write out whatever is left
+        let drain = async {
+            while let Ok(message) = control.try_recv() {
+                if write_half.send(message).await.is_err() {
+                    return;
+                }
+            }
+            while let Some(message) = receiver.next().await {
+                if write_half.send(message).await.is_err() {
+                    return;
+                }
+            }
+            let _ = write_half.close().await;
+        };
+        match linger {
+            Some(linger) => {
+                futures::select! {
+                    _ = drain.fuse() => {},
+                    _ = async_rt::task::sleep(linger).fuse() => {},
+                }
+            }
+            None => drain.await,
+        }
+    }
+}
+
+impl Sink<Message> for ZmqSendQueue {
+    type Error = CodecError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.sender.poll_ready(cx).map_err(|_| disconnected())
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Message) -> Result<(), Self::Error> {
+        self.sender.start_send(item).map_err(|_| disconnected())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sender)
+            .poll_flush(cx)
+            .map_err(|_| disconnected())
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.sender)
+            .poll_close(cx)
+            .map_err(|_| disconnected())
+    }
+}
+
+impl TrySend for ZmqSendQueue {
+    fn try_send(mut self: Pin<&mut Self>, item: Message) -> ZmqResult<()> {
+        match self.sender.try_send(item) {
+            Ok(()) => Ok(()),
+            Err(e) if e.is_full() => Err(ZmqError::BufferFull("Send queue is full")),
+            Err(_) => Err(disconnected().into()),
+        }
+    }
+}
+
+impl ZmqRecvQueue {
+    fn new(
+        read_half: ZmqFramedRead,
+        close_notify: Option<oneshot::Sender<()>>,
+        liveness: Arc<Liveness>,
+        hwm: usize,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(channel_buffer(hwm));
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        async_rt::task::spawn(Self::read_loop(read_half, sender, liveness, stop_receiver));
+        Self {
+            receiver,
+            close_notify,
+            _recv_coro_stop: stop_sender,
+        }
+    }
+
+    async fn read_loop(
+        mut read_half: ZmqFramedRead,
+        mut sender: mpsc::Sender<CodecResult<Message>>,
+        liveness: Arc<Liveness>,
+        stop_receiver: oneshot::Receiver<()>,
+    ) {
+        let mut stop_receiver = stop_receiver.fuse();
+        // Set by the peer's PINGs, the connection is considered dead when
+        // nothing arrives for that long
+        let mut peer_ttl = None;
+        loop {
+            let expired = async move {
+                match peer_ttl {
+                    Some(ttl) => async_rt::task::sleep(ttl).await,
+                    None => futures::future::pending().await,
+                }
+            };
+            futures::select! {
+                _ = stop_receiver => break,
+                _ = expired.fuse() => {
+                    let timed_out = CodecError::Io(std::io::ErrorKind::TimedOut.into());
+                    let _ = sender.send(Err(timed_out)).await;
+                    break;
+                },
+                message = read_half.next().fuse() => match message {
+                    Some(Ok(Message::Command(command)))
+                        if command.name == ZmqCommandName::PING
+                            || command.name == ZmqCommandName::PONG =>
+                    {
+                        liveness.received();
+                        if command.name == ZmqCommandName::PING {
+                            let ttl = command.ping_ttl();
+                            peer_ttl = if ttl.as_millis() > 0 { Some(ttl) } else { None };
+                            liveness.send_command(ZmqCommand::pong(&command));
+                        }
+                    }
+                    Some(message) => {
+                        liveness.received();
+                        let failed = message.is_err();
+                        if sender.send(message).await.is_err() || failed {
+                            break;
+                        }
+                    }
+                    None => break,
+                },
+            }
+        }
+    }
+}
+
+impl Stream for ZmqRecvQueue {
+    type Item = CodecResult<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let item = self.receiver.poll_next_unpin(cx);
+        if let Poll::Ready(None) | Poll::Ready(Some(Err(_))) = &item {
+            if let Some(notify) = self.close_notify.take() {
+                let _ = notify.send(());
+            }
+        }
+        item
+    }
+}
@@ -1,12 +1,14 @@
 use super::command::ZmqCommand;
 use super::error::CodecError;
-use super::greeting::ZmqGreeting;
+use super::greeting::{GreetingPart, ZmqGreeting, ZmtpVersion};
 use super::Message;
-use crate::ZmqMessage;
+use crate::security::{CurveDecoder, CurveEncoder};
+use crate::{PeerMetadata, ZmqMessage};
 
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use futures_codec::{Decoder, Encoder};
 use std::convert::TryFrom;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Copy)]
 struct Frame {
@@ -17,10 +19,19 @@ struct Frame {
 
 #[derive(Debug)]
 enum DecoderState {
-    Greeting,
+    Signature,
+    Major,
+    GreetingRest(u8),
     FrameHeader,
     FrameLen(Frame),
     Frame(Frame),
+    // ZMTP 1.0 frames: the length, counting the flags, comes first
+    V1FrameLen,
+    V1LongFrameLen,
+    V1Frame,
+    // No ZMTP framing at all, used by STREAM sockets to talk to plain TCP
+    // peers. Every chunk of bytes read is a single frame message
+    Raw,
 }
 
 #[derive(Debug)]
@@ -31,16 +42,118 @@ pub struct ZmqCodec {
     // This allows to incapsulate it's processing inside codec and not expose
     // internal details to higher levels
     buffered_message: Option<ZmqMessage>,
+    // Largest message frame accepted from the peer
+    max_msg_size: Option<usize>,
+    // Set once a CURVE handshake is done, frames are then carried in
+    // encrypted MESSAGE commands
+    encoder: Option<CurveEncoder>,
+    decoder: Option<CurveDecoder>,
+    // Decides the framing. Found out from the greeting when decoding, set
+    // once it is exchanged when encoding
+    zmtp_version: ZmtpVersion,
+    // Attached to every message received once the handshake is done
+    metadata: Option<Arc<PeerMetadata>>,
 }
 
 impl ZmqCodec {
     pub fn new() -> Self {
         Self {
-            state: DecoderState::Greeting,
-            waiting_for: 64, // len of the greeting frame,
+            state: DecoderState::Signature,
+            waiting_for: 1, // ZMTP 1.0 peers may send less than a signature
             buffered_message: None,
+            max_msg_size: None,
+            encoder: None,
+            decoder: None,
+            zmtp_version: ZmqGreeting::default().version,
+            metadata: None,
         }
     }
+
+    pub(crate) fn set_max_msg_size(&mut self, max_msg_size: Option<usize>) {
+        self.max_msg_size = max_msg_size;
+    }
+
+    /// Encrypts frames sent from now on
+    pub(crate) fn set_encoder(&mut self, encoder: CurveEncoder) {
+        self.encoder = Some(encoder);
+    }
+
+    /// Decrypts frames received from now on
+    pub(crate) fn set_decoder(&mut self, decoder: CurveDecoder) {
+        self.decoder = Some(decoder);
+    }
+
+    /// Attaches the peer's metadata to messages received from now on
+    pub(crate) fn set_metadata(&mut self, metadata: PeerMetadata) {
+        self.metadata = Some(Arc::new(metadata));
+    }
+
+    pub(crate) fn metadata(&self) -> Option<Arc<PeerMetadata>> {
+        self.metadata.clone()
+    }
+
+    /// Switches to the framing of `version`, ZMTP 1.0 has its own. Only
+    /// needed for encoding, the decoder switches once it read the greeting
+    pub(crate) fn set_zmtp_version(&mut self, version: ZmtpVersion) {
+        self.zmtp_version = version;
+        self.state = if version.0 == 1 {
+            DecoderState::V1FrameLen
+        } else {
+            DecoderState::FrameHeader
+        };
+        self.waiting_for = 1;
+    }
+
+    /// Adds a frame to the message being received, returning the message
+    /// once it is complete
+    fn push_frame(
+        &mut self,
+        data: BytesMut,
+        more: bool,
+        src: &mut BytesMut,
+    ) -> Result<Option<Message>, CodecError> {
+        match &mut self.buffered_message {
+            Some(v) => v.push_back(data.freeze()),
+            None => self.buffered_message = Some(ZmqMessage::from(data.freeze())),
+        }
+
+        if more {
+            self.decode(src)
+        } else {
+            // Quoth the Raven “Nevermore.”
+            let mut message = self
+                .buffered_message
+                .take()
+                .expect("Corrupted decoder state");
+            message.set_metadata(self.metadata.clone());
+            Ok(Some(Message::Message(message)))
+        }
+    }
+
+    /// Waits for a ZMTP 1.0 frame of `len` bytes, flags included
+    fn start_v1_frame(&mut self, len: usize) -> Result<(), CodecError> {
+        if len == 0 {
+            return Err(CodecError::Decode("Frame length must include the flags"));
+        }
+        match self.max_msg_size {
+            Some(max) if len - 1 > max => {
+                Err(CodecError::Decode("Message frame exceeds max_msg_size"))
+            }
+            _ => {
+                self.state = DecoderState::V1Frame;
+                self.waiting_for = len;
+                Ok(())
+            }
+        }
+    }
+
+    /// Switches the codec to raw mode: incoming bytes are passed through as
+    /// single frame messages and outgoing frames are written as is
+    pub(crate) fn set_raw(&mut self) {
+        self.state = DecoderState::Raw;
+        self.waiting_for = 1;
+        self.buffered_message = None;
+    }
 }
 
 impl Default for ZmqCodec {
@@ -59,15 +172,38 @@ impl Decoder for ZmqCodec {
             return Ok(None);
         }
         match self.state {
-            DecoderState::Greeting => {
-                if src[0] != 0xff {
-                    return Err(CodecError::Decode("Bad first byte of greeting"));
+            DecoderState::Signature => {
+                if src[0] == 0xff && src.len() < 10 {
+                    self.waiting_for = 10;
+                    return self.decode(src);
                 }
-                self.state = DecoderState::FrameHeader;
+                // Either a short identity frame or a long one, whose flags
+                // take the place of the 0x7f ending signatures
+                if src[0] != 0xff || src[9] & 0x01 == 0 {
+                    self.set_zmtp_version((1, 0));
+                    return Ok(Some(Message::Greeting(GreetingPart::Unversioned)));
+                }
+                let mut signature = src.split_to(10);
+                signature.advance(1);
+                let identity_len = (signature.get_u64() as usize).saturating_sub(1);
+                self.state = DecoderState::Major;
                 self.waiting_for = 1;
-                Ok(Some(Message::Greeting(ZmqGreeting::try_from(
-                    src.split_to(64).freeze(),
-                )?)))
+                Ok(Some(Message::Greeting(GreetingPart::Signature {
+                    identity_len,
+                })))
+            }
+            DecoderState::Major => {
+                let major = src.get_u8();
+                self.state = DecoderState::GreetingRest(major);
+                self.waiting_for = GreetingPart::rest_len(major);
+                Ok(Some(Message::Greeting(GreetingPart::Major(major))))
+            }
+            DecoderState::GreetingRest(major) => {
+                let part = GreetingPart::parse_rest(major, src.split_to(self.waiting_for))?;
+                if let GreetingPart::Rest(greeting) = &part {
+                    self.set_zmtp_version(greeting.version);
+                }
+                Ok(Some(Message::Greeting(part)))
             }
             DecoderState::FrameHeader => {
                 let flags = src.get_u8();
@@ -88,32 +224,66 @@ impl Decoder for ZmqCodec {
                 } else {
                     src.get_u8() as usize
                 };
-                self.decode(src)
+                match self.max_msg_size {
+                    // Encrypted frames are checked once decrypted
+                    Some(max)
+                        if !frame.command && self.decoder.is_none() && self.waiting_for > max =>
+                    {
+                        Err(CodecError::Decode("Message frame exceeds max_msg_size"))
+                    }
+                    _ => self.decode(src),
+                }
+            }
+            DecoderState::Raw => {
+                let data = src.split_to(src.len());
+                let mut message = ZmqMessage::from(data.freeze());
+                message.set_metadata(self.metadata.clone());
+                Ok(Some(Message::Message(message)))
             }
             DecoderState::Frame(frame) => {
-                let data = src.split_to(self.waiting_for);
+                let mut data = src.split_to(self.waiting_for);
+                let mut frame = frame;
                 self.state = DecoderState::FrameHeader;
                 self.waiting_for = 1;
+                if let (Some(decoder), false) = (&mut self.decoder, frame.command) {
+                    let (flags, plaintext) = decoder.decrypt(&data)?;
+                    frame.command = flags & 0b0000_0010 != 0;
+                    frame.more = flags & 0b0000_0001 != 0;
+                    data = plaintext;
+                    match self.max_msg_size {
+                        Some(max) if !frame.command && data.len() > max => {
+                            return Err(CodecError::Decode("Message frame exceeds max_msg_size"));
+                        }
+                        _ => {}
+                    }
+                }
                 if frame.command {
                     return Ok(Some(Message::Command(ZmqCommand::try_from(data)?)));
                 }
 
-                // process incoming message frame
-                match &mut self.buffered_message {
-                    Some(v) => v.push_back(data.freeze()),
-                    None => self.buffered_message = Some(ZmqMessage::from(data.freeze())),
-                }
-
-                if frame.more {
-                    self.decode(src)
-                } else {
-                    // Quoth the Raven “Nevermore.”
-                    Ok(Some(Message::Message(
-                        self.buffered_message
-                            .take()
-                            .expect("Corrupted decoder state"),
-                    )))
+                self.push_frame(data, frame.more, src)
+            }
+            DecoderState::V1FrameLen => {
+                match src.get_u8() {
+                    0xff => {
+                        self.state = DecoderState::V1LongFrameLen;
+                        self.waiting_for = 8;
+                    }
+                    len => self.start_v1_frame(len as usize)?,
                 }
+                self.decode(src)
+            }
+            DecoderState::V1LongFrameLen => {
+                let len = src.get_u64() as usize;
+                self.start_v1_frame(len)?;
+                self.decode(src)
+            }
+            DecoderState::V1Frame => {
+                let mut data = src.split_to(self.waiting_for);
+                let more = data.get_u8() & 0b0000_0001 != 0;
+                self.state = DecoderState::V1FrameLen;
+                self.waiting_for = 1;
+                self.push_frame(data, more, src)
             }
         }
     }
@@ -140,6 +310,21 @@ impl ZmqCodec {
         }
         dst.extend_from_slice(frame.as_ref());
     }
+
+    fn encode_v1_frame(&mut self, frame: &Bytes, dst: &mut BytesMut, more: bool) {
+        // The length counts the flags
+        let len = frame.len() + 1;
+        if len >= 255 {
+            dst.reserve(len + 9);
+            dst.put_u8(0xff);
+            dst.put_u64(len as u64);
+        } else {
+            dst.reserve(len + 1);
+            dst.put_u8(len as u8);
+        }
+        dst.put_u8(more.into());
+        dst.extend_from_slice(frame.as_ref());
+    }
 }
 
 impl Encoder for ZmqCodec {
@@ -147,15 +332,61 @@ impl Encoder for ZmqCodec {
     type Item = Message;
 
     fn encode(&mut self, message: Self::Item, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        match message {
-            Message::Greeting(payload) => dst.unsplit(payload.into()),
-            Message::Command(command) => dst.unsplit(command.into()),
-            Message::Message(message) => {
+        if let DecoderState::Raw = self.state {
+            return match message {
+                Message::Message(message) => {
+                    for part in message.iter() {
+                        dst.extend_from_slice(part.as_ref());
+                    }
+                    Ok(())
+                }
+                _ => Err(CodecError::Other("Only raw data can be sent in raw mode")),
+            };
+        }
+        if self.zmtp_version.0 < 3 {
+            // No commands, nor security, before ZMTP 3.0
+            return match message {
+                Message::Greeting(part) => part.write_to(dst),
+                Message::Message(message) => {
+                    let last_element = message.len() - 1;
+                    for (idx, part) in message.iter().enumerate() {
+                        if self.zmtp_version.0 == 1 {
+                            self.encode_v1_frame(part, dst, idx != last_element);
+                        } else {
+                            self._encode_frame(part, dst, idx != last_element);
+                        }
+                    }
+                    Ok(())
+                }
+                Message::Command(_) => Err(CodecError::Other("Commands need ZMTP 3.0")),
+            };
+        }
+        match (message, self.encoder.as_mut()) {
+            (Message::Greeting(part), _) => part.write_to(dst)?,
+            (Message::Command(command), None) => dst.unsplit(command.into()),
+            (Message::Command(command), Some(encoder)) => {
+                let frame = encoder.encrypt(0b0000_0010, &command.to_bytes());
+                self._encode_frame(&frame, dst, false);
+            }
+            (Message::Message(message), None) => {
                 let last_element = message.len() - 1;
                 for (idx, part) in message.iter().enumerate() {
                     self._encode_frame(part, dst, idx != last_element);
                 }
             }
+            (Message::Message(message), Some(encoder)) => {
+                // Like libzmq, MESSAGE commands are sent as single frames,
+                // the MORE flag is part of the encrypted content
+                let last_element = message.len() - 1;
+                let frames: Vec<Bytes> = message
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, part)| encoder.encrypt((idx != last_element) as u8, part))
+                    .collect();
+                for frame in frames.iter() {
+                    self._encode_frame(frame, dst, false);
+                }
+            }
         }
         Ok(())
     }
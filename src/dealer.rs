@@ -1,11 +1,11 @@
 use crate::backend::GenericSocketBackend;
-use crate::codec::{Message, ZmqFramedRead};
+use crate::codec::{Message, ZmqRecvQueue};
 use crate::fair_queue::FairQueue;
 use crate::transport::AcceptStopHandle;
-use crate::util::PeerIdentity;
+use crate::util::{ConnectHandle, PeerIdentity};
 use crate::{
-    Endpoint, MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketRecv, SocketSend,
-    SocketType, ZmqMessage, ZmqResult,
+    Endpoint, MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketOptions, SocketRecv,
+    SocketSend, SocketType, ZmqError, ZmqMessage, ZmqResult,
 };
 use async_trait::async_trait;
 use futures::channel::mpsc;
@@ -16,8 +16,9 @@ use std::sync::Arc;
 
 pub struct DealerSocket {
     backend: Arc<GenericSocketBackend>,
-    fair_queue: FairQueue<ZmqFramedRead, PeerIdentity>,
+    fair_queue: FairQueue<ZmqRecvQueue, PeerIdentity>,
     binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
 }
 
 impl Drop for DealerSocket {
@@ -28,15 +29,17 @@ impl Drop for DealerSocket {
 
 #[async_trait]
 impl Socket for DealerSocket {
-    fn new() -> Self {
+    fn with_options(options: SocketOptions) -> Self {
         let fair_queue = FairQueue::new(true);
         Self {
             backend: Arc::new(GenericSocketBackend::new(
                 Some(fair_queue.inner()),
                 SocketType::DEALER,
+                options,
             )),
             fair_queue,
             binds: HashMap::new(),
+            connections: HashMap::new(),
         }
     }
 
@@ -48,6 +51,10 @@ impl Socket for DealerSocket {
         &mut self.binds
     }
 
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
     fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
         let (sender, receiver) = mpsc::channel(1024);
         self.backend.socket_monitor.lock().replace(sender);
@@ -63,8 +70,12 @@ impl SocketRecv for DealerSocket {
                 Some((_peer_id, Ok(Message::Message(message)))) => {
                     return Ok(message);
                 }
-                Some((_peer_id, _)) => todo!(),
-                None => todo!(),
+                Some((_peer_id, Ok(_))) => {}
+                Some((peer_id, Err(e))) => {
+                    log::debug!("DEALER peer {:?} failed: {}", peer_id, e);
+                    self.backend.peer_disconnected(&peer_id);
+                }
+                None => return Err(ZmqError::NoMessage),
             };
         }
     }
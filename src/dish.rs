@@ -0,0 +1,200 @@
+use crate::backend::GenericSocketBackend;
+use crate::codec::*;
+use crate::endpoint::Endpoint;
+use crate::error::{ZmqError, ZmqResult};
+use crate::fair_queue::{FairQueue, QueueInner};
+use crate::message::*;
+use crate::radio::check_group;
+use crate::transport::AcceptStopHandle;
+use crate::util::{ConnectHandle, PeerIdentity};
+use crate::{
+    MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketOptions, SocketRecv, SocketType,
+};
+
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Remembers the groups joined so far, so that they can be replayed to
+/// radios connecting later on
+pub(crate) struct DishSocketBackend {
+    pub(crate) inner: GenericSocketBackend,
+    groups: Mutex<HashSet<String>>,
+}
+
+impl DishSocketBackend {
+    fn new(
+        fair_queue_inner: Arc<Mutex<QueueInner<ZmqRecvQueue, PeerIdentity>>>,
+        options: SocketOptions,
+    ) -> Self {
+        Self {
+            inner: GenericSocketBackend::new(Some(fair_queue_inner), SocketType::DISH, options),
+            groups: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Records the group as joined or left and sends the matching command to
+    /// every connected radio
+    async fn membership(&self, join: bool, group: &str) -> ZmqResult<()> {
+        check_group(group)?;
+        {
+            let mut groups = self.groups.lock();
+            if join && !groups.insert(group.to_owned()) {
+                return Err(ZmqError::Socket("Group already joined"));
+            }
+            if !join && !groups.remove(group) {
+                return Err(ZmqError::Socket("Group not joined"));
+            }
+        }
+        // Like subscriptions, JOIN and LEAVE commands bypass the send HWM
+        let mut dead_peers = Vec::new();
+        for peer in self.inner.peers.iter() {
+            let command = ZmqCommand::membership(join, group.as_bytes());
+            if let Err(e) = peer.send_queue.send_control(Message::Command(command)) {
+                log::debug!("Failed to send to peer {:?}: {}", peer.key(), e);
+                dead_peers.push(peer.key().clone());
+            }
+        }
+        for peer_id in dead_peers {
+            self.inner.peer_disconnected(&peer_id);
+        }
+        Ok(())
+    }
+
+    /// Turns a `[group, body]` message into its body, with the group set.
+    /// Radios over UDP send every group, so messages are filtered here too
+    fn delivered(&self, mut message: ZmqMessage) -> Option<ZmqMessage> {
+        if message.len() != 2 {
+            return None;
+        }
+        let group = message.pop_front()?;
+        let group = std::str::from_utf8(&group).ok()?;
+        if !self.groups.lock().contains(group) {
+            return None;
+        }
+        message.set_group(group);
+        Some(message)
+    }
+}
+
+impl SocketBackend for DishSocketBackend {
+    fn socket_type(&self) -> SocketType {
+        self.inner.socket_type()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+
+    fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>> {
+        self.inner.monitor()
+    }
+
+    fn socket_options(&self) -> &SocketOptions {
+        self.inner.socket_options()
+    }
+}
+
+impl MultiPeerBackend for DishSocketBackend {
+    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) -> ZmqResult<()> {
+        self.inner.add_peer(peer_id, io)?;
+        if let Some(peer) = self.inner.peers.get(peer_id) {
+            for group in self.groups.lock().iter() {
+                let command = ZmqCommand::membership(true, group.as_bytes());
+                peer.send_queue.send_control(Message::Command(command))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn peer_disconnected(&self, peer_id: &PeerIdentity) {
+        self.inner.peer_disconnected(peer_id)
+    }
+
+    fn connection_lost(&self, peer_id: &PeerIdentity, connection: ConnectionId) {
+        self.inner.connection_lost(peer_id, connection)
+    }
+}
+
+/// Receives the messages RADIO sockets send to the groups it joined, with
+/// [`ZmqMessage::group`] set. Besides TCP and the other connected
+/// transports, DISH sockets can bind to `udp://` endpoints, joining the
+/// multicast group if the address is one
+pub struct DishSocket {
+    backend: Arc<DishSocketBackend>,
+    fair_queue: FairQueue<ZmqRecvQueue, PeerIdentity>,
+    binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
+}
+
+impl Drop for DishSocket {
+    fn drop(&mut self) {
+        self.backend.shutdown()
+    }
+}
+
+impl DishSocket {
+    /// Joins the group. Radios that connect later on are told as well
+    pub async fn join(&mut self, group: &str) -> ZmqResult<()> {
+        self.backend.membership(true, group).await
+    }
+
+    pub async fn leave(&mut self, group: &str) -> ZmqResult<()> {
+        self.backend.membership(false, group).await
+    }
+}
+
+#[async_trait]
+impl Socket for DishSocket {
+    fn with_options(options: SocketOptions) -> Self {
+        let fair_queue = FairQueue::new(true);
+        Self {
+            backend: Arc::new(DishSocketBackend::new(fair_queue.inner(), options)),
+            fair_queue,
+            binds: HashMap::new(),
+            connections: HashMap::new(),
+        }
+    }
+
+    fn backend(&self) -> Arc<dyn MultiPeerBackend> {
+        self.backend.clone()
+    }
+
+    fn binds(&mut self) -> &mut HashMap<Endpoint, AcceptStopHandle> {
+        &mut self.binds
+    }
+
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
+    fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.inner.socket_monitor.lock().replace(sender);
+        receiver
+    }
+}
+
+#[async_trait]
+impl SocketRecv for DishSocket {
+    async fn recv(&mut self) -> ZmqResult<ZmqMessage> {
+        loop {
+            match self.fair_queue.next().await {
+                Some((_peer_id, Ok(Message::Message(message)))) => {
+                    if let Some(message) = self.backend.delivered(message) {
+                        return Ok(message);
+                    }
+                }
+                Some((_peer_id, Ok(_))) => {}
+                Some((peer_id, Err(e))) => {
+                    log::debug!("DISH peer {:?} failed: {}", peer_id, e);
+                    self.backend.peer_disconnected(&peer_id);
+                }
+                None => return Err(ZmqError::NoMessage),
+            }
+        }
+    }
+}
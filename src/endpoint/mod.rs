@@ -34,6 +34,17 @@ pub enum Endpoint {
     // TODO: Add endpoints for the other transport variants
     Tcp(Host, Port),
     Ipc(Option<PathBuf>),
+    /// Name shared by sockets of the same process
+    Inproc(String),
+    /// TCP connection secured with TLS
+    TlsTcp(Host, Port),
+    /// WebSocket URL, the path starts with `/`
+    Ws(Host, Port, String),
+    /// WebSocket URL of a server using TLS
+    Wss(Host, Port, String),
+    /// UDP address, which may be a multicast group. Only carries RADIO and
+    /// DISH sockets
+    Udp(Host, Port),
 }
 
 impl Endpoint {
@@ -41,6 +52,11 @@ impl Endpoint {
         match self {
             Self::Tcp(_, _) => Transport::Tcp,
             Self::Ipc(_) => Transport::Ipc,
+            Self::Inproc(_) => Transport::Inproc,
+            Self::TlsTcp(..) => Transport::TlsTcp,
+            Self::Ws(..) => Transport::Ws,
+            Self::Wss(..) => Transport::Wss,
+            Self::Udp(..) => Transport::Udp,
         }
     }
 
@@ -59,7 +75,7 @@ impl FromStr for Endpoint {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         lazy_static! {
-            static ref TRANSPORT_REGEX: Regex = Regex::new(r"^([[:lower:]]+)://(.+)$").unwrap();
+            static ref TRANSPORT_REGEX: Regex = Regex::new(r"^([[:lower:]+]+)://(.+)$").unwrap();
             static ref HOST_PORT_REGEX: Regex = Regex::new(r"^(.+):(\d+)$").unwrap();
         }
 
@@ -84,15 +100,41 @@ impl FromStr for Endpoint {
             Ok((host, port))
         }
 
+        fn extract_ws_url(address: &str) -> Result<(Host, Port, String), EndpointError> {
+            let (host_port, path) = match address.find('/') {
+                Some(i) => address.split_at(i),
+                None => (address, "/"),
+            };
+            let (host, port) = extract_host_port(host_port)?;
+            Ok((host, port, path.to_string()))
+        }
+
         let endpoint = match transport {
             Transport::Tcp => {
                 let (host, port) = extract_host_port(address)?;
                 Endpoint::Tcp(host, port)
             }
+            Transport::TlsTcp => {
+                let (host, port) = extract_host_port(address)?;
+                Endpoint::TlsTcp(host, port)
+            }
             Transport::Ipc => {
                 let path: PathBuf = address.to_string().into();
                 Endpoint::Ipc(Some(path))
             }
+            Transport::Inproc => Endpoint::Inproc(address.to_string()),
+            Transport::Ws => {
+                let (host, port, path) = extract_ws_url(address)?;
+                Endpoint::Ws(host, port, path)
+            }
+            Transport::Wss => {
+                let (host, port, path) = extract_ws_url(address)?;
+                Endpoint::Wss(host, port, path)
+            }
+            Transport::Udp => {
+                let (host, port) = extract_host_port(address)?;
+                Endpoint::Udp(host, port)
+            }
         };
 
         Ok(endpoint)
@@ -109,8 +151,31 @@ impl fmt::Display for Endpoint {
                     write!(f, "tcp://{}:{}", host, port)
                 }
             }
+            Endpoint::TlsTcp(host, port) => {
+                if let Host::Ipv6(_) = host {
+                    write!(f, "tls+tcp://[{}]:{}", host, port)
+                } else {
+                    write!(f, "tls+tcp://{}:{}", host, port)
+                }
+            }
+            Endpoint::Udp(host, port) => {
+                if let Host::Ipv6(_) = host {
+                    write!(f, "udp://[{}]:{}", host, port)
+                } else {
+                    write!(f, "udp://{}:{}", host, port)
+                }
+            }
             Endpoint::Ipc(Some(path)) => write!(f, "ipc://{}", path.display()),
             Endpoint::Ipc(None) => write!(f, "ipc://????"),
+            Endpoint::Inproc(name) => write!(f, "inproc://{}", name),
+            Endpoint::Ws(host, port, path) | Endpoint::Wss(host, port, path) => {
+                let transport = self.transport();
+                if let Host::Ipv6(_) = host {
+                    write!(f, "{}://[{}]:{}{}", transport, host, port, path)
+                } else {
+                    write!(f, "{}://{}:{}{}", transport, host, port, path)
+                }
+            }
         }
     }
 }
@@ -165,6 +230,34 @@ mod tests {
                 Endpoint::Ipc(Some(PathBuf::from("@abstract/namespace"))),
                 "ipc://@abstract/namespace"
             ),
+            (
+                Endpoint::Inproc("my-service".to_string()),
+                "inproc://my-service"
+            ),
+            (
+                Endpoint::TlsTcp(Host::Domain("broker.example.com".to_string()), 5555),
+                "tls+tcp://broker.example.com:5555"
+            ),
+            (
+                Endpoint::Ws(
+                    Host::Ipv4("127.0.0.1".parse().unwrap()),
+                    8080,
+                    "/zmq/feed".to_string()
+                ),
+                "ws://127.0.0.1:8080/zmq/feed"
+            ),
+            (
+                Endpoint::Wss(Host::Ipv6("::1".parse().unwrap()), 443, "/".to_string()),
+                "wss://[::1]:443/"
+            ),
+            (
+                Endpoint::Udp(Host::Ipv4("239.0.0.1".parse().unwrap()), 5555),
+                "udp://239.0.0.1:5555"
+            ),
+            (
+                Endpoint::Udp(Host::Ipv6("ff02::1".parse().unwrap()), 5555),
+                "udp://[ff02::1]:5555"
+            ),
             (
                 Endpoint::Tcp(Host::Domain("www.example.com".to_string()), 1234),
                 "tcp://www.example.com:1234",
@@ -208,6 +301,14 @@ mod tests {
         assert_eq!(&format!("{}", Endpoint::Ipc(None)), "ipc://????");
     }
 
+    #[test]
+    fn test_ws_default_path() {
+        assert_eq!(
+            "ws://example.com:80".parse::<Endpoint>().unwrap(),
+            Endpoint::Ws(Host::Domain("example.com".to_string()), 80, "/".to_string())
+        );
+    }
+
     #[test]
     fn test_endpoint_parse() {
         use std::mem::discriminant as disc;
@@ -237,6 +338,7 @@ mod tests {
             ("tcp://127.0.0.1", EndpointError::Syntax("")),
             ("tcp://127.0.0.1:65536", EndpointError::Syntax("")),
             ("TCP://127.0.0.1:1234", EndpointError::Syntax("")),
+            ("ws://127.0.0.1/path", EndpointError::Syntax("")),
         ];
 
         for (s, target_variant) in inexact_counter_examples {
@@ -10,7 +10,17 @@ use super::EndpointError;
 pub enum Transport {
     /// TCP transport
     Tcp,
+    /// TCP transport secured with TLS
+    TlsTcp,
     Ipc,
+    /// In-process transport, between sockets of the same process
+    Inproc,
+    /// ZMTP over WebSocket, as specified by ZWS 2.0
+    Ws,
+    /// ZMTP over WebSocket over TLS
+    Wss,
+    /// Unreliable datagrams between RADIO and DISH sockets
+    Udp,
 }
 
 impl FromStr for Transport {
@@ -19,7 +29,12 @@ impl FromStr for Transport {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let result = match s {
             "tcp" => Transport::Tcp,
+            "tls+tcp" => Transport::TlsTcp,
             "ipc" => Transport::Ipc,
+            "inproc" => Transport::Inproc,
+            "ws" => Transport::Ws,
+            "wss" => Transport::Wss,
+            "udp" => Transport::Udp,
             _ => return Err(EndpointError::UnknownTransport(s.to_string())),
         };
         Ok(result)
@@ -37,7 +52,12 @@ impl fmt::Display for Transport {
     fn fmt(&self, f: &mut fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
         let s = match self {
             Transport::Tcp => "tcp",
+            Transport::TlsTcp => "tls+tcp",
             Transport::Ipc => "ipc",
+            Transport::Inproc => "inproc",
+            Transport::Ws => "ws",
+            Transport::Wss => "wss",
+            Transport::Udp => "udp",
         };
         write!(f, "{}", s)
     }
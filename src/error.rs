@@ -16,6 +16,8 @@ pub enum ZmqError {
     Network(#[from] std::io::Error),
     #[error("Socket bind doesn't exist: {0}")]
     NoSuchBind(Endpoint),
+    #[error("Socket connection doesn't exist: {0}")]
+    NoSuchConnection(Endpoint),
     #[error("Codec Error: {0}")]
     Codec(#[from] CodecError),
     #[error("Socket Error: {0}")]
@@ -42,8 +44,14 @@ pub enum ZmqError {
     Other(&'static str),
     #[error("No message received")]
     NoMessage,
+    #[error("Authentication failed: {0}")]
+    AuthenticationFailed(String),
     #[error("Unsupported ZMTP version")]
     UnsupportedVersion(ZmtpVersion),
+    /// Equivalent of `EHOSTUNREACH`: no peer has the identity the message
+    /// is addressed to. The message is returned as it was given
+    #[error("No peer to route the message to")]
+    HostUnreachable(ZmqMessage),
 }
 
 impl From<futures::channel::mpsc::TrySendError<Message>> for ZmqError {
@@ -22,10 +22,13 @@ impl<S, K: Clone + Eq + Hash> QueueInner<S, K> {
             priority: self.counter.fetch_add(1, atomic::Ordering::Relaxed),
             key: k,
         });
-        match &self.waker {
-            Some(w) => w.wake_by_ref(),
-            None => (),
-        };
+        if let Some(w) = &self.waker {
+            w.wake_by_ref();
+        }
+    }
+
+    pub fn remove(&mut self, k: &K) {
+        self.streams.remove(k);
     }
 }
 
@@ -49,7 +52,7 @@ impl<K: Clone> Eq for ReadyEvent<K> {}
 
 impl<K: Clone> PartialOrd for ReadyEvent<K> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        other.priority.partial_cmp(&self.priority)
+        Some(self.cmp(other))
     }
 }
 impl<K: Clone> Ord for ReadyEvent<K> {
@@ -80,8 +83,8 @@ where
 impl<S, T, K> Stream for FairQueue<S, K>
 where
     T: Send,
-    S: Stream<Item = T> + Send,
-    K: Eq + Hash + Unpin + Clone + Send + Sync,
+    S: Stream<Item = T> + Send + 'static,
+    K: Eq + Hash + Unpin + Clone + Send + Sync + 'static,
 {
     type Item = (K, T);
 
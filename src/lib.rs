@@ -4,20 +4,30 @@ mod async_rt;
 mod backend;
 mod codec;
 mod dealer;
+mod dish;
 mod endpoint;
 mod error;
 mod fair_queue;
 mod message;
+mod metadata;
+mod options;
+mod pair;
 mod r#pub;
 mod pull;
 mod push;
+mod radio;
 mod rep;
 mod req;
 mod router;
+mod security;
+mod stream;
 mod sub;
 mod task_handle;
 mod transport;
+mod trie;
 pub mod util;
+mod xpub;
+mod xsub;
 
 #[doc(hidden)]
 pub mod __async_rt {
@@ -26,27 +36,38 @@ pub mod __async_rt {
 }
 
 pub use crate::dealer::*;
+pub use crate::dish::*;
 pub use crate::endpoint::{Endpoint, Host, Transport, TryIntoEndpoint};
 pub use crate::error::{ZmqError, ZmqResult};
+pub use crate::metadata::PeerMetadata;
+pub use crate::options::{SocketOptions, SocketOptionsBuilder};
+pub use crate::pair::*;
 pub use crate::pull::*;
 pub use crate::push::*;
 pub use crate::r#pub::*;
+pub use crate::radio::*;
 pub use crate::rep::*;
 pub use crate::req::*;
 pub use crate::router::*;
+pub use crate::security::{
+    z85, CurveKeyPair, PlainValidator, ZapAuthenticator, ZapCredentials, ZapHandler, ZapReply,
+    ZapRequest, ZapStatus,
+};
+pub use crate::stream::*;
 pub use crate::sub::*;
+pub use crate::xpub::*;
+pub use crate::xsub::*;
 pub use message::*;
 
 use crate::codec::*;
 use crate::transport::AcceptStopHandle;
-use util::PeerIdentity;
+use util::{ConnectHandle, PeerIdentity};
 
 #[macro_use]
 extern crate enum_primitive_derive;
 
 use async_trait::async_trait;
 use futures::channel::mpsc;
-use futures_codec::FramedWrite;
 use num_traits::ToPrimitive;
 use parking_lot::Mutex;
 use std::collections::HashMap;
@@ -68,6 +89,8 @@ pub enum SocketType {
     XPUB = 9,
     XSUB = 10,
     STREAM = 11,
+    RADIO = 14,
+    DISH = 15,
 }
 
 impl TryFrom<&str> for SocketType {
@@ -87,6 +110,8 @@ impl TryFrom<&str> for SocketType {
             "XPUB" => SocketType::XPUB,
             "XSUB" => SocketType::XSUB,
             "STREAM" => SocketType::STREAM,
+            "RADIO" => SocketType::RADIO,
+            "DISH" => SocketType::DISH,
             _ => return Err(ZmqError::Other("Unknown socket type")),
         })
     }
@@ -107,6 +132,8 @@ impl Display for SocketType {
             SocketType::XPUB => write!(f, "XPUB"),
             SocketType::XSUB => write!(f, "XSUB"),
             SocketType::STREAM => write!(f, "STREAM"),
+            SocketType::RADIO => write!(f, "RADIO"),
+            SocketType::DISH => write!(f, "DISH"),
         }
     }
 }
@@ -119,6 +146,9 @@ pub enum SocketEvent {
     Listening(Endpoint),
     Accepted(Endpoint, PeerIdentity),
     AcceptFailed(ZmqError),
+    /// The security handshake with a peer completed, before it is reported
+    /// as [`SocketEvent::Connected`] or [`SocketEvent::Accepted`]
+    HandshakeSucceeded(PeerIdentity, PeerMetadata),
     Closed,
     CloseFailed,
     Disconnected(PeerIdentity),
@@ -127,15 +157,22 @@ pub enum SocketEvent {
 pub trait MultiPeerBackend: SocketBackend {
     /// This should not be public..
     /// Find a better way of doing this
-
-    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo);
+    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) -> ZmqResult<()>;
     fn peer_disconnected(&self, peer_id: &PeerIdentity);
+
+    /// Drops the peer if the given connection still serves it. Used when a
+    /// connection goes away on its own, a newer connection may have taken
+    /// over the peer's identity meanwhile
+    fn connection_lost(&self, peer_id: &PeerIdentity, _connection: ConnectionId) {
+        self.peer_disconnected(peer_id)
+    }
 }
 
 pub trait SocketBackend: Send + Sync {
     fn socket_type(&self) -> SocketType;
     fn shutdown(&self);
     fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>>;
+    fn socket_options(&self) -> &SocketOptions;
 }
 
 #[async_trait]
@@ -150,7 +187,11 @@ pub trait SocketSend {
 
 #[async_trait]
 pub trait Socket: Sized + Send {
-    fn new() -> Self;
+    fn new() -> Self {
+        Self::with_options(SocketOptions::default())
+    }
+
+    fn with_options(options: SocketOptions) -> Self;
 
     fn backend(&self) -> Arc<dyn MultiPeerBackend>;
 
@@ -161,6 +202,7 @@ pub trait Socket: Sized + Send {
     /// (port # resolved, for example).
     async fn bind(&mut self, endpoint: &str) -> ZmqResult<Endpoint> {
         let endpoint = endpoint.try_into()?;
+        util::check_transport(&endpoint, self.backend().socket_type(), true)?;
 
         let cloned_backend = self.backend();
         let cback = move |result| {
@@ -168,7 +210,8 @@ pub trait Socket: Sized + Send {
             async move {
                 let result = match result {
                     Ok((socket, endpoint)) => {
-                        match util::peer_connected(socket, cloned_backend.clone()).await {
+                        match util::peer_connected(socket, &endpoint, cloned_backend.clone()).await
+                        {
                             Ok(peer_id) => Ok((endpoint, peer_id)),
                             Err(e) => Err(e),
                         }
@@ -190,7 +233,8 @@ pub trait Socket: Sized + Send {
             }
         };
 
-        let (endpoint, stop_handle) = transport::begin_accept(endpoint, cback).await?;
+        let (endpoint, stop_handle) =
+            transport::begin_accept(endpoint, self.backend().socket_options(), cback).await?;
 
         if let Some(monitor) = self.backend().monitor().lock().as_mut() {
             let _ = monitor.try_send(SocketEvent::Listening(endpoint.clone()));
@@ -217,11 +261,7 @@ pub trait Socket: Sized + Send {
     /// Unbinds all bound endpoints, blocking until finished.
     async fn unbind_all(&mut self) -> Vec<ZmqError> {
         let mut errs = Vec::new();
-        let endpoints: Vec<_> = self
-            .binds()
-            .iter()
-            .map(|(endpoint, _)| endpoint.clone())
-            .collect();
+        let endpoints: Vec<_> = self.binds().keys().cloned().collect();
         for endpoint in endpoints {
             if let Err(err) = self.unbind(endpoint).await {
                 errs.push(err);
@@ -231,57 +271,76 @@ pub trait Socket: Sized + Send {
     }
 
     /// Connects to the given endpoint.
+    ///
+    /// Waits until the first connection is established. Whenever it is lost
+    /// afterwards, the socket reconnects in the background, as configured by
+    /// the reconnect intervals of its [`SocketOptions`].
+    ///
+    /// # Errors
+    /// Fails if the socket is connected to `endpoint` already, it has to be
+    /// disconnected from first.
     async fn connect(&mut self, endpoint: &str) -> ZmqResult<()> {
         let backend = self.backend();
-        let endpoint = endpoint.try_into()?;
-
-        let result = match util::connect_forever(endpoint).await {
-            Ok((socket, endpoint)) => match util::peer_connected(socket, backend).await {
-                Ok(peer_id) => Ok((endpoint, peer_id)),
-                Err(e) => Err(e),
-            },
-            Err(e) => Err(e),
-        };
-        match result {
-            Ok((endpoint, peer_id)) => {
-                if let Some(monitor) = self.backend().monitor().lock().as_mut() {
-                    let _ = monitor.try_send(SocketEvent::Connected(endpoint, peer_id));
-                }
-                Ok(())
-            }
-            Err(e) => Err(e),
+        let endpoint: Endpoint = endpoint.try_into()?;
+        util::check_transport(&endpoint, backend.socket_type(), false)?;
+        if self.connections().contains_key(&endpoint) {
+            return Err(ZmqError::Socket("Already connected to this endpoint"));
         }
+
+        let connection = util::connect_peer(&endpoint, backend.clone()).await?;
+        let handle = util::spawn_reconnect(endpoint.clone(), backend, connection);
+        self.connections().insert(endpoint, handle);
+        Ok(())
     }
 
+    /// Endpoints connected to with [`Socket::connect`] and not disconnected
+    /// from since, as passed to `connect`
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle>;
+
     /// Creates and setups new socket monitor
     ///
     /// Subsequent calls to this method each create a new monitor channel.
     /// Sender side of previous one is dropped.
     fn monitor(&mut self) -> mpsc::Receiver<SocketEvent>;
 
-    // TODO: async fn connections(&self) -> ?
-
-    /// Disconnects from the given endpoint, blocking until finished.
+    /// Disconnects from the given endpoint, blocking until finished. The
+    /// socket stops reconnecting to it.
     ///
     /// # Errors
     /// May give a `ZmqError::NoSuchConnection` if `endpoint` isn't connected.
     /// May also give any other zmq errors encountered when attempting to
     /// disconnect.
-    // TODO: async fn disconnect(&mut self, endpoint: impl TryIntoEndpoint + 'async_trait) ->
-    // ZmqResult<()>;
+    async fn disconnect(&mut self, endpoint: Endpoint) -> ZmqResult<()> {
+        let handle = self.connections().remove(&endpoint);
+        let handle = handle.ok_or(ZmqError::NoSuchConnection(endpoint))?;
+        if let Some((peer_id, connection)) = handle.0.shutdown().await? {
+            self.backend().connection_lost(&peer_id, connection);
+        }
+        Ok(())
+    }
 
-    /// Disconnects all connecttions, blocking until finished.
-    // TODO: async fn disconnect_all(&mut self) -> ZmqResult<()>;
+    /// Disconnects all connections, blocking until finished.
+    async fn disconnect_all(&mut self) -> Vec<ZmqError> {
+        let mut errs = Vec::new();
+        let endpoints: Vec<_> = self.connections().keys().cloned().collect();
+        for endpoint in endpoints {
+            if let Err(err) = self.disconnect(endpoint).await {
+                errs.push(err);
+            }
+        }
+        errs
+    }
 
-    /// Closes the socket, blocking until all associated binds are closed.
+    /// Closes the socket, blocking until all associated connections and binds
+    /// are closed.
     /// This is equivalent to `drop()`, but with the benefit of blocking until
     /// resources are released, and getting any underlying errors.
     ///
     /// Returns any encountered errors.
-    // TODO: Call disconnect_all() when added
     async fn close(mut self) -> Vec<ZmqError> {
-        // self.disconnect_all().await?;
-        self.unbind_all().await
+        let mut errs = self.disconnect_all().await;
+        errs.extend(self.unbind_all().await);
+        errs
     }
 }
 
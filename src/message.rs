@@ -1,7 +1,10 @@
+use crate::metadata::PeerMetadata;
+
 use bytes::Bytes;
 use std::collections::vec_deque::{Iter, VecDeque};
 use std::convert::{From, TryFrom};
 use std::fmt;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct ZmqEmptyMessageError;
@@ -15,6 +18,8 @@ impl fmt::Display for ZmqEmptyMessageError {
 #[derive(Debug, Clone)]
 pub struct ZmqMessage {
     frames: VecDeque<Bytes>,
+    metadata: Option<Arc<PeerMetadata>>,
+    group: Option<String>,
 }
 
 impl ZmqMessage {
@@ -46,6 +51,28 @@ impl ZmqMessage {
         self.frames.get(index)
     }
 
+    /// Metadata of the peer the message was received from, the equivalent
+    /// of `zmq_msg_gets`. `None` for messages built locally
+    pub fn metadata(&self) -> Option<&PeerMetadata> {
+        self.metadata.as_deref()
+    }
+
+    pub(crate) fn set_metadata(&mut self, metadata: Option<Arc<PeerMetadata>>) {
+        self.metadata = metadata;
+    }
+
+    /// Group the message is published to by a RADIO socket, or was received
+    /// through by a DISH one
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    /// Sets the group a RADIO socket publishes the message to. Group names
+    /// are up to 255 bytes long
+    pub fn set_group(&mut self, group: impl Into<String>) {
+        self.group = Some(group.into());
+    }
+
     pub fn into_vec(self) -> Vec<Bytes> {
         Vec::from(self.frames)
     }
@@ -61,7 +88,11 @@ impl TryFrom<Vec<Bytes>> for ZmqMessage {
         if v.is_empty() {
             Err(ZmqEmptyMessageError)
         } else {
-            Ok(Self { frames: v.into() })
+            Ok(Self {
+                frames: v.into(),
+                metadata: None,
+                group: None,
+            })
         }
     }
 }
@@ -72,7 +103,11 @@ impl TryFrom<VecDeque<Bytes>> for ZmqMessage {
         if v.is_empty() {
             Err(ZmqEmptyMessageError)
         } else {
-            Ok(Self { frames: v })
+            Ok(Self {
+                frames: v,
+                metadata: None,
+                group: None,
+            })
         }
     }
 }
@@ -81,6 +116,8 @@ impl From<Bytes> for ZmqMessage {
     fn from(b: Bytes) -> Self {
         Self {
             frames: vec![b].into(),
+            metadata: None,
+            group: None,
         }
     }
 }
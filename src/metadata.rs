@@ -0,0 +1,59 @@
+use bytes::Bytes;
+use std::collections::HashMap;
+
+/// Properties of a peer connection: the ones the peer sent in its READY
+/// command, such as `Socket-Type` or application defined `X-*` ones, the
+/// `User-Id` assigned by the [`crate::ZapHandler`] of the socket and the
+/// `Peer-Address` of TCP peers and the `Peer-Certificate-Subject` of TLS
+/// peers. Property names are case insensitive
+///
+/// Reported in [`crate::SocketEvent::HandshakeSucceeded`] and attached to
+/// the messages received from the peer, see [`crate::ZmqMessage::metadata`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeerMetadata {
+    properties: HashMap<String, Bytes>,
+}
+
+impl PeerMetadata {
+    pub fn get(&self, property: &str) -> Option<&Bytes> {
+        self.properties
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(property))
+            .map(|(_, value)| value)
+    }
+
+    /// Same as [`PeerMetadata::get`], for properties holding text
+    pub fn get_str(&self, property: &str) -> Option<&str> {
+        self.get(property)
+            .and_then(|value| std::str::from_utf8(value).ok())
+    }
+
+    /// User id the ZAP handler assigned to the peer, if any
+    pub fn user_id(&self) -> Option<&str> {
+        self.get_str("User-Id")
+    }
+
+    /// IP address of the peer, for TCP connections
+    pub fn peer_address(&self) -> Option<&str> {
+        self.get_str("Peer-Address")
+    }
+
+    /// Subject of the certificate the peer presented, for TLS connections,
+    /// such as `CN=worker-1, O=Example`
+    pub fn peer_certificate_subject(&self) -> Option<&str> {
+        self.get_str("Peer-Certificate-Subject")
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Bytes)> {
+        self.properties
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    pub(crate) fn insert(&mut self, property: impl Into<String>, value: impl Into<Bytes>) {
+        let property = property.into();
+        self.properties
+            .retain(|name, _| !name.eq_ignore_ascii_case(&property));
+        self.properties.insert(property, value.into());
+    }
+}
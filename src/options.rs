@@ -0,0 +1,615 @@
+use crate::error::{ZmqError, ZmqResult};
+use crate::message::ZmqMessage;
+use crate::metadata::PeerMetadata;
+use crate::security::{CurveKeyPair, PlainValidator, Security, SharedZapHandler, ZapHandler};
+use crate::util::PeerIdentity;
+
+use bytes::Bytes;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Settings applied to a socket when it is created with
+/// [`crate::Socket::with_options`]. Use [`SocketOptions::builder`] to create
+/// them, unset options keep libzmq's defaults.
+///
+/// # Examples
+/// ```
+/// # use zeromq::{SocketOptions, util::PeerIdentity};
+/// # use std::convert::TryInto;
+/// # use std::time::Duration;
+/// # fn main() -> Result<(), zeromq::ZmqError> {
+/// let identity: PeerIdentity = b"worker-1".to_vec().try_into()?;
+/// let options = SocketOptions::builder()
+///     .peer_identity(identity)
+///     .send_hwm(100)
+///     .reconnect_ivl(Duration::from_millis(50))
+///     .reconnect_ivl_max(Some(Duration::from_secs(5)))
+///     .build()?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SocketOptions {
+    pub(crate) peer_id: Option<PeerIdentity>,
+    pub(crate) send_hwm: usize,
+    pub(crate) recv_hwm: usize,
+    pub(crate) linger: Option<Duration>,
+    pub(crate) reconnect_ivl: Duration,
+    pub(crate) reconnect_ivl_max: Option<Duration>,
+    pub(crate) handshake_timeout: Option<Duration>,
+    pub(crate) heartbeat_ivl: Option<Duration>,
+    pub(crate) heartbeat_timeout: Option<Duration>,
+    pub(crate) heartbeat_ttl: Option<Duration>,
+    pub(crate) max_msg_size: Option<usize>,
+    pub(crate) router_mandatory: bool,
+    pub(crate) router_handover: bool,
+    pub(crate) probe_router: bool,
+    pub(crate) req_relaxed: bool,
+    pub(crate) req_correlate: bool,
+    pub(crate) xpub_verbose: bool,
+    pub(crate) xpub_verboser: bool,
+    pub(crate) xpub_manual: bool,
+    pub(crate) xpub_welcome_msg: Option<ZmqMessage>,
+    pub(crate) metadata: PeerMetadata,
+    pub(crate) security: Security,
+    pub(crate) zap_domain: String,
+    pub(crate) zap_handler: Option<SharedZapHandler>,
+    pub(crate) ipc_wildcard_dir: Option<PathBuf>,
+    #[cfg(feature = "tls-transport")]
+    pub(crate) tls_client: Option<Arc<futures_rustls::rustls::ClientConfig>>,
+    #[cfg(feature = "tls-transport")]
+    pub(crate) tls_server: Option<Arc<futures_rustls::rustls::ServerConfig>>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            peer_id: None,
+            send_hwm: 1000,
+            recv_hwm: 1000,
+            linger: None,
+            reconnect_ivl: Duration::from_millis(100),
+            reconnect_ivl_max: None,
+            handshake_timeout: Some(Duration::from_secs(30)),
+            heartbeat_ivl: None,
+            heartbeat_timeout: None,
+            heartbeat_ttl: None,
+            max_msg_size: None,
+            router_mandatory: false,
+            router_handover: false,
+            probe_router: false,
+            req_relaxed: false,
+            req_correlate: false,
+            xpub_verbose: false,
+            xpub_verboser: false,
+            xpub_manual: false,
+            xpub_welcome_msg: None,
+            metadata: PeerMetadata::default(),
+            security: Security::Null,
+            zap_domain: String::new(),
+            zap_handler: None,
+            ipc_wildcard_dir: None,
+            #[cfg(feature = "tls-transport")]
+            tls_client: None,
+            #[cfg(feature = "tls-transport")]
+            tls_server: None,
+        }
+    }
+}
+
+impl SocketOptions {
+    pub fn builder() -> SocketOptionsBuilder {
+        SocketOptionsBuilder {
+            options: SocketOptions::default(),
+            #[cfg(feature = "tls-transport")]
+            tls_certificate: None,
+            #[cfg(feature = "tls-transport")]
+            tls_ca: None,
+        }
+    }
+
+    pub fn peer_identity(&self) -> Option<&PeerIdentity> {
+        self.peer_id.as_ref()
+    }
+
+    pub fn send_hwm(&self) -> usize {
+        self.send_hwm
+    }
+
+    pub fn recv_hwm(&self) -> usize {
+        self.recv_hwm
+    }
+
+    pub fn linger(&self) -> Option<Duration> {
+        self.linger
+    }
+
+    pub fn reconnect_ivl(&self) -> Duration {
+        self.reconnect_ivl
+    }
+
+    pub fn reconnect_ivl_max(&self) -> Option<Duration> {
+        self.reconnect_ivl_max
+    }
+
+    pub fn handshake_timeout(&self) -> Option<Duration> {
+        self.handshake_timeout
+    }
+
+    pub fn heartbeat_ivl(&self) -> Option<Duration> {
+        self.heartbeat_ivl
+    }
+
+    /// The configured timeout, or `heartbeat_ivl` if unset
+    pub fn heartbeat_timeout(&self) -> Option<Duration> {
+        self.heartbeat_timeout.or(self.heartbeat_ivl)
+    }
+
+    pub fn heartbeat_ttl(&self) -> Option<Duration> {
+        self.heartbeat_ttl
+    }
+
+    pub fn max_msg_size(&self) -> Option<usize> {
+        self.max_msg_size
+    }
+
+    pub fn router_mandatory(&self) -> bool {
+        self.router_mandatory
+    }
+
+    pub fn router_handover(&self) -> bool {
+        self.router_handover
+    }
+
+    pub fn probe_router(&self) -> bool {
+        self.probe_router
+    }
+
+    pub fn req_relaxed(&self) -> bool {
+        self.req_relaxed
+    }
+
+    pub fn req_correlate(&self) -> bool {
+        self.req_correlate
+    }
+
+    pub fn xpub_verbose(&self) -> bool {
+        self.xpub_verbose
+    }
+
+    pub fn xpub_verboser(&self) -> bool {
+        self.xpub_verboser
+    }
+
+    pub fn xpub_manual(&self) -> bool {
+        self.xpub_manual
+    }
+
+    pub fn xpub_welcome_msg(&self) -> Option<&ZmqMessage> {
+        self.xpub_welcome_msg.as_ref()
+    }
+
+    /// Application metadata sent to peers, see
+    /// [`SocketOptionsBuilder::metadata`]
+    pub fn metadata(&self) -> &PeerMetadata {
+        &self.metadata
+    }
+
+    /// Whether this socket acts as the server of its security mechanism
+    pub fn as_server(&self) -> bool {
+        self.security.as_server()
+    }
+
+    pub fn zap_domain(&self) -> &str {
+        &self.zap_domain
+    }
+
+    /// Directory `ipc://*` binds create their socket in, see
+    /// [`SocketOptionsBuilder::ipc_wildcard_dir`]
+    pub fn ipc_wildcard_dir(&self) -> PathBuf {
+        match &self.ipc_wildcard_dir {
+            Some(dir) => dir.clone(),
+            None => std::env::temp_dir(),
+        }
+    }
+
+    /// Delay before the next connection attempt, `try_num` attempts having
+    /// failed already. The delay only grows if `reconnect_ivl_max` is set
+    pub(crate) fn reconnect_delay(&self, try_num: u32) -> Duration {
+        let ivl = self.reconnect_ivl.as_secs_f64();
+        match self.reconnect_ivl_max {
+            Some(ivl_max) => {
+                let backoff = ivl * std::f64::consts::E.powf(f64::from(try_num) / 3.0);
+                Duration::from_secs_f64(backoff.min(ivl_max.as_secs_f64()))
+            }
+            None => self.reconnect_ivl,
+        }
+    }
+}
+
+pub struct SocketOptionsBuilder {
+    options: SocketOptions,
+    #[cfg(feature = "tls-transport")]
+    tls_certificate: Option<(Vec<u8>, Vec<u8>)>,
+    #[cfg(feature = "tls-transport")]
+    tls_ca: Option<Vec<u8>>,
+}
+
+impl SocketOptionsBuilder {
+    /// Equivalent of `ZMQ_ROUTING_ID`. Sent to peers in the `Identity`
+    /// property of the READY command, ROUTER peers use it to address this
+    /// socket. A random identity is used by default
+    pub fn peer_identity(mut self, identity: PeerIdentity) -> Self {
+        self.options.peer_id = Some(identity);
+        self
+    }
+
+    /// Equivalent of `ZMQ_SNDHWM`. Maximum number of outgoing messages
+    /// queued per peer, 0 means no limit. Defaults to 1000.
+    ///
+    /// Once a peer reaches it PUB and ROUTER sockets drop messages for that
+    /// peer, PUSH, DEALER and PAIR sockets skip to the next peer and only
+    /// wait if every peer is full
+    pub fn send_hwm(mut self, hwm: usize) -> Self {
+        self.options.send_hwm = hwm;
+        self
+    }
+
+    /// Equivalent of `ZMQ_RCVHWM`. Maximum number of incoming messages
+    /// read ahead per peer, 0 means no limit. Beyond that the peer is slowed
+    /// down by TCP flow control. Defaults to 1000
+    pub fn recv_hwm(mut self, hwm: usize) -> Self {
+        self.options.recv_hwm = hwm;
+        self
+    }
+
+    /// Equivalent of `ZMQ_LINGER`. How long messages still queued for a peer
+    /// keep being sent in the background after the socket is closed or the
+    /// peer disconnected. `None` keeps going until everything is sent
+    pub fn linger(mut self, linger: Option<Duration>) -> Self {
+        self.options.linger = linger;
+        self
+    }
+
+    /// Equivalent of `ZMQ_RECONNECT_IVL`. Time to wait before trying to
+    /// reconnect to an endpoint whose connection was lost. Defaults to 100ms
+    pub fn reconnect_ivl(mut self, ivl: Duration) -> Self {
+        self.options.reconnect_ivl = ivl;
+        self
+    }
+
+    /// Equivalent of `ZMQ_RECONNECT_IVL_MAX`. When set, the interval between
+    /// failed reconnection attempts grows exponentially from
+    /// `reconnect_ivl` up to this value. Otherwise `reconnect_ivl` is used
+    /// for every attempt
+    pub fn reconnect_ivl_max(mut self, ivl_max: Option<Duration>) -> Self {
+        self.options.reconnect_ivl_max = ivl_max;
+        self
+    }
+
+    /// Equivalent of `ZMQ_HANDSHAKE_IVL`. Connections that don't complete
+    /// the ZMTP handshake in time are dropped. `None` waits forever. Defaults
+    /// to 30s
+    pub fn handshake_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.options.handshake_timeout = timeout;
+        self
+    }
+
+    /// Equivalent of `ZMQ_HEARTBEAT_IVL`. Sends a PING command to every peer
+    /// at this interval, peers that stay silent for `heartbeat_timeout`
+    /// after a PING get disconnected. `None` disables heartbeats, which is
+    /// the default
+    pub fn heartbeat_ivl(mut self, ivl: Option<Duration>) -> Self {
+        self.options.heartbeat_ivl = ivl;
+        self
+    }
+
+    /// Equivalent of `ZMQ_HEARTBEAT_TIMEOUT`. How long a peer may take to
+    /// send anything after a PING. Defaults to `heartbeat_ivl`
+    pub fn heartbeat_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.options.heartbeat_timeout = timeout;
+        self
+    }
+
+    /// Equivalent of `ZMQ_HEARTBEAT_TTL`. Sent along with PINGs, asks peers
+    /// to drop the connection when they hear nothing from this socket for
+    /// that long. Rounded down to a tenth of a second, at most 6553.5s
+    pub fn heartbeat_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.options.heartbeat_ttl = ttl;
+        self
+    }
+
+    /// Equivalent of `ZMQ_MAXMSGSIZE`. Peers sending a message frame larger
+    /// than this many bytes get disconnected. No limit by default
+    pub fn max_msg_size(mut self, max_msg_size: Option<usize>) -> Self {
+        self.options.max_msg_size = max_msg_size;
+        self
+    }
+
+    /// Equivalent of `ZMQ_ROUTER_MANDATORY`. ROUTER sockets silently drop
+    /// messages they can't deliver. With this set, sending fails with
+    /// [`ZmqError::HostUnreachable`] when no peer has the identity, and
+    /// with [`ZmqError::BufferFull`] when the peer is at its send HWM.
+    /// Off by default
+    pub fn router_mandatory(mut self, mandatory: bool) -> Self {
+        self.options.router_mandatory = mandatory;
+        self
+    }
+
+    /// Equivalent of `ZMQ_ROUTER_HANDOVER`. When a peer connects with an
+    /// identity already in use, ROUTER sockets refuse it. With this set, the
+    /// connection using the identity so far is dropped and the new one takes
+    /// over. Off by default
+    pub fn router_handover(mut self, handover: bool) -> Self {
+        self.options.router_handover = handover;
+        self
+    }
+
+    /// Equivalent of `ZMQ_PROBE_ROUTER`. ROUTER, DEALER and REQ sockets send
+    /// an empty message to every new peer, so that ROUTER peers learn their
+    /// identity right away. Off by default
+    pub fn probe_router(mut self, probe: bool) -> Self {
+        self.options.probe_router = probe;
+        self
+    }
+
+    /// Equivalent of `ZMQ_REQ_RELAXED`. REQ sockets may send a new request
+    /// before the reply to the previous one came, which is then given up on.
+    /// The new request goes to the next peer. Off by default
+    ///
+    /// Together with [`SocketOptionsBuilder::req_correlate`], this is what
+    /// Lazy Pirate clients need: stop waiting on
+    /// [`crate::SocketRecv::recv`] after a timeout and send the request again
+    pub fn req_relaxed(mut self, relaxed: bool) -> Self {
+        self.options.req_relaxed = relaxed;
+        self
+    }
+
+    /// Equivalent of `ZMQ_REQ_CORRELATE`. REQ sockets prefix requests with
+    /// an id and discard replies that don't carry the id of the last
+    /// request. Off by default
+    pub fn req_correlate(mut self, correlate: bool) -> Self {
+        self.options.req_correlate = correlate;
+        self
+    }
+
+    /// Equivalent of `ZMQ_XPUB_VERBOSE`. XPUB sockets pass on every
+    /// subscribe message, not only the ones for topics nobody was subscribed
+    /// to yet. Off by default, see [`crate::XPubSocket::set_verbose`]
+    pub fn xpub_verbose(mut self, verbose: bool) -> Self {
+        self.options.xpub_verbose = verbose;
+        self
+    }
+
+    /// Equivalent of `ZMQ_XPUB_VERBOSER`. XPUB sockets pass on every
+    /// subscribe and unsubscribe message. Off by default, see
+    /// [`crate::XPubSocket::set_verboser`]
+    pub fn xpub_verboser(mut self, verboser: bool) -> Self {
+        self.options.xpub_verboser = verboser;
+        self
+    }
+
+    /// Equivalent of `ZMQ_XPUB_MANUAL`. XPUB sockets pass subscription
+    /// messages on without applying them. Off by default, see
+    /// [`crate::XPubSocket::set_manual`]
+    pub fn xpub_manual(mut self, manual: bool) -> Self {
+        self.options.xpub_manual = manual;
+        self
+    }
+
+    /// Equivalent of `ZMQ_XPUB_WELCOME_MSG`. Sent by XPUB sockets to every
+    /// subscriber as soon as it connects. None by default, see
+    /// [`crate::XPubSocket::set_welcome_msg`]
+    pub fn xpub_welcome_msg(mut self, message: Option<ZmqMessage>) -> Self {
+        self.options.xpub_welcome_msg = message;
+        self
+    }
+
+    /// Equivalent of `ZMQ_METADATA`. Adds a property to the metadata sent to
+    /// peers during the handshake, where they can read it from
+    /// [`crate::ZmqMessage::metadata`]. Names must start with `X-`
+    pub fn metadata(mut self, property: impl Into<String>, value: impl Into<Bytes>) -> Self {
+        self.options.metadata.insert(property, value);
+        self
+    }
+
+    /// Authenticates to PLAIN servers with the given credentials. Both must
+    /// be at most 255 bytes long
+    pub fn plain_client(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.options.security = Security::PlainClient {
+            username: username.into(),
+            password: password.into(),
+        };
+        self
+    }
+
+    /// Equivalent of `ZMQ_PLAIN_SERVER`. Requires connecting peers to
+    /// authenticate with a username and password, which are checked by
+    /// `validator`
+    pub fn plain_server(mut self, validator: impl PlainValidator + 'static) -> Self {
+        self.options.security = Security::PlainServer {
+            validator: Arc::new(validator),
+        };
+        self
+    }
+
+    /// Equivalent of `ZMQ_CURVE_SERVERKEY` along with `ZMQ_CURVE_PUBLICKEY`
+    /// and `ZMQ_CURVE_SECRETKEY`. Encrypts connections to a CURVE server
+    /// whose public key is `server_key`, presenting `keypair` to it
+    pub fn curve_client(mut self, server_key: [u8; 32], keypair: CurveKeyPair) -> Self {
+        self.options.security = Security::CurveClient {
+            server_key,
+            keypair,
+        };
+        self
+    }
+
+    /// Equivalent of `ZMQ_CURVE_SERVER` along with `ZMQ_CURVE_SECRETKEY`.
+    /// Requires connecting peers to use CURVE and to know the public key of
+    /// `keypair`
+    pub fn curve_server(mut self, keypair: CurveKeyPair) -> Self {
+        self.options.security = Security::CurveServer { keypair };
+        self
+    }
+
+    /// Equivalent of `ZMQ_ZAP_DOMAIN`. Passed on to the ZAP handler, which
+    /// may use it to tell sockets apart. Empty by default
+    pub fn zap_domain(mut self, domain: impl Into<String>) -> Self {
+        self.options.zap_domain = domain.into();
+        self
+    }
+
+    /// Hands authentication of peers over to `handler`, which stands for the
+    /// ZAP handler libzmq expects at `inproc://zeromq.zap.01`. It is
+    /// consulted by PLAIN and CURVE servers, and on both ends of NULL
+    /// connections. See [`ZapAuthenticator`](crate::ZapAuthenticator) for a
+    /// ready made one
+    pub fn zap_handler(mut self, handler: impl ZapHandler + 'static) -> Self {
+        self.options.zap_handler = Some(SharedZapHandler(Arc::new(handler)));
+        self
+    }
+
+    /// Directory in which binding `ipc://*` creates a socket with a unique
+    /// name, returned by [`Socket::bind`](crate::Socket::bind) and deleted
+    /// on unbind. Defaults to the system temporary directory
+    pub fn ipc_wildcard_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.options.ipc_wildcard_dir = Some(dir.as_ref().to_owned());
+        self
+    }
+
+    /// PEM encoded certificate chain and private key presented to TLS
+    /// peers. Needed to bind `tls+tcp://` and `wss://` endpoints, sent to
+    /// servers asking for a client certificate
+    #[cfg(feature = "tls-transport")]
+    pub fn tls_certificate(mut self, chain: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) -> Self {
+        self.tls_certificate = Some((chain.into(), key.into()));
+        self
+    }
+
+    /// PEM encoded CA certificates that TLS peers must be signed by. Needed
+    /// to connect to `tls+tcp://` and `wss://` endpoints. When binding,
+    /// clients are then required to present a certificate too
+    #[cfg(feature = "tls-transport")]
+    pub fn tls_ca(mut self, ca: impl Into<Vec<u8>>) -> Self {
+        self.tls_ca = Some(ca.into());
+        self
+    }
+
+    pub fn build(self) -> ZmqResult<SocketOptions> {
+        #[allow(unused_mut)]
+        let mut options = self.options;
+        #[cfg(feature = "tls-transport")]
+        {
+            use crate::transport::tls;
+            let identity = self
+                .tls_certificate
+                .as_ref()
+                .map(|(chain, key)| (chain.as_slice(), key.as_slice()));
+            if let Some(ca) = &self.tls_ca {
+                options.tls_client = Some(tls::client_config(ca, identity)?);
+            }
+            if let Some((chain, key)) = identity {
+                options.tls_server = Some(tls::server_config(chain, key, self.tls_ca.as_deref())?);
+            }
+        }
+        if let Security::PlainClient { username, password } = &options.security {
+            if username.len() > 255 || password.len() > 255 {
+                return Err(ZmqError::Socket(
+                    "PLAIN username and password must not exceed 255 bytes",
+                ));
+            }
+        }
+        for (property, _) in options.metadata.iter() {
+            let prefixed =
+                property.len() > 2 && property.as_bytes()[..2].eq_ignore_ascii_case(b"X-");
+            if !prefixed || property.len() > 255 {
+                return Err(ZmqError::Socket(
+                    "Metadata property names must start with X- and not exceed 255 bytes",
+                ));
+            }
+        }
+        if options.reconnect_ivl == Duration::from_secs(0) {
+            return Err(ZmqError::Socket("reconnect_ivl must not be zero"));
+        }
+        if let Some(ivl_max) = options.reconnect_ivl_max {
+            if ivl_max < options.reconnect_ivl {
+                return Err(ZmqError::Socket(
+                    "reconnect_ivl_max must not be lower than reconnect_ivl",
+                ));
+            }
+        }
+        if options.handshake_timeout == Some(Duration::from_secs(0)) {
+            return Err(ZmqError::Socket(
+                "handshake_timeout must not be zero, use None to disable it",
+            ));
+        }
+        if options.heartbeat_ivl == Some(Duration::from_secs(0)) {
+            return Err(ZmqError::Socket(
+                "heartbeat_ivl must not be zero, use None to disable heartbeats",
+            ));
+        }
+        if let Some(ttl) = options.heartbeat_ttl {
+            if ttl > Duration::from_millis(u64::from(u16::MAX) * 100) {
+                return Err(ZmqError::Socket("heartbeat_ttl must not exceed 6553.5s"));
+            }
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_delay() {
+        let constant = SocketOptions::builder()
+            .reconnect_ivl(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        assert_eq!(constant.reconnect_delay(0), Duration::from_millis(200));
+        assert_eq!(constant.reconnect_delay(10), Duration::from_millis(200));
+
+        let backoff = SocketOptions::builder()
+            .reconnect_ivl(Duration::from_millis(100))
+            .reconnect_ivl_max(Some(Duration::from_secs(1)))
+            .build()
+            .unwrap();
+        assert_eq!(backoff.reconnect_delay(0), Duration::from_millis(100));
+        assert!(backoff.reconnect_delay(1) > Duration::from_millis(100));
+        assert_eq!(backoff.reconnect_delay(1000), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(SocketOptions::builder()
+            .reconnect_ivl(Duration::from_secs(0))
+            .build()
+            .is_err());
+        assert!(SocketOptions::builder()
+            .reconnect_ivl(Duration::from_secs(2))
+            .reconnect_ivl_max(Some(Duration::from_secs(1)))
+            .build()
+            .is_err());
+        assert!(SocketOptions::builder()
+            .handshake_timeout(Some(Duration::from_secs(0)))
+            .build()
+            .is_err());
+        assert!(SocketOptions::builder()
+            .handshake_timeout(None)
+            .build()
+            .is_ok());
+        assert!(SocketOptions::builder()
+            .heartbeat_ivl(Some(Duration::from_secs(0)))
+            .build()
+            .is_err());
+        assert!(SocketOptions::builder()
+            .heartbeat_ttl(Some(Duration::from_secs(7000)))
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use crate::backend::GenericSocketBackend;
+use crate::codec::{Message, ZmqRecvQueue};
+use crate::fair_queue::FairQueue;
+use crate::transport::AcceptStopHandle;
+use crate::util::{ConnectHandle, PeerIdentity};
+use crate::{
+    Endpoint, MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketOptions, SocketRecv,
+    SocketSend, SocketType, ZmqError, ZmqMessage, ZmqResult,
+};
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// An exclusive pair socket. It talks to exactly one peer at a time, any
+/// additional connections are refused while that peer is attached.
+pub struct PairSocket {
+    backend: Arc<GenericSocketBackend>,
+    fair_queue: FairQueue<ZmqRecvQueue, PeerIdentity>,
+    binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
+}
+
+impl Drop for PairSocket {
+    fn drop(&mut self) {
+        self.backend.shutdown();
+    }
+}
+
+#[async_trait]
+impl Socket for PairSocket {
+    fn with_options(options: SocketOptions) -> Self {
+        let fair_queue = FairQueue::new(true);
+        Self {
+            backend: Arc::new(GenericSocketBackend::new(
+                Some(fair_queue.inner()),
+                SocketType::PAIR,
+                options,
+            )),
+            fair_queue,
+            binds: HashMap::new(),
+            connections: HashMap::new(),
+        }
+    }
+
+    fn backend(&self) -> Arc<dyn MultiPeerBackend> {
+        self.backend.clone()
+    }
+
+    fn binds(&mut self) -> &mut HashMap<Endpoint, AcceptStopHandle> {
+        &mut self.binds
+    }
+
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
+    fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.socket_monitor.lock().replace(sender);
+        receiver
+    }
+}
+
+#[async_trait]
+impl SocketRecv for PairSocket {
+    async fn recv(&mut self) -> ZmqResult<ZmqMessage> {
+        loop {
+            match self.fair_queue.next().await {
+                Some((_peer_id, Ok(Message::Message(message)))) => {
+                    return Ok(message);
+                }
+                Some((_peer_id, Ok(_))) => {
+                    // Commands are of no interest to a PAIR socket
+                }
+                Some((peer_id, Err(e))) => {
+                    log::debug!("PAIR peer {:?} failed: {}", peer_id, e);
+                    self.backend.peer_disconnected(&peer_id);
+                }
+                None => return Err(ZmqError::NoMessage),
+            };
+        }
+    }
+}
+
+#[async_trait]
+impl SocketSend for PairSocket {
+    async fn send(&mut self, message: ZmqMessage) -> ZmqResult<()> {
+        self.backend
+            .send_round_robin(Message::Message(message))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::async_rt;
+    use crate::util::tests::test_bind_to_any_port_helper;
+
+    #[async_rt::test]
+    async fn test_bind_to_any_port() -> ZmqResult<()> {
+        let s = PairSocket::new();
+        test_bind_to_any_port_helper(s).await
+    }
+}
@@ -4,113 +4,223 @@ use crate::endpoint::Endpoint;
 use crate::error::ZmqResult;
 use crate::message::*;
 use crate::transport::AcceptStopHandle;
-use crate::util::PeerIdentity;
+use crate::trie::SubscriptionTrie;
+use crate::util::{ConnectHandle, PeerIdentity};
 use crate::{
-    MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketSend, SocketType, ZmqError,
+    MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketOptions, SocketSend, SocketType,
+    ZmqError,
 };
 
 use async_trait::async_trait;
+use bytes::{BufMut, BytesMut};
 use dashmap::DashMap;
 use futures::channel::{mpsc, oneshot};
 use futures::FutureExt;
 use parking_lot::Mutex;
 use std::collections::HashMap;
-use std::io::ErrorKind;
 use std::pin::Pin;
 use std::sync::Arc;
 
 pub(crate) struct Subscriber {
-    pub(crate) subscriptions: Vec<Vec<u8>>,
-    pub(crate) send_queue: Pin<Box<ZmqFramedWrite>>,
+    pub(crate) send_queue: ZmqSendQueue,
     _subscription_coro_stop: oneshot::Sender<()>,
 }
 
+/// Settings that only make sense for XPUB sockets, initialized from the
+/// socket options. See [`crate::XPubSocket`].
+pub(crate) struct XPubOptions {
+    pub(crate) verbose_subs: bool,
+    pub(crate) verbose_unsubs: bool,
+    pub(crate) manual: bool,
+    pub(crate) welcome_msg: Option<ZmqMessage>,
+}
+
 pub(crate) struct PubSocketBackend {
     subscribers: DashMap<PeerIdentity, Subscriber>,
+    subscriptions: Mutex<SubscriptionTrie<PeerIdentity>>,
     socket_monitor: Mutex<Option<mpsc::Sender<SocketEvent>>>,
+    socket_type: SocketType,
+    // Only present for XPUB sockets. Carries messages coming from
+    // subscribers up to the application
+    upstream: Option<mpsc::Sender<(PeerIdentity, ZmqMessage)>>,
+    pub(crate) xpub_options: Mutex<XPubOptions>,
+    socket_options: SocketOptions,
+}
+
+/// Splits a `\x01topic` / `\x00topic` message into the subscribe flag and
+/// the topic. Returns `None` if the message is not a subscription.
+pub(crate) fn parse_subscription(message: &ZmqMessage) -> Option<(bool, &[u8])> {
+    if message.len() != 1 {
+        return None;
+    }
+    let data = message.get(0)?;
+    match data.first() {
+        Some(1) => Some((true, &data[1..])),
+        Some(0) => Some((false, &data[1..])),
+        _ => None,
+    }
+}
+
+/// Builds the `\x01topic` / `\x00topic` message, which is how ZMTP 3.0
+/// peers subscribe
+pub(crate) fn subscription_message(subscribe: bool, topic: &[u8]) -> ZmqMessage {
+    let mut buf = BytesMut::with_capacity(topic.len() + 1);
+    buf.put_u8(subscribe.into());
+    buf.extend_from_slice(topic);
+    ZmqMessage::from(buf.freeze())
 }
 
 impl PubSocketBackend {
-    fn message_received(&self, peer_id: &PeerIdentity, message: Message) {
+    pub(crate) fn new(
+        socket_type: SocketType,
+        upstream: Option<mpsc::Sender<(PeerIdentity, ZmqMessage)>>,
+        socket_options: SocketOptions,
+    ) -> Self {
+        Self {
+            subscribers: DashMap::new(),
+            subscriptions: Mutex::new(SubscriptionTrie::new()),
+            socket_monitor: Mutex::new(None),
+            socket_type,
+            upstream,
+            xpub_options: Mutex::new(XPubOptions {
+                verbose_subs: socket_options.xpub_verbose || socket_options.xpub_verboser,
+                verbose_unsubs: socket_options.xpub_verboser,
+                manual: socket_options.xpub_manual,
+                welcome_msg: socket_options.xpub_welcome_msg.clone(),
+            }),
+            socket_options,
+        }
+    }
+
+    /// Returns true if nobody was subscribed to this topic before
+    pub(crate) fn subscribe(&self, peer_id: &PeerIdentity, topic: &[u8]) -> bool {
+        if !self.subscribers.contains_key(peer_id) {
+            return false;
+        }
+        self.subscriptions.lock().add(topic, peer_id)
+    }
+
+    /// Returns true if the peer was the last one subscribed to this topic
+    pub(crate) fn unsubscribe(&self, peer_id: &PeerIdentity, topic: &[u8]) -> bool {
+        let mut subscriptions = self.subscriptions.lock();
+        subscriptions.remove(topic, peer_id) && !subscriptions.contains(topic)
+    }
+
+    /// Applies a message received from a subscriber. Returns the message if
+    /// it has to be passed on to the application (XPUB only)
+    fn message_received(&self, peer_id: &PeerIdentity, message: Message) -> Option<ZmqMessage> {
         let message = match message {
             Message::Message(m) => m,
-            _ => return,
+            // ZMTP 3.1 peers subscribe with commands instead, XPUB
+            // applications get those in the 3.0 form all the same
+            Message::Command(command) => match command.name {
+                ZmqCommandName::SUBSCRIBE => subscription_message(true, &command.data),
+                ZmqCommandName::CANCEL => subscription_message(false, &command.data),
+                _ => return None,
+            },
+            _ => return None,
         };
-        assert_eq!(message.len(), 1);
-        let data: Vec<u8> = message.into_vec().pop().unwrap().to_vec();
-        if data.is_empty() {
-            return;
+        let xpub = self.socket_type == SocketType::XPUB;
+        let (subscribe, topic) = match parse_subscription(&message) {
+            Some(parsed) => parsed,
+            // Anything else coming upstream is only of interest to XPUB
+            None => return if xpub { Some(message) } else { None },
+        };
+
+        let options = self.xpub_options.lock();
+        if xpub && options.manual {
+            // The application decides what gets applied, see
+            // XPubSocket::subscribe
+            drop(options);
+            return Some(message);
         }
-        match data[0] {
-            1 => {
-                // Subscribe
-                self.subscribers
-                    .get_mut(&peer_id)
-                    .unwrap()
-                    .subscriptions
-                    .push(Vec::from(&data[1..]));
-            }
-            0 => {
-                // Unsubscribe
-                let mut del_index = None;
-                let sub = Vec::from(&data[1..]);
-                for (idx, subscription) in self
-                    .subscribers
-                    .get(&peer_id)
-                    .unwrap()
-                    .subscriptions
-                    .iter()
-                    .enumerate()
-                {
-                    if &sub == subscription {
-                        del_index = Some(idx);
-                        break;
-                    }
+        let notify = if subscribe {
+            self.subscribe(peer_id, topic) || options.verbose_subs
+        } else {
+            self.unsubscribe(peer_id, topic) || options.verbose_unsubs
+        };
+        drop(options);
+        if xpub && notify {
+            Some(message)
+        } else {
+            None
+        }
+    }
+
+    /// Sends the message to every subscriber with a matching subscription
+    pub(crate) fn publish(&self, message: ZmqMessage) -> ZmqResult<()> {
+        let topic = message.get(0).map(|frame| frame.as_ref()).unwrap_or(&[]);
+        let matching = self.subscriptions.lock().matches(topic);
+        let mut dead_peers = Vec::new();
+        for peer_id in matching {
+            let mut subscriber = match self.subscribers.get_mut(&peer_id) {
+                Some(subscriber) => subscriber,
+                None => continue,
+            };
+            let res =
+                Pin::new(&mut subscriber.send_queue).try_send(Message::Message(message.clone()));
+            match res {
+                Ok(()) => {}
+                Err(ZmqError::BufferFull(_)) => {
+                    // Subscribers at their send HWM miss out on the message
+                    log::trace!("Dropping message for slow subscriber {:?}", peer_id);
                 }
-                if let Some(index) = del_index {
-                    self.subscribers
-                        .get_mut(&peer_id)
-                        .unwrap()
-                        .subscriptions
-                        .remove(index);
+                Err(e) => {
+                    log::debug!("Failed to send to subscriber {:?}: {}", peer_id, e);
+                    dead_peers.push(peer_id.clone());
                 }
             }
-            _ => (),
         }
+        for peer in dead_peers {
+            self.peer_disconnected(&peer);
+        }
+        Ok(())
     }
 }
 
 impl SocketBackend for PubSocketBackend {
     fn socket_type(&self) -> SocketType {
-        SocketType::PUB
+        self.socket_type
     }
 
     fn shutdown(&self) {
         self.subscribers.clear();
+        *self.subscriptions.lock() = SubscriptionTrie::new();
     }
 
     fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>> {
         &self.socket_monitor
     }
+
+    fn socket_options(&self) -> &SocketOptions {
+        &self.socket_options
+    }
 }
 
 impl MultiPeerBackend for PubSocketBackend {
-    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) {
-        let (mut recv_queue, send_queue) = io.into_parts();
-        // TODO provide handling for recv_queue
+    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) -> ZmqResult<()> {
+        let zmtp_version = io.zmtp_version;
+        let (mut recv_queue, mut send_queue) = io.into_queues(&self.socket_options);
+        if let Some(welcome) = &self.xpub_options.lock().welcome_msg {
+            Pin::new(&mut send_queue).try_send(Message::Message(welcome.clone()))?;
+        }
         let (sender, stop_receiver) = oneshot::channel();
         self.subscribers.insert(
             peer_id.clone(),
             Subscriber {
-                subscriptions: vec![],
-                send_queue: Box::pin(send_queue),
+                send_queue,
                 _subscription_coro_stop: sender,
             },
         );
+        if zmtp_version.0 == 1 {
+            // ZMTP 1.0 subscribers never send their subscriptions, they
+            // filter on their side
+            self.subscribe(peer_id, b"");
+        }
         let backend = self;
         let peer_id = peer_id.clone();
         async_rt::task::spawn(async move {
-            use futures::StreamExt;
+            use futures::{SinkExt, StreamExt};
             let mut stop_receiver = stop_receiver.fuse();
             loop {
                 futures::select! {
@@ -119,9 +229,18 @@ impl MultiPeerBackend for PubSocketBackend {
                      },
                      message = recv_queue.next().fuse() => {
                         match message {
-                            Some(Ok(m)) => backend.message_received(&peer_id, m),
+                            Some(Ok(m)) => {
+                                let upstream_msg = backend.message_received(&peer_id, m);
+                                if let (Some(m), Some(upstream)) = (upstream_msg, &backend.upstream) {
+                                    // Applies backpressure on the subscriber if
+                                    // the application doesn't keep up
+                                    if upstream.clone().send((peer_id.clone(), m)).await.is_err() {
+                                        break;
+                                    }
+                                }
+                            }
                             Some(Err(e)) => {
-                                dbg!(e);
+                                log::debug!("Subscriber {:?} failed: {}", peer_id, e);
                                 backend.peer_disconnected(&peer_id);
                                 break;
                             }
@@ -135,17 +254,47 @@ impl MultiPeerBackend for PubSocketBackend {
                 }
             }
         });
+        Ok(())
     }
 
     fn peer_disconnected(&self, peer_id: &PeerIdentity) {
         log::info!("Client disconnected {:?}", peer_id);
-        self.subscribers.remove(peer_id);
+        if self.subscribers.remove(peer_id).is_none() {
+            return;
+        }
+        let topics = self.subscriptions.lock().remove_key(peer_id);
+        let upstream = match &self.upstream {
+            Some(upstream) => upstream,
+            None => return,
+        };
+        let options = self.xpub_options.lock();
+        if options.manual {
+            return;
+        }
+        // Let the application know about subscriptions that went away together
+        // with the subscriber
+        for topic in topics {
+            if options.verbose_unsubs || !self.subscriptions.lock().contains(&topic) {
+                let mut data = Vec::with_capacity(topic.len() + 1);
+                data.push(0);
+                data.extend_from_slice(&topic);
+                let message = ZmqMessage::from(bytes::Bytes::from(data));
+                if upstream
+                    .clone()
+                    .try_send((peer_id.clone(), message))
+                    .is_err()
+                {
+                    log::warn!("Dropped unsubscription of disconnected peer {:?}", peer_id);
+                }
+            }
+        }
     }
 }
 
 pub struct PubSocket {
     pub(crate) backend: Arc<PubSocketBackend>,
     binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
 }
 
 impl Drop for PubSocket {
@@ -157,48 +306,17 @@ impl Drop for PubSocket {
 #[async_trait]
 impl SocketSend for PubSocket {
     async fn send(&mut self, message: ZmqMessage) -> ZmqResult<()> {
-        let mut dead_peers = Vec::new();
-        for mut subscriber in self.backend.subscribers.iter_mut() {
-            for sub_filter in &subscriber.subscriptions {
-                if sub_filter.as_slice() == &message.get(0).unwrap()[0..sub_filter.len()] {
-                    let res = subscriber
-                        .send_queue
-                        .as_mut()
-                        .try_send(Message::Message(message.clone()));
-                    match res {
-                        Ok(()) => {}
-                        Err(ZmqError::Codec(CodecError::Io(e))) => {
-                            if e.kind() == ErrorKind::BrokenPipe {
-                                dead_peers.push(subscriber.key().clone());
-                            } else {
-                                dbg!(e);
-                            }
-                        }
-                        Err(e) => {
-                            dbg!(e);
-                            todo!()
-                        }
-                    }
-                    break;
-                }
-            }
-        }
-        for peer in dead_peers {
-            self.backend.peer_disconnected(&peer);
-        }
-        Ok(())
+        self.backend.publish(message)
     }
 }
 
 #[async_trait]
 impl Socket for PubSocket {
-    fn new() -> Self {
+    fn with_options(options: SocketOptions) -> Self {
         Self {
-            backend: Arc::new(PubSocketBackend {
-                subscribers: DashMap::new(),
-                socket_monitor: Mutex::new(None),
-            }),
+            backend: Arc::new(PubSocketBackend::new(SocketType::PUB, None, options)),
             binds: HashMap::new(),
+            connections: HashMap::new(),
         }
     }
 
@@ -210,6 +328,10 @@ impl Socket for PubSocket {
         &mut self.binds
     }
 
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
     fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
         let (sender, receiver) = mpsc::channel(1024);
         self.backend.socket_monitor.lock().replace(sender);
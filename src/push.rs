@@ -1,9 +1,10 @@
 use crate::backend::GenericSocketBackend;
 use crate::codec::Message;
 use crate::transport::AcceptStopHandle;
+use crate::util::ConnectHandle;
 use crate::{
-    Endpoint, MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketSend, SocketType,
-    ZmqMessage, ZmqResult,
+    Endpoint, MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketOptions, SocketSend,
+    SocketType, ZmqMessage, ZmqResult,
 };
 use async_trait::async_trait;
 use futures::channel::mpsc;
@@ -14,6 +15,7 @@ use std::sync::Arc;
 pub struct PushSocket {
     backend: Arc<GenericSocketBackend>,
     binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
 }
 
 impl Drop for PushSocket {
@@ -24,10 +26,11 @@ impl Drop for PushSocket {
 
 #[async_trait]
 impl Socket for PushSocket {
-    fn new() -> Self {
+    fn with_options(options: SocketOptions) -> Self {
         Self {
-            backend: Arc::new(GenericSocketBackend::new(None, SocketType::PUSH)),
+            backend: Arc::new(GenericSocketBackend::new(None, SocketType::PUSH, options)),
             binds: HashMap::new(),
+            connections: HashMap::new(),
         }
     }
 
@@ -39,6 +42,10 @@ impl Socket for PushSocket {
         &mut self.binds
     }
 
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
     fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
         let (sender, receiver) = mpsc::channel(1024);
         self.backend.socket_monitor.lock().replace(sender);
@@ -0,0 +1,238 @@
+use crate::async_rt;
+use crate::codec::*;
+use crate::endpoint::Endpoint;
+use crate::error::{ZmqError, ZmqResult};
+use crate::message::*;
+use crate::transport::AcceptStopHandle;
+use crate::util::{ConnectHandle, PeerIdentity};
+use crate::{
+    MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketOptions, SocketSend, SocketType,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::channel::{mpsc, oneshot};
+use futures::FutureExt;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Group names are limited to 255 bytes, as in libzmq
+pub(crate) const MAX_GROUP_LEN: usize = 255;
+
+pub(crate) fn check_group(group: &str) -> ZmqResult<()> {
+    if group.len() > MAX_GROUP_LEN {
+        return Err(ZmqError::Socket("Group names are limited to 255 bytes"));
+    }
+    Ok(())
+}
+
+pub(crate) struct Dish {
+    send_queue: ZmqSendQueue,
+    // Groups the dish joined. Peers over connectionless transports can't
+    // tell, they get every group
+    groups: Option<HashSet<Bytes>>,
+    _join_coro_stop: oneshot::Sender<()>,
+}
+
+pub(crate) struct RadioSocketBackend {
+    dishes: DashMap<PeerIdentity, Dish>,
+    socket_monitor: Mutex<Option<mpsc::Sender<SocketEvent>>>,
+    socket_options: SocketOptions,
+}
+
+impl RadioSocketBackend {
+    fn new(socket_options: SocketOptions) -> Self {
+        Self {
+            dishes: DashMap::new(),
+            socket_monitor: Mutex::new(None),
+            socket_options,
+        }
+    }
+
+    /// Applies a JOIN or LEAVE command received from a dish
+    fn command_received(&self, peer_id: &PeerIdentity, command: ZmqCommand) {
+        let mut dish = match self.dishes.get_mut(peer_id) {
+            Some(dish) => dish,
+            None => return,
+        };
+        if let Some(groups) = &mut dish.groups {
+            match command.name {
+                ZmqCommandName::JOIN => {
+                    groups.insert(command.data);
+                }
+                ZmqCommandName::LEAVE => {
+                    groups.remove(&command.data);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Sends the message to every dish that joined its group, as a group
+    /// frame followed by the body
+    fn publish(&self, message: ZmqMessage) -> ZmqResult<()> {
+        let group = message
+            .group()
+            .ok_or(ZmqError::Socket("RADIO messages need a group"))?;
+        check_group(group)?;
+        if message.len() != 1 {
+            return Err(ZmqError::Socket(
+                "RADIO sockets don't send multipart messages",
+            ));
+        }
+        let mut frames = ZmqMessage::from(Bytes::copy_from_slice(group.as_bytes()));
+        frames.push_back(message.get(0).cloned().unwrap_or_default());
+
+        let mut dead_peers = Vec::new();
+        for mut dish in self.dishes.iter_mut() {
+            let joined = match &dish.groups {
+                Some(groups) => groups.contains(group.as_bytes()),
+                None => true,
+            };
+            if !joined {
+                continue;
+            }
+            let res = Pin::new(&mut dish.send_queue).try_send(Message::Message(frames.clone()));
+            match res {
+                Ok(()) => {}
+                Err(ZmqError::BufferFull(_)) => {
+                    // Dishes at their send HWM miss out on the message
+                    log::trace!("Dropping message for slow dish {:?}", dish.key());
+                }
+                Err(e) => {
+                    log::debug!("Failed to send to dish {:?}: {}", dish.key(), e);
+                    dead_peers.push(dish.key().clone());
+                }
+            }
+        }
+        for peer in dead_peers {
+            self.peer_disconnected(&peer);
+        }
+        Ok(())
+    }
+}
+
+impl SocketBackend for RadioSocketBackend {
+    fn socket_type(&self) -> SocketType {
+        SocketType::RADIO
+    }
+
+    fn shutdown(&self) {
+        self.dishes.clear();
+    }
+
+    fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>> {
+        &self.socket_monitor
+    }
+
+    fn socket_options(&self) -> &SocketOptions {
+        &self.socket_options
+    }
+}
+
+impl MultiPeerBackend for RadioSocketBackend {
+    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) -> ZmqResult<()> {
+        let groups = if io.connectionless {
+            None
+        } else {
+            Some(HashSet::new())
+        };
+        let (mut recv_queue, send_queue) = io.into_queues(&self.socket_options);
+        let (sender, stop_receiver) = oneshot::channel();
+        self.dishes.insert(
+            peer_id.clone(),
+            Dish {
+                send_queue,
+                groups,
+                _join_coro_stop: sender,
+            },
+        );
+        let backend = self;
+        let peer_id = peer_id.clone();
+        async_rt::task::spawn(async move {
+            use futures::StreamExt;
+            let mut stop_receiver = stop_receiver.fuse();
+            loop {
+                futures::select! {
+                    _ = stop_receiver => break,
+                    message = recv_queue.next().fuse() => match message {
+                        Some(Ok(Message::Command(command))) => {
+                            backend.command_received(&peer_id, command);
+                        }
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => {
+                            log::debug!("Dish {:?} failed: {}", peer_id, e);
+                            backend.peer_disconnected(&peer_id);
+                            break;
+                        }
+                        None => {
+                            backend.peer_disconnected(&peer_id);
+                            break;
+                        }
+                    },
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn peer_disconnected(&self, peer_id: &PeerIdentity) {
+        log::info!("Dish disconnected {:?}", peer_id);
+        self.dishes.remove(peer_id);
+    }
+}
+
+/// Sends each message to the DISH sockets that joined its group, see
+/// [`ZmqMessage::set_group`]. Like PUB, messages are dropped for dishes that
+/// don't keep up. Besides TCP and the other connected transports, RADIO
+/// sockets can connect to `udp://` endpoints, multicast ones included
+pub struct RadioSocket {
+    backend: Arc<RadioSocketBackend>,
+    binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
+}
+
+impl Drop for RadioSocket {
+    fn drop(&mut self) {
+        self.backend.shutdown();
+    }
+}
+
+#[async_trait]
+impl SocketSend for RadioSocket {
+    async fn send(&mut self, message: ZmqMessage) -> ZmqResult<()> {
+        self.backend.publish(message)
+    }
+}
+
+#[async_trait]
+impl Socket for RadioSocket {
+    fn with_options(options: SocketOptions) -> Self {
+        Self {
+            backend: Arc::new(RadioSocketBackend::new(options)),
+            binds: HashMap::new(),
+            connections: HashMap::new(),
+        }
+    }
+
+    fn backend(&self) -> Arc<dyn MultiPeerBackend> {
+        self.backend.clone()
+    }
+
+    fn binds(&mut self) -> &mut HashMap<Endpoint, AcceptStopHandle> {
+        &mut self.binds
+    }
+
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
+    fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.socket_monitor.lock().replace(sender);
+        receiver
+    }
+}
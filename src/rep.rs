@@ -16,20 +16,22 @@ use std::sync::Arc;
 
 struct RepPeer {
     pub(crate) _identity: PeerIdentity,
-    pub(crate) send_queue: ZmqFramedWrite,
+    pub(crate) send_queue: ZmqSendQueue,
 }
 
 struct RepSocketBackend {
     pub(crate) peers: DashMap<PeerIdentity, RepPeer>,
-    fair_queue_inner: Arc<Mutex<QueueInner<ZmqFramedRead, PeerIdentity>>>,
+    fair_queue_inner: Arc<Mutex<QueueInner<ZmqRecvQueue, PeerIdentity>>>,
     socket_monitor: Mutex<Option<mpsc::Sender<SocketEvent>>>,
+    socket_options: SocketOptions,
 }
 
 pub struct RepSocket {
     backend: Arc<RepSocketBackend>,
     current_request: Option<PeerIdentity>,
-    fair_queue: FairQueue<ZmqFramedRead, PeerIdentity>,
+    fair_queue: FairQueue<ZmqRecvQueue, PeerIdentity>,
     binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
 }
 
 impl Drop for RepSocket {
@@ -40,17 +42,19 @@ impl Drop for RepSocket {
 
 #[async_trait]
 impl Socket for RepSocket {
-    fn new() -> Self {
+    fn with_options(options: SocketOptions) -> Self {
         let fair_queue = FairQueue::new(true);
         Self {
             backend: Arc::new(RepSocketBackend {
                 peers: DashMap::new(),
                 fair_queue_inner: fair_queue.inner(),
                 socket_monitor: Mutex::new(None),
+                socket_options: options,
             }),
             current_request: None,
             fair_queue,
             binds: HashMap::new(),
+            connections: HashMap::new(),
         }
     }
 
@@ -62,6 +66,10 @@ impl Socket for RepSocket {
         &mut self.binds
     }
 
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
     fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
         let (sender, receiver) = mpsc::channel(1024);
         self.backend.socket_monitor.lock().replace(sender);
@@ -70,8 +78,8 @@ impl Socket for RepSocket {
 }
 
 impl MultiPeerBackend for RepSocketBackend {
-    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) {
-        let (recv_queue, send_queue) = io.into_parts();
+    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) -> ZmqResult<()> {
+        let (recv_queue, send_queue) = io.into_queues(&self.socket_options);
 
         self.peers.insert(
             peer_id.clone(),
@@ -83,6 +91,7 @@ impl MultiPeerBackend for RepSocketBackend {
         self.fair_queue_inner
             .lock()
             .insert(peer_id.clone(), recv_queue);
+        Ok(())
     }
 
     fn peer_disconnected(&self, peer_id: &PeerIdentity) {
@@ -90,6 +99,7 @@ impl MultiPeerBackend for RepSocketBackend {
             let _ = monitor.try_send(SocketEvent::Disconnected(peer_id.clone()));
         }
         self.peers.remove(peer_id);
+        self.fair_queue_inner.lock().remove(peer_id);
     }
 }
 
@@ -105,6 +115,10 @@ impl SocketBackend for RepSocketBackend {
     fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>> {
         &self.socket_monitor
     }
+
+    fn socket_options(&self) -> &SocketOptions {
+        &self.socket_options
+    }
 }
 
 #[async_trait]
@@ -136,18 +150,24 @@ impl SocketRecv for RepSocket {
     async fn recv(&mut self) -> ZmqResult<ZmqMessage> {
         loop {
             match self.fair_queue.next().await {
-                Some((peer_id, Ok(message))) => {
-                    match message {
-                        Message::Message(mut m) => {
-                            assert!(m.len() > 1);
-                            assert!(m.pop_front().unwrap().is_empty()); // Ensure that we have delimeter as first part
-                            self.current_request = Some(peer_id);
-                            return Ok(m);
-                        }
-                        _ => todo!(),
+                Some((peer_id, Ok(Message::Message(mut m)))) => {
+                    // Requests start with an empty delimiter frame, anything
+                    // else such as the empty message of a ROUTER probe is
+                    // dropped
+                    if m.len() < 2 || !m.get(0).unwrap().is_empty() {
+                        log::debug!("Discarding malformed request from {:?}", peer_id);
+                        continue;
                     }
+                    m.pop_front();
+                    self.current_request = Some(peer_id);
+                    return Ok(m);
+                }
+                // Commands are of no interest to REP sockets
+                Some((_peer_id, Ok(_))) => {}
+                Some((peer_id, Err(e))) => {
+                    log::debug!("REP peer {:?} failed: {}", peer_id, e);
+                    self.backend.peer_disconnected(&peer_id);
                 }
-                Some((_peer_id, _)) => todo!(),
                 None => return Err(ZmqError::NoMessage),
             };
         }
@@ -2,7 +2,7 @@ use crate::codec::*;
 use crate::endpoint::Endpoint;
 use crate::error::*;
 use crate::transport::AcceptStopHandle;
-use crate::util::{Peer, PeerIdentity};
+use crate::util::{ConnectHandle, Peer, PeerIdentity};
 use crate::*;
 use crate::{SocketType, ZmqResult};
 
@@ -18,12 +18,17 @@ struct ReqSocketBackend {
     pub(crate) peers: DashMap<PeerIdentity, Peer>,
     pub(crate) round_robin: SegQueue<PeerIdentity>,
     socket_monitor: Mutex<Option<mpsc::Sender<SocketEvent>>>,
+    socket_options: SocketOptions,
 }
 
 pub struct ReqSocket {
     backend: Arc<ReqSocketBackend>,
+    // Peer the last request went to, until its reply is received
     current_request: Option<PeerIdentity>,
+    // Id of the last request, sent along with it when correlating
+    request_id: u32,
     binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
 }
 
 impl Drop for ReqSocket {
@@ -35,7 +40,7 @@ impl Drop for ReqSocket {
 #[async_trait]
 impl SocketSend for ReqSocket {
     async fn send(&mut self, mut message: ZmqMessage) -> ZmqResult<()> {
-        if self.current_request.is_some() {
+        if self.current_request.is_some() && !self.backend.socket_options.req_relaxed {
             return Err(ZmqError::ReturnToSender {
                 reason: "Unable to send message. Request already in progress",
                 message,
@@ -60,6 +65,10 @@ impl SocketSend for ReqSocket {
                 Some(mut peer) => {
                     self.backend.round_robin.push(next_peer_id.clone());
                     message.push_front(Bytes::new());
+                    if self.backend.socket_options.req_correlate {
+                        self.request_id = self.request_id.wrapping_add(1);
+                        message.push_front(Bytes::copy_from_slice(&self.request_id.to_be_bytes()));
+                    }
                     peer.send_queue.send(Message::Message(message)).await?;
                     self.current_request = Some(next_peer_id);
                     return Ok(());
@@ -73,39 +82,67 @@ impl SocketSend for ReqSocket {
 #[async_trait]
 impl SocketRecv for ReqSocket {
     async fn recv(&mut self) -> ZmqResult<ZmqMessage> {
-        match self.current_request.take() {
-            Some(peer_id) => {
-                if let Some(mut peer) = self.backend.peers.get_mut(&peer_id) {
-                    let message = peer.recv_queue.next().await;
-                    match message {
-                        Some(Ok(Message::Message(mut m))) => {
-                            assert!(m.len() > 1);
-                            assert!(m.pop_front().unwrap().is_empty()); // Ensure that we have delimeter as first part
-                            Ok(m)
+        // The request stays in progress until its reply comes, even if this
+        // future is dropped before
+        let peer_id = match &self.current_request {
+            Some(peer_id) => peer_id.clone(),
+            None => return Err(ZmqError::Other("Unable to recv. No request in progress")),
+        };
+        loop {
+            let message = match self.backend.peers.get_mut(&peer_id) {
+                Some(mut peer) => peer.recv_queue.next().await,
+                None => {
+                    self.current_request = None;
+                    return Err(ZmqError::Other("Server disconnected"));
+                }
+            };
+            match message {
+                Some(Ok(Message::Message(mut m))) => {
+                    if self.backend.socket_options.req_correlate {
+                        let request_id = m.pop_front();
+                        if request_id.as_deref() != Some(&self.request_id.to_be_bytes()[..]) {
+                            log::debug!("Discarding reply to an earlier request");
+                            continue;
                         }
-                        Some(_) => todo!(),
-                        None => Err(ZmqError::NoMessage),
                     }
-                } else {
-                    Err(ZmqError::Other("Server disconnected"))
+                    // Replies start with an empty delimiter frame
+                    if m.len() < 2 || !m.get(0).unwrap().is_empty() {
+                        log::debug!("Discarding malformed reply from {:?}", peer_id);
+                        continue;
+                    }
+                    m.pop_front();
+                    self.current_request = None;
+                    return Ok(m);
+                }
+                // Commands are of no interest to REQ sockets
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    self.current_request = None;
+                    return Err(e.into());
+                }
+                None => {
+                    self.current_request = None;
+                    return Err(ZmqError::NoMessage);
                 }
             }
-            None => Err(ZmqError::Other("Unable to recv. No request in progress")),
         }
     }
 }
 
 #[async_trait]
 impl Socket for ReqSocket {
-    fn new() -> Self {
+    fn with_options(options: SocketOptions) -> Self {
         Self {
             backend: Arc::new(ReqSocketBackend {
                 peers: DashMap::new(),
                 round_robin: SegQueue::new(),
                 socket_monitor: Mutex::new(None),
+                socket_options: options,
             }),
             current_request: None,
+            request_id: rand::random(),
             binds: HashMap::new(),
+            connections: HashMap::new(),
         }
     }
 
@@ -117,6 +154,10 @@ impl Socket for ReqSocket {
         &mut self.binds
     }
 
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
     fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
         let (sender, receiver) = mpsc::channel(1024);
         self.backend.socket_monitor.lock().replace(sender);
@@ -125,8 +166,8 @@ impl Socket for ReqSocket {
 }
 
 impl MultiPeerBackend for ReqSocketBackend {
-    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) {
-        let (recv_queue, send_queue) = io.into_parts();
+    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) -> ZmqResult<()> {
+        let (recv_queue, send_queue) = io.into_queues(&self.socket_options);
         self.peers.insert(
             peer_id.clone(),
             Peer {
@@ -136,6 +177,7 @@ impl MultiPeerBackend for ReqSocketBackend {
             },
         );
         self.round_robin.push(peer_id.clone());
+        Ok(())
     }
 
     fn peer_disconnected(&self, peer_id: &PeerIdentity) {
@@ -155,4 +197,8 @@ impl SocketBackend for ReqSocketBackend {
     fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>> {
         &self.socket_monitor
     }
+
+    fn socket_options(&self) -> &SocketOptions {
+        &self.socket_options
+    }
 }
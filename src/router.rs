@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use futures::stream::StreamExt;
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::pin::Pin;
 use std::sync::Arc;
 
 use crate::backend::GenericSocketBackend;
@@ -11,16 +11,16 @@ use crate::error::{ZmqError, ZmqResult};
 use crate::fair_queue::FairQueue;
 use crate::message::*;
 use crate::transport::AcceptStopHandle;
-use crate::util::PeerIdentity;
-use crate::{MultiPeerBackend, SocketEvent, SocketRecv, SocketSend, SocketType};
+use crate::util::{ConnectHandle, PeerIdentity};
+use crate::{MultiPeerBackend, SocketEvent, SocketOptions, SocketRecv, SocketSend, SocketType};
 use crate::{Socket, SocketBackend};
 use futures::channel::mpsc;
-use futures::SinkExt;
 
 pub struct RouterSocket {
     backend: Arc<GenericSocketBackend>,
     binds: HashMap<Endpoint, AcceptStopHandle>,
-    fair_queue: FairQueue<ZmqFramedRead, PeerIdentity>,
+    connections: HashMap<Endpoint, ConnectHandle>,
+    fair_queue: FairQueue<ZmqRecvQueue, PeerIdentity>,
 }
 
 impl Drop for RouterSocket {
@@ -31,14 +31,16 @@ impl Drop for RouterSocket {
 
 #[async_trait]
 impl Socket for RouterSocket {
-    fn new() -> Self {
+    fn with_options(options: SocketOptions) -> Self {
         let fair_queue = FairQueue::new(true);
         Self {
             backend: Arc::new(GenericSocketBackend::new(
                 Some(fair_queue.inner()),
                 SocketType::ROUTER,
+                options,
             )),
             binds: HashMap::new(),
+            connections: HashMap::new(),
             fair_queue,
         }
     }
@@ -51,6 +53,10 @@ impl Socket for RouterSocket {
         &mut self.binds
     }
 
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
     fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
         let (sender, receiver) = mpsc::channel(1024);
         self.backend.socket_monitor.lock().replace(sender);
@@ -67,8 +73,12 @@ impl SocketRecv for RouterSocket {
                     message.push_front(peer_id.into());
                     return Ok(message);
                 }
-                Some((_peer_id, _)) => todo!(),
-                None => todo!(),
+                Some((_peer_id, Ok(_))) => {}
+                Some((peer_id, Err(e))) => {
+                    log::debug!("ROUTER peer {:?} failed: {}", peer_id, e);
+                    self.backend.peer_disconnected(&peer_id);
+                }
+                None => return Err(ZmqError::NoMessage),
             };
         }
     }
@@ -77,14 +87,29 @@ impl SocketRecv for RouterSocket {
 #[async_trait]
 impl SocketSend for RouterSocket {
     async fn send(&mut self, mut message: ZmqMessage) -> ZmqResult<()> {
-        assert!(message.len() > 1);
-        let peer_id: PeerIdentity = message.pop_front().unwrap().to_vec().try_into()?;
-        match self.backend.peers.get_mut(&peer_id) {
-            Some(mut peer) => {
-                peer.send_queue.send(Message::Message(message)).await?;
+        if message.len() < 2 {
+            return Err(ZmqError::ReturnToSender {
+                reason: "ROUTER messages start with the identity of the peer",
+                message,
+            });
+        }
+        let mandatory = self.backend.socket_options().router_mandatory;
+        let peer_id: &[u8] = message.get(0).unwrap();
+        let mut peer = match self.backend.peers.get_mut(peer_id) {
+            Some(peer) => peer,
+            None if mandatory => return Err(ZmqError::HostUnreachable(message)),
+            None => {
+                log::trace!("Dropping message for unknown peer {:?}", message.get(0));
+                return Ok(());
+            }
+        };
+        message.pop_front();
+        match Pin::new(&mut peer.send_queue).try_send(Message::Message(message)) {
+            Err(ZmqError::BufferFull(_)) if !mandatory => {
+                log::trace!("Dropping message for slow peer {:?}", peer.key());
                 Ok(())
             }
-            None => Err(ZmqError::Other("Destination client not found by identity")),
+            res => res,
         }
     }
 }
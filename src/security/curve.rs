@@ -0,0 +1,493 @@
+//! CURVE mechanism, see https://rfc.zeromq.org/spec/26/
+//!
+//! Once the handshake is done every frame is encrypted into a MESSAGE
+//! command by the codec, see [`CurveEncoder`] and [`CurveDecoder`]
+
+use super::recv_command;
+use super::zap::{self, ZapCredentials};
+use crate::codec::{CodecError, CodecResult, FramedIo, Message, ZmqCommand, ZmqCommandName};
+use crate::endpoint::Endpoint;
+use crate::util::{self, PeerIdentity};
+use crate::{PeerMetadata, SocketOptions, SocketType, ZmqError, ZmqResult};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use crypto_box::aead::rand_core::RngCore;
+use crypto_box::aead::{Aead, OsRng};
+use crypto_box::{Nonce, PublicKey, SalsaBox, SecretKey};
+use crypto_secretbox::{KeyInit, XSalsa20Poly1305};
+use futures::SinkExt;
+use std::convert::TryFrom;
+use std::fmt::Debug;
+
+const HELLO_SIZE: usize = 194;
+const WELCOME_SIZE: usize = 160;
+const COOKIE_SIZE: usize = 96;
+const INITIATE_MIN_SIZE: usize = COOKIE_SIZE + 8 + 16 + 128;
+const MAC_SIZE: usize = 16;
+const MESSAGE_PREFIX: &[u8] = b"\x07MESSAGE";
+
+/// A long-term CURVE key pair. Keys are usually shared in their
+/// [`super::z85`] form
+///
+/// # Examples
+/// ```
+/// # use zeromq::{CurveKeyPair, SocketOptions};
+/// let server = CurveKeyPair::new();
+/// let server_options = SocketOptions::builder()
+///     .curve_server(server.clone())
+///     .build()
+///     .unwrap();
+/// let client_options = SocketOptions::builder()
+///     .curve_client(server.public_key, CurveKeyPair::new())
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct CurveKeyPair {
+    pub public_key: [u8; 32],
+    pub secret_key: [u8; 32],
+}
+
+impl CurveKeyPair {
+    /// Generates a random key pair
+    pub fn new() -> Self {
+        Self::from_secret_key(SecretKey::generate(&mut OsRng).to_bytes())
+    }
+
+    /// Derives the public key matching `secret_key`
+    pub fn from_secret_key(secret_key: [u8; 32]) -> Self {
+        let public_key = SecretKey::from(secret_key).public_key().to_bytes();
+        Self {
+            public_key,
+            secret_key,
+        }
+    }
+}
+
+impl Default for CurveKeyPair {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for CurveKeyPair {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CurveKeyPair")
+            .field("public_key", &super::z85::encode(&self.public_key))
+            .finish()
+    }
+}
+
+fn nonce(prefix: &[u8], suffix: &[u8]) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[..prefix.len()].copy_from_slice(prefix);
+    nonce[prefix.len()..].copy_from_slice(suffix);
+    nonce
+}
+
+fn short_nonce(prefix: &[u8], counter: u64) -> Nonce {
+    nonce(prefix, &counter.to_be_bytes())
+}
+
+fn random_nonce_suffix() -> [u8; 16] {
+    let mut suffix = [0u8; 16];
+    OsRng.fill_bytes(&mut suffix);
+    suffix
+}
+
+fn public_key(bytes: &[u8]) -> PublicKey {
+    PublicKey::from(<[u8; 32]>::try_from(bytes).expect("Public keys are 32 bytes long"))
+}
+
+fn malformed(command: &'static str) -> ZmqError {
+    ZmqError::Codec(CodecError::Command(command))
+}
+
+fn crypto_failed(what: &str) -> ZmqError {
+    ZmqError::AuthenticationFailed(format!("Failed to open {} box", what))
+}
+
+/// Encrypts outgoing frames into MESSAGE commands
+pub(crate) struct CurveEncoder {
+    session: SalsaBox,
+    nonce_prefix: &'static [u8],
+    nonce: u64,
+}
+
+impl CurveEncoder {
+    /// `flags` is the MORE (0x01) and COMMAND (0x02) bits of the frame
+    pub(crate) fn encrypt(&mut self, flags: u8, frame: &[u8]) -> Bytes {
+        let mut plaintext = Vec::with_capacity(frame.len() + 1);
+        plaintext.push(flags);
+        plaintext.extend_from_slice(frame);
+        let ciphertext = self
+            .session
+            .encrypt(
+                &short_nonce(self.nonce_prefix, self.nonce),
+                plaintext.as_slice(),
+            )
+            .expect("Encryption with a valid key can't fail");
+
+        let mut message = BytesMut::with_capacity(MESSAGE_PREFIX.len() + 8 + ciphertext.len());
+        message.extend_from_slice(MESSAGE_PREFIX);
+        message.put_u64(self.nonce);
+        message.extend_from_slice(&ciphertext);
+        self.nonce += 1;
+        message.freeze()
+    }
+}
+
+impl Debug for CurveEncoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CurveEncoder")
+            .field("nonce", &self.nonce)
+            .finish()
+    }
+}
+
+/// Decrypts MESSAGE commands sent by the peer
+pub(crate) struct CurveDecoder {
+    session: SalsaBox,
+    nonce_prefix: &'static [u8],
+    peer_nonce: u64,
+}
+
+impl CurveDecoder {
+    /// Returns the flags of the frame, see [`CurveEncoder::encrypt`], along
+    /// with its content
+    pub(crate) fn decrypt(&mut self, message: &[u8]) -> CodecResult<(u8, BytesMut)> {
+        if !message.starts_with(MESSAGE_PREFIX) {
+            return Err(CodecError::Decode("Expected a MESSAGE command"));
+        }
+        let mut message = &message[MESSAGE_PREFIX.len()..];
+        if message.len() < 8 + MAC_SIZE + 1 {
+            return Err(CodecError::Decode("Malformed MESSAGE command"));
+        }
+        let nonce = message.get_u64();
+        if nonce <= self.peer_nonce {
+            return Err(CodecError::Decode("Invalid MESSAGE nonce"));
+        }
+        self.peer_nonce = nonce;
+        let plaintext = self
+            .session
+            .decrypt(&short_nonce(self.nonce_prefix, nonce), message)
+            .map_err(|_| CodecError::Decode("Failed to decrypt MESSAGE command"))?;
+        let mut frame = BytesMut::from(plaintext.as_slice());
+        let flags = frame.get_u8();
+        Ok((flags, frame))
+    }
+}
+
+impl Debug for CurveDecoder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CurveDecoder")
+            .field("peer_nonce", &self.peer_nonce)
+            .finish()
+    }
+}
+
+/// Encrypts everything sent over `raw_socket` from now on
+fn secure(
+    raw_socket: &mut FramedIo,
+    peer_key: &PublicKey,
+    secret_key: &SecretKey,
+    as_server: bool,
+    nonce: u64,
+    peer_nonce: u64,
+) {
+    let (encode_prefix, decode_prefix): (&[u8], &[u8]) = if as_server {
+        (b"CurveZMQMESSAGES", b"CurveZMQMESSAGEC")
+    } else {
+        (b"CurveZMQMESSAGEC", b"CurveZMQMESSAGES")
+    };
+    raw_socket
+        .write_half
+        .encoder_mut()
+        .set_encoder(CurveEncoder {
+            session: SalsaBox::new(peer_key, secret_key),
+            nonce_prefix: encode_prefix,
+            nonce,
+        });
+    raw_socket
+        .read_half
+        .decoder_mut()
+        .set_decoder(CurveDecoder {
+            session: SalsaBox::new(peer_key, secret_key),
+            nonce_prefix: decode_prefix,
+            peer_nonce,
+        });
+}
+
+pub(super) async fn client_handshake(
+    raw_socket: &mut FramedIo,
+    server_key: &[u8; 32],
+    keypair: &CurveKeyPair,
+    socket_type: SocketType,
+    options: &SocketOptions,
+) -> ZmqResult<(PeerIdentity, PeerMetadata)> {
+    let server_key = PublicKey::from(*server_key);
+    let secret_key = SecretKey::from(keypair.secret_key);
+    let cn_secret = SecretKey::generate(&mut OsRng);
+    let cn_public = cn_secret.public_key();
+    let mut cn_nonce = 1;
+
+    // HELLO proves we know the server's public key
+    let signature = SalsaBox::new(&server_key, &cn_secret)
+        .encrypt(&short_nonce(b"CurveZMQHELLO---", cn_nonce), &[0u8; 64][..])
+        .expect("Encryption with a valid key can't fail");
+    let mut hello = BytesMut::with_capacity(HELLO_SIZE);
+    hello.extend_from_slice(&[1, 0]);
+    // Anti-amplification padding
+    hello.extend_from_slice(&[0u8; 72]);
+    hello.extend_from_slice(cn_public.as_bytes());
+    hello.put_u64(cn_nonce);
+    hello.extend_from_slice(&signature);
+    cn_nonce += 1;
+    raw_socket
+        .write_half
+        .send(Message::Command(ZmqCommand::new(
+            ZmqCommandName::HELLO,
+            hello.freeze(),
+        )))
+        .await?;
+
+    let welcome = recv_command(raw_socket, ZmqCommandName::WELCOME).await?;
+    if welcome.data.len() != WELCOME_SIZE {
+        return Err(malformed("Malformed WELCOME command"));
+    }
+    let welcome = SalsaBox::new(&server_key, &cn_secret)
+        .decrypt(
+            &nonce(b"WELCOME-", &welcome.data[..16]),
+            &welcome.data[16..],
+        )
+        .map_err(|_| crypto_failed("WELCOME"))?;
+    let cn_server = public_key(&welcome[..32]);
+    let cookie = &welcome[32..];
+    let session = SalsaBox::new(&cn_server, &cn_secret);
+
+    // The vouch proves we own the long-term key we present in INITIATE
+    let vouch_nonce = random_nonce_suffix();
+    let mut vouch = Vec::with_capacity(64);
+    vouch.extend_from_slice(cn_public.as_bytes());
+    vouch.extend_from_slice(server_key.as_bytes());
+    let vouch = SalsaBox::new(&cn_server, &secret_key)
+        .encrypt(&nonce(b"VOUCH---", &vouch_nonce), vouch.as_slice())
+        .expect("Encryption with a valid key can't fail");
+    let metadata = ZmqCommand::with_properties(
+        ZmqCommandName::INITIATE,
+        &ZmqCommand::metadata(socket_type, options),
+    );
+    let mut plaintext = Vec::with_capacity(128 + metadata.data.len());
+    plaintext.extend_from_slice(&keypair.public_key);
+    plaintext.extend_from_slice(&vouch_nonce);
+    plaintext.extend_from_slice(&vouch);
+    plaintext.extend_from_slice(&metadata.data);
+    let ciphertext = session
+        .encrypt(
+            &short_nonce(b"CurveZMQINITIATE", cn_nonce),
+            plaintext.as_slice(),
+        )
+        .expect("Encryption with a valid key can't fail");
+    let mut initiate = BytesMut::with_capacity(COOKIE_SIZE + 8 + ciphertext.len());
+    initiate.extend_from_slice(cookie);
+    initiate.put_u64(cn_nonce);
+    initiate.extend_from_slice(&ciphertext);
+    cn_nonce += 1;
+    raw_socket
+        .write_half
+        .send(Message::Command(ZmqCommand::new(
+            ZmqCommandName::INITIATE,
+            initiate.freeze(),
+        )))
+        .await?;
+
+    let ready = recv_command(raw_socket, ZmqCommandName::READY).await?;
+    if ready.data.len() < 8 + MAC_SIZE {
+        return Err(malformed("Malformed READY command"));
+    }
+    let mut data = ready.data.clone();
+    let peer_nonce = data.get_u64();
+    let metadata = session
+        .decrypt(&short_nonce(b"CurveZMQREADY---", peer_nonce), data.as_ref())
+        .map_err(|_| crypto_failed("READY"))?;
+    let peer = util::check_peer_metadata(
+        socket_type,
+        &ZmqCommand::new(ZmqCommandName::READY, metadata.into()),
+    )?;
+    secure(
+        raw_socket, &cn_server, &cn_secret, false, cn_nonce, peer_nonce,
+    );
+    Ok(peer)
+}
+
+/// Returns the identity and metadata of the peer along with the user id
+/// assigned by the ZAP handler
+pub(super) async fn server_handshake(
+    raw_socket: &mut FramedIo,
+    keypair: &CurveKeyPair,
+    socket_type: SocketType,
+    options: &SocketOptions,
+    address: &Endpoint,
+) -> ZmqResult<(PeerIdentity, PeerMetadata, Option<String>)> {
+    let secret_key = SecretKey::from(keypair.secret_key);
+
+    let hello = recv_command(raw_socket, ZmqCommandName::HELLO).await?;
+    if hello.data.len() != HELLO_SIZE || hello.data[..2] != [1, 0] {
+        return Err(malformed("Malformed HELLO command"));
+    }
+    let cn_client = public_key(&hello.data[74..106]);
+    let hello_nonce = (&hello.data[106..114]).get_u64();
+    SalsaBox::new(&cn_client, &secret_key)
+        .decrypt(
+            &short_nonce(b"CurveZMQHELLO---", hello_nonce),
+            &hello.data[114..],
+        )
+        .map_err(|_| crypto_failed("HELLO"))?;
+
+    // The cookie holds our short-term key. We keep the connection state
+    // anyway but check the client hands it back untouched
+    let cn_secret = SecretKey::generate(&mut OsRng);
+    let cookie_key = XSalsa20Poly1305::generate_key(&mut OsRng);
+    let cookie_nonce = random_nonce_suffix();
+    let mut cookie_plaintext = Vec::with_capacity(64);
+    cookie_plaintext.extend_from_slice(cn_client.as_bytes());
+    cookie_plaintext.extend_from_slice(&cn_secret.to_bytes());
+    let cookie = XSalsa20Poly1305::new(&cookie_key)
+        .encrypt(
+            &nonce(b"COOKIE--", &cookie_nonce),
+            cookie_plaintext.as_slice(),
+        )
+        .expect("Encryption with a valid key can't fail");
+    let mut plaintext = Vec::with_capacity(128);
+    plaintext.extend_from_slice(cn_secret.public_key().as_bytes());
+    plaintext.extend_from_slice(&cookie_nonce);
+    plaintext.extend_from_slice(&cookie);
+    let welcome_nonce = random_nonce_suffix();
+    let ciphertext = SalsaBox::new(&cn_client, &secret_key)
+        .encrypt(&nonce(b"WELCOME-", &welcome_nonce), plaintext.as_slice())
+        .expect("Encryption with a valid key can't fail");
+    let mut welcome = BytesMut::with_capacity(WELCOME_SIZE);
+    welcome.extend_from_slice(&welcome_nonce);
+    welcome.extend_from_slice(&ciphertext);
+    raw_socket
+        .write_half
+        .send(Message::Command(ZmqCommand::new(
+            ZmqCommandName::WELCOME,
+            welcome.freeze(),
+        )))
+        .await?;
+
+    let initiate = recv_command(raw_socket, ZmqCommandName::INITIATE).await?;
+    let data = &initiate.data;
+    if data.len() < INITIATE_MIN_SIZE {
+        return Err(malformed("Malformed INITIATE command"));
+    }
+    let returned_cookie = XSalsa20Poly1305::new(&cookie_key)
+        .decrypt(&nonce(b"COOKIE--", &data[..16]), &data[16..COOKIE_SIZE])
+        .map_err(|_| crypto_failed("cookie"))?;
+    if returned_cookie != cookie_plaintext {
+        return Err(ZmqError::AuthenticationFailed("Invalid cookie".into()));
+    }
+    let peer_nonce = (&data[COOKIE_SIZE..COOKIE_SIZE + 8]).get_u64();
+    if peer_nonce <= hello_nonce {
+        return Err(malformed("Invalid INITIATE nonce"));
+    }
+    let session = SalsaBox::new(&cn_client, &cn_secret);
+    let plaintext = session
+        .decrypt(
+            &short_nonce(b"CurveZMQINITIATE", peer_nonce),
+            &data[COOKIE_SIZE + 8..],
+        )
+        .map_err(|_| crypto_failed("INITIATE"))?;
+    let client_key = public_key(&plaintext[..32]);
+    let vouch = SalsaBox::new(&client_key, &cn_secret)
+        .decrypt(&nonce(b"VOUCH---", &plaintext[32..48]), &plaintext[48..128])
+        .map_err(|_| crypto_failed("vouch"))?;
+    if vouch[..32] != cn_client.as_bytes()[..] {
+        return Err(ZmqError::AuthenticationFailed("Invalid vouch".into()));
+    }
+    let (peer_id, peer_metadata) = util::check_peer_metadata(
+        socket_type,
+        &ZmqCommand::new(
+            ZmqCommandName::INITIATE,
+            Bytes::copy_from_slice(&plaintext[128..]),
+        ),
+    )?;
+    let credentials = ZapCredentials::Curve {
+        public_key: *client_key.as_bytes(),
+    };
+    let user_id = zap::authenticate(raw_socket, options, address, credentials).await?;
+
+    let cn_nonce = 1;
+    let metadata = ZmqCommand::with_properties(
+        ZmqCommandName::READY,
+        &ZmqCommand::metadata(socket_type, options),
+    );
+    let ciphertext = session
+        .encrypt(
+            &short_nonce(b"CurveZMQREADY---", cn_nonce),
+            metadata.data.as_ref(),
+        )
+        .expect("Encryption with a valid key can't fail");
+    let mut ready = BytesMut::with_capacity(8 + ciphertext.len());
+    ready.put_u64(cn_nonce);
+    ready.extend_from_slice(&ciphertext);
+    raw_socket
+        .write_half
+        .send(Message::Command(ZmqCommand::new(
+            ZmqCommandName::READY,
+            ready.freeze(),
+        )))
+        .await?;
+    secure(
+        raw_socket,
+        &cn_client,
+        &cn_secret,
+        true,
+        cn_nonce + 1,
+        peer_nonce,
+    );
+    Ok((peer_id, peer_metadata, user_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn test_key_pair_from_secret_key() {
+        // Test keys from https://rfc.zeromq.org/spec/32/
+        let secret_key = super::super::z85::decode("JTKVSB%%)wK0E.X)V>+}o?pNmC{O&4W4b!Ni{Lh6")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let keypair = CurveKeyPair::from_secret_key(secret_key);
+        assert_eq!(
+            super::super::z85::encode(&keypair.public_key).unwrap(),
+            "rq:rM>}U?@Lns47E1%kR.o@n%FcmmsL/@{H8]yf7"
+        );
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let client = SecretKey::generate(&mut OsRng);
+        let server = SecretKey::generate(&mut OsRng);
+        let mut encoder = CurveEncoder {
+            session: SalsaBox::new(&server.public_key(), &client),
+            nonce_prefix: b"CurveZMQMESSAGEC",
+            nonce: 3,
+        };
+        let mut decoder = CurveDecoder {
+            session: SalsaBox::new(&client.public_key(), &server),
+            nonce_prefix: b"CurveZMQMESSAGEC",
+            peer_nonce: 2,
+        };
+        let message = encoder.encrypt(0x01, b"Hello");
+        let (flags, frame) = decoder.decrypt(&message).unwrap();
+        assert_eq!(flags, 0x01);
+        assert_eq!(frame.as_ref(), b"Hello");
+        // Replayed messages are refused
+        assert!(decoder.decrypt(&message).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Security mechanisms negotiated during the ZMTP handshake, see
+//! https://rfc.zeromq.org/spec/23/#authentication-and-confidentiality
+
+mod curve;
+mod plain;
+pub mod z85;
+mod zap;
+
+pub use curve::CurveKeyPair;
+pub(crate) use curve::{CurveDecoder, CurveEncoder};
+pub(crate) use zap::SharedZapHandler;
+pub use zap::{ZapAuthenticator, ZapCredentials, ZapHandler, ZapReply, ZapRequest, ZapStatus};
+
+use crate::codec::mechanism::ZmqMechanism;
+use crate::codec::{FramedIo, Message, ZmqCommand, ZmqCommandName};
+use crate::endpoint::Endpoint;
+use crate::metadata::PeerMetadata;
+use crate::util::{self, PeerIdentity};
+use crate::{SocketOptions, SocketType, ZmqError, ZmqResult};
+
+use futures::StreamExt;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Decides whether a PLAIN client may connect, given the username and
+/// password it presented. Implemented for closures
+///
+/// # Examples
+/// ```
+/// # use zeromq::SocketOptions;
+/// let options = SocketOptions::builder()
+///     .plain_server(|username: &str, password: &str| username == "admin" && password == "secret")
+///     .build()
+///     .unwrap();
+/// ```
+pub trait PlainValidator: Send + Sync {
+    fn validate(&self, username: &str, password: &str) -> bool;
+}
+
+impl<F> PlainValidator for F
+where
+    F: Fn(&str, &str) -> bool + Send + Sync,
+{
+    fn validate(&self, username: &str, password: &str) -> bool {
+        self(username, password)
+    }
+}
+
+/// Security mechanism used by a socket along with its role and credentials
+#[derive(Clone)]
+pub(crate) enum Security {
+    Null,
+    PlainClient {
+        username: String,
+        password: String,
+    },
+    PlainServer {
+        validator: Arc<dyn PlainValidator>,
+    },
+    CurveClient {
+        server_key: [u8; 32],
+        keypair: CurveKeyPair,
+    },
+    CurveServer {
+        keypair: CurveKeyPair,
+    },
+}
+
+impl Debug for Security {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Security::Null => write!(f, "Null"),
+            Security::PlainClient { username, .. } => f
+                .debug_struct("PlainClient")
+                .field("username", username)
+                .finish(),
+            Security::PlainServer { .. } => write!(f, "PlainServer"),
+            Security::CurveClient { keypair, .. } => f
+                .debug_struct("CurveClient")
+                .field("keypair", keypair)
+                .finish(),
+            Security::CurveServer { keypair } => f
+                .debug_struct("CurveServer")
+                .field("keypair", keypair)
+                .finish(),
+        }
+    }
+}
+
+impl Security {
+    pub(crate) fn mechanism(&self) -> ZmqMechanism {
+        match self {
+            Security::Null => ZmqMechanism::NULL,
+            Security::PlainClient { .. } | Security::PlainServer { .. } => ZmqMechanism::PLAIN,
+            Security::CurveClient { .. } | Security::CurveServer { .. } => ZmqMechanism::CURVE,
+        }
+    }
+
+    pub(crate) fn as_server(&self) -> bool {
+        matches!(
+            self,
+            Security::PlainServer { .. } | Security::CurveServer { .. }
+        )
+    }
+
+    /// Runs the mechanism specific part of the handshake following the
+    /// greeting, consulting the ZAP handler of the socket if needed. Returns
+    /// the identity and metadata of the peer
+    pub(crate) async fn handshake(
+        &self,
+        raw_socket: &mut FramedIo,
+        socket_type: SocketType,
+        options: &SocketOptions,
+        address: &Endpoint,
+    ) -> ZmqResult<(PeerIdentity, PeerMetadata)> {
+        let (peer_id, mut metadata, user_id) = match self {
+            Security::Null => {
+                let user_id =
+                    zap::authenticate(raw_socket, options, address, ZapCredentials::Null).await?;
+                let (peer_id, metadata) =
+                    util::ready_exchange(raw_socket, socket_type, options).await?;
+                (peer_id, metadata, user_id)
+            }
+            Security::PlainClient { username, password } => {
+                let (peer_id, metadata) =
+                    plain::client_handshake(raw_socket, username, password, socket_type, options)
+                        .await?;
+                (peer_id, metadata, None)
+            }
+            Security::PlainServer { validator } => {
+                plain::server_handshake(
+                    raw_socket,
+                    validator.as_ref(),
+                    socket_type,
+                    options,
+                    address,
+                )
+                .await?
+            }
+            Security::CurveClient {
+                server_key,
+                keypair,
+            } => {
+                let (peer_id, metadata) =
+                    curve::client_handshake(raw_socket, server_key, keypair, socket_type, options)
+                        .await?;
+                (peer_id, metadata, None)
+            }
+            Security::CurveServer { keypair } => {
+                curve::server_handshake(raw_socket, keypair, socket_type, options, address).await?
+            }
+        };
+        if let Some(user_id) = user_id.filter(|user_id| !user_id.is_empty()) {
+            metadata.insert("User-Id", user_id);
+        }
+        Ok((peer_id, metadata))
+    }
+}
+
+/// Waits for the next handshake command. ERROR commands sent by the peer are
+/// turned into errors
+async fn recv_command(
+    raw_socket: &mut FramedIo,
+    expected: ZmqCommandName,
+) -> ZmqResult<ZmqCommand> {
+    match raw_socket.read_half.next().await {
+        Some(Ok(Message::Command(command))) if command.name == expected => Ok(command),
+        Some(Ok(Message::Command(command))) if command.name == ZmqCommandName::ERROR => {
+            Err(ZmqError::AuthenticationFailed(command.error_reason()))
+        }
+        Some(Ok(_)) => Err(ZmqError::Other("Unexpected message during handshake")),
+        Some(Err(e)) => Err(e.into()),
+        None => Err(ZmqError::Other("Connection closed during handshake")),
+    }
+}
@@ -0,0 +1,97 @@
+//! PLAIN mechanism, see https://rfc.zeromq.org/spec/24/
+
+use super::zap::{self, ZapCredentials};
+use super::{recv_command, PlainValidator};
+use crate::codec::{take_short, CodecResult, FramedIo, Message, ZmqCommand, ZmqCommandName};
+use crate::endpoint::Endpoint;
+use crate::util::{self, PeerIdentity};
+use crate::{PeerMetadata, SocketOptions, SocketType, ZmqError, ZmqResult};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures::SinkExt;
+
+fn hello(username: &str, password: &str) -> ZmqCommand {
+    let mut data = BytesMut::with_capacity(username.len() + password.len() + 2);
+    data.put_u8(username.len() as u8);
+    data.extend_from_slice(username.as_bytes());
+    data.put_u8(password.len() as u8);
+    data.extend_from_slice(password.as_bytes());
+    ZmqCommand::new(ZmqCommandName::HELLO, data.freeze())
+}
+
+fn parse_hello(command: &ZmqCommand) -> CodecResult<(Bytes, Bytes)> {
+    let mut data = command.data.clone();
+    let username = take_short(&mut data)?;
+    let password = take_short(&mut data)?;
+    Ok((username, password))
+}
+
+pub(super) async fn client_handshake(
+    raw_socket: &mut FramedIo,
+    username: &str,
+    password: &str,
+    socket_type: SocketType,
+    options: &SocketOptions,
+) -> ZmqResult<(PeerIdentity, PeerMetadata)> {
+    raw_socket
+        .write_half
+        .send(Message::Command(hello(username, password)))
+        .await?;
+    recv_command(raw_socket, ZmqCommandName::WELCOME).await?;
+
+    let metadata = ZmqCommand::metadata(socket_type, options);
+    let initiate = ZmqCommand::with_properties(ZmqCommandName::INITIATE, &metadata);
+    raw_socket
+        .write_half
+        .send(Message::Command(initiate))
+        .await?;
+    let ready = recv_command(raw_socket, ZmqCommandName::READY).await?;
+    util::check_peer_metadata(socket_type, &ready)
+}
+
+/// Returns the identity and metadata of the peer along with the user id
+/// assigned by the ZAP handler
+pub(super) async fn server_handshake(
+    raw_socket: &mut FramedIo,
+    validator: &dyn PlainValidator,
+    socket_type: SocketType,
+    options: &SocketOptions,
+    address: &Endpoint,
+) -> ZmqResult<(PeerIdentity, PeerMetadata, Option<String>)> {
+    let hello = recv_command(raw_socket, ZmqCommandName::HELLO).await?;
+    let (username, password) = parse_hello(&hello)?;
+    let credentials = match (
+        std::str::from_utf8(&username),
+        std::str::from_utf8(&password),
+    ) {
+        (Ok(username), Ok(password)) if validator.validate(username, password) => {
+            ZapCredentials::Plain {
+                username: username.to_owned(),
+                password: password.to_owned(),
+            }
+        }
+        _ => {
+            // libzmq expects the ZAP status code as reason
+            raw_socket
+                .write_half
+                .send(Message::Command(ZmqCommand::error("400")))
+                .await?;
+            return Err(ZmqError::AuthenticationFailed(
+                "Invalid username or password".into(),
+            ));
+        }
+    };
+    let user_id = zap::authenticate(raw_socket, options, address, credentials).await?;
+    let welcome = ZmqCommand::new(ZmqCommandName::WELCOME, Bytes::new());
+    raw_socket
+        .write_half
+        .send(Message::Command(welcome))
+        .await?;
+
+    let initiate = recv_command(raw_socket, ZmqCommandName::INITIATE).await?;
+    let (peer_id, peer_metadata) = util::check_peer_metadata(socket_type, &initiate)?;
+    let metadata = ZmqCommand::metadata(socket_type, options);
+    let ready = ZmqCommand::with_properties(ZmqCommandName::READY, &metadata);
+    raw_socket.write_half.send(Message::Command(ready)).await?;
+    Ok((peer_id, peer_metadata, user_id))
+}
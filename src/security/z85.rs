@@ -0,0 +1,82 @@
+//! Z85 encoding used to print CURVE keys, see https://rfc.zeromq.org/spec/32/
+//!
+//! # Examples
+//! ```
+//! let key = zeromq::z85::decode("rq:rM>}U?@Lns47E1%kR.o@n%FcmmsL/@{H8]yf7").unwrap();
+//! assert_eq!(key.len(), 32);
+//! assert_eq!(
+//!     zeromq::z85::encode(&key).unwrap(),
+//!     "rq:rM>}U?@Lns47E1%kR.o@n%FcmmsL/@{H8]yf7"
+//! );
+//! ```
+
+use crate::{ZmqError, ZmqResult};
+
+const ALPHABET: &[u8; 85] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ.-:+=^!/*?&<>()[]{}@%$#";
+
+/// Encodes `data`, whose length must be a multiple of 4
+pub fn encode(data: &[u8]) -> ZmqResult<String> {
+    if !data.len().is_multiple_of(4) {
+        return Err(ZmqError::Other(
+            "Z85 encoded data length must be a multiple of 4",
+        ));
+    }
+    let mut encoded = String::with_capacity(data.len() / 4 * 5);
+    for chunk in data.chunks(4) {
+        let mut value = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let mut digits = [0u8; 5];
+        for digit in digits.iter_mut().rev() {
+            *digit = ALPHABET[(value % 85) as usize];
+            value /= 85;
+        }
+        encoded.extend(digits.iter().map(|&c| c as char));
+    }
+    Ok(encoded)
+}
+
+/// Decodes `encoded`, whose length must be a multiple of 5
+pub fn decode(encoded: &str) -> ZmqResult<Vec<u8>> {
+    let encoded = encoded.as_bytes();
+    if !encoded.len().is_multiple_of(5) {
+        return Err(ZmqError::Other("Z85 string length must be a multiple of 5"));
+    }
+    let mut data = Vec::with_capacity(encoded.len() / 5 * 4);
+    for chunk in encoded.chunks(5) {
+        let mut value: u64 = 0;
+        for c in chunk {
+            let digit = ALPHABET
+                .iter()
+                .position(|a| a == c)
+                .ok_or(ZmqError::Other("Invalid character in Z85 string"))?;
+            value = value * 85 + digit as u64;
+        }
+        if value > u64::from(u32::MAX) {
+            return Err(ZmqError::Other("Invalid Z85 string"));
+        }
+        data.extend_from_slice(&(value as u32).to_be_bytes());
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reference_vector() {
+        let data = [0x86, 0x4F, 0xD2, 0x6F, 0xB5, 0x59, 0xF7, 0x5B];
+        assert_eq!(encode(&data).unwrap(), "HelloWorld");
+        assert_eq!(decode("HelloWorld").unwrap(), data);
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(encode(&[1, 2, 3]).is_err());
+        assert!(decode("Hello").is_ok());
+        assert!(decode("Hell").is_err());
+        assert!(decode("Hell\"").is_err());
+        // Above 2^32 - 1
+        assert!(decode("%%%%%").is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! ZAP, the ZeroMQ Authentication Protocol, see https://rfc.zeromq.org/spec/27/
+//!
+//! Instead of a REP socket bound to `inproc://zeromq.zap.01`, handlers are
+//! set per socket with [`crate::SocketOptionsBuilder::zap_handler`]
+
+use super::z85;
+use crate::codec::{FramedIo, Message, ZmqCommand};
+use crate::endpoint::{Endpoint, Host};
+use crate::{SocketOptions, ZmqError, ZmqResult};
+
+use async_trait::async_trait;
+use futures::SinkExt;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Credentials presented by the peer, depending on the security mechanism
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZapCredentials {
+    Null,
+    Plain { username: String, password: String },
+    Curve { public_key: [u8; 32] },
+}
+
+/// Sent to the [`ZapHandler`] for every connection going through the
+/// handshake
+#[derive(Debug, Clone)]
+pub struct ZapRequest {
+    /// Equivalent of `ZMQ_ZAP_DOMAIN` of the socket, empty by default
+    pub domain: String,
+    /// Remote end of the connection
+    pub address: Endpoint,
+    /// Identity of the authenticating socket itself, if set. As in RFC 27,
+    /// this says nothing about the peer, whose credentials are in
+    /// `credentials`
+    pub identity: Option<Vec<u8>>,
+    pub credentials: ZapCredentials,
+}
+
+impl ZapRequest {
+    /// Name of the security mechanism, as in the ZAP protocol
+    pub fn mechanism(&self) -> &'static str {
+        match self.credentials {
+            ZapCredentials::Null => "NULL",
+            ZapCredentials::Plain { .. } => "PLAIN",
+            ZapCredentials::Curve { .. } => "CURVE",
+        }
+    }
+
+    /// IP address of the peer, for TCP, TLS and WebSocket connections
+    pub fn ip(&self) -> Option<IpAddr> {
+        let host = match &self.address {
+            Endpoint::Tcp(host, _)
+            | Endpoint::TlsTcp(host, _)
+            | Endpoint::Ws(host, ..)
+            | Endpoint::Wss(host, ..) => host,
+            _ => return None,
+        };
+        match host {
+            Host::Ipv4(ip) => Some(IpAddr::V4(*ip)),
+            Host::Ipv6(ip) => Some(IpAddr::V6(*ip)),
+            Host::Domain(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZapStatus {
+    /// 200, the peer is accepted
+    Success,
+    /// 300, the peer may retry later
+    TemporaryError,
+    /// 400, the peer is denied
+    Failure,
+    /// 500, the handler failed, the connection is dropped without telling
+    /// the peer why
+    InternalError,
+}
+
+impl ZapStatus {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ZapStatus::Success => "200",
+            ZapStatus::TemporaryError => "300",
+            ZapStatus::Failure => "400",
+            ZapStatus::InternalError => "500",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ZapReply {
+    pub status: ZapStatus,
+    pub status_text: String,
+    /// Reported as the `User-Id` property of the peer's
+    /// [`crate::PeerMetadata`]
+    pub user_id: String,
+}
+
+impl ZapReply {
+    pub fn accept(user_id: impl Into<String>) -> Self {
+        Self {
+            status: ZapStatus::Success,
+            status_text: "OK".into(),
+            user_id: user_id.into(),
+        }
+    }
+
+    pub fn deny(status_text: impl Into<String>) -> Self {
+        Self {
+            status: ZapStatus::Failure,
+            status_text: status_text.into(),
+            user_id: String::new(),
+        }
+    }
+}
+
+/// Decides which peers a socket accepts. It is consulted by servers of the
+/// PLAIN and CURVE mechanisms, once the peer proved its credentials, and by
+/// both ends of NULL connections
+///
+/// # Examples
+/// ```
+/// # use zeromq::{SocketOptions, ZapCredentials, ZapHandler, ZapReply, ZapRequest};
+/// struct OnlyAdmin;
+///
+/// #[async_trait::async_trait]
+/// impl ZapHandler for OnlyAdmin {
+///     async fn authenticate(&self, request: &ZapRequest) -> ZapReply {
+///         match &request.credentials {
+///             ZapCredentials::Plain { username, .. } if username == "admin" => {
+///                 ZapReply::accept("admin")
+///             }
+///             _ => ZapReply::deny("Not an admin"),
+///         }
+///     }
+/// }
+///
+/// let options = SocketOptions::builder()
+///     .zap_handler(OnlyAdmin)
+///     .build()
+///     .unwrap();
+/// ```
+#[async_trait]
+pub trait ZapHandler: Send + Sync {
+    async fn authenticate(&self, request: &ZapRequest) -> ZapReply;
+}
+
+#[derive(Clone)]
+pub(crate) struct SharedZapHandler(pub(crate) Arc<dyn ZapHandler>);
+
+impl Debug for SharedZapHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ZapHandler")
+    }
+}
+
+/// A [`ZapHandler`] filtering peers on their IP address and CURVE key.
+///
+/// When IP addresses are allowed, peers coming from any other address are
+/// denied. Otherwise only denied addresses are. Likewise, once CURVE keys
+/// are allowed, CURVE peers presenting any other key are denied. Accepted
+/// CURVE peers get the Z85 form of their key as user id
+///
+/// # Examples
+/// ```
+/// # use zeromq::{CurveKeyPair, SocketOptions, ZapAuthenticator};
+/// let server = CurveKeyPair::new();
+/// let client = CurveKeyPair::new();
+/// let authenticator = ZapAuthenticator::new()
+///     .allow_ip("127.0.0.1".parse().unwrap())
+///     .allow_curve_key(client.public_key);
+/// let options = SocketOptions::builder()
+///     .curve_server(server)
+///     .zap_handler(authenticator)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ZapAuthenticator {
+    allowed_ips: HashSet<IpAddr>,
+    denied_ips: HashSet<IpAddr>,
+    curve_keys: HashSet<[u8; 32]>,
+}
+
+impl ZapAuthenticator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allow_ip(mut self, ip: IpAddr) -> Self {
+        self.allowed_ips.insert(ip);
+        self
+    }
+
+    pub fn deny_ip(mut self, ip: IpAddr) -> Self {
+        self.denied_ips.insert(ip);
+        self
+    }
+
+    pub fn allow_curve_key(mut self, public_key: [u8; 32]) -> Self {
+        self.curve_keys.insert(public_key);
+        self
+    }
+}
+
+#[async_trait]
+impl ZapHandler for ZapAuthenticator {
+    async fn authenticate(&self, request: &ZapRequest) -> ZapReply {
+        if let Some(ip) = request.ip() {
+            if !self.allowed_ips.is_empty() && !self.allowed_ips.contains(&ip) {
+                return ZapReply::deny("Address not allowed");
+            }
+            if self.denied_ips.contains(&ip) {
+                return ZapReply::deny("Address denied");
+            }
+        }
+        match &request.credentials {
+            ZapCredentials::Curve { public_key } => {
+                if !self.curve_keys.is_empty() && !self.curve_keys.contains(public_key) {
+                    return ZapReply::deny("Unknown CURVE key");
+                }
+                ZapReply::accept(z85::encode(public_key).expect("Keys are 32 bytes long"))
+            }
+            _ => ZapReply::accept(""),
+        }
+    }
+}
+
+/// Asks the ZAP handler of the socket, if any, whether to accept the peer
+/// and returns the user id it assigned. Denied peers are told why with an
+/// ERROR command
+pub(super) async fn authenticate(
+    raw_socket: &mut FramedIo,
+    options: &SocketOptions,
+    address: &Endpoint,
+    credentials: ZapCredentials,
+) -> ZmqResult<Option<String>> {
+    let handler = match &options.zap_handler {
+        Some(handler) => handler.0.clone(),
+        None => return Ok(None),
+    };
+    let request = ZapRequest {
+        domain: options.zap_domain.clone(),
+        address: address.clone(),
+        identity: options.peer_id.clone().map(Vec::from),
+        credentials,
+    };
+    let reply = handler.authenticate(&request).await;
+    match reply.status {
+        ZapStatus::Success => Ok(Some(reply.user_id)),
+        ZapStatus::InternalError => Err(ZmqError::AuthenticationFailed(reply.status_text)),
+        status => {
+            raw_socket
+                .write_half
+                .send(Message::Command(ZmqCommand::error(status.code())))
+                .await?;
+            Err(ZmqError::AuthenticationFailed(reply.status_text))
+        }
+    }
+}
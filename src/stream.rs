@@ -0,0 +1,229 @@
+use crate::async_rt;
+use crate::codec::*;
+use crate::endpoint::Endpoint;
+use crate::error::{ZmqError, ZmqResult};
+use crate::message::*;
+use crate::transport::AcceptStopHandle;
+use crate::util::{ConnectHandle, PeerIdentity};
+use crate::{
+    MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketOptions, SocketRecv, SocketSend,
+    SocketType,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::channel::{mpsc, oneshot};
+use futures::{FutureExt, SinkExt, StreamExt};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub(crate) struct StreamPeer {
+    pub(crate) send_queue: ZmqSendQueue,
+    _recv_coro_stop: oneshot::Sender<()>,
+}
+
+pub(crate) struct StreamSocketBackend {
+    peers: DashMap<PeerIdentity, StreamPeer>,
+    socket_monitor: Mutex<Option<mpsc::Sender<SocketEvent>>>,
+    // Data and connect/disconnect notifications from all peers, already
+    // prefixed with the routing id
+    incoming: mpsc::Sender<ZmqMessage>,
+    socket_options: SocketOptions,
+}
+
+/// Routing id followed by an empty frame. Signals a connect or disconnect
+/// when received, and closes the connection when sent
+fn notification(peer_id: &PeerIdentity) -> ZmqMessage {
+    let mut message = ZmqMessage::from(Bytes::new());
+    message.push_front(peer_id.clone().into());
+    message
+}
+
+impl StreamSocketBackend {
+    pub(crate) fn new(incoming: mpsc::Sender<ZmqMessage>, socket_options: SocketOptions) -> Self {
+        Self {
+            peers: DashMap::new(),
+            socket_monitor: Mutex::new(None),
+            incoming,
+            socket_options,
+        }
+    }
+}
+
+impl SocketBackend for StreamSocketBackend {
+    fn socket_type(&self) -> SocketType {
+        SocketType::STREAM
+    }
+
+    fn shutdown(&self) {
+        self.peers.clear();
+    }
+
+    fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>> {
+        &self.socket_monitor
+    }
+
+    fn socket_options(&self) -> &SocketOptions {
+        &self.socket_options
+    }
+}
+
+impl MultiPeerBackend for StreamSocketBackend {
+    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) -> ZmqResult<()> {
+        let (mut recv_queue, send_queue) = io.into_queues(&self.socket_options);
+        let (sender, stop_receiver) = oneshot::channel();
+        self.peers.insert(
+            peer_id.clone(),
+            StreamPeer {
+                send_queue,
+                _recv_coro_stop: sender,
+            },
+        );
+        let backend = self;
+        let peer_id = peer_id.clone();
+        async_rt::task::spawn(async move {
+            let mut incoming = backend.incoming.clone();
+            if incoming.send(notification(&peer_id)).await.is_err() {
+                return;
+            }
+            let mut stop_receiver = stop_receiver.fuse();
+            loop {
+                futures::select! {
+                    _ = stop_receiver => break,
+                    message = recv_queue.next().fuse() => {
+                        match message {
+                            Some(Ok(Message::Message(mut m))) => {
+                                m.push_front(peer_id.clone().into());
+                                if incoming.send(m).await.is_err() {
+                                    break;
+                                }
+                            }
+                            Some(Ok(_)) => {}
+                            Some(Err(e)) => {
+                                log::debug!("STREAM peer {:?} failed: {}", peer_id, e);
+                                backend.peer_disconnected(&peer_id);
+                                let _ = incoming.send(notification(&peer_id)).await;
+                                break;
+                            }
+                            None => {
+                                backend.peer_disconnected(&peer_id);
+                                let _ = incoming.send(notification(&peer_id)).await;
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn peer_disconnected(&self, peer_id: &PeerIdentity) {
+        self.peers.remove(peer_id);
+    }
+}
+
+/// Equivalent of libzmq's `ZMQ_STREAM`. Talks to plain TCP peers that don't
+/// speak ZMTP.
+///
+/// Every message returned by [`SocketRecv::recv`] has two frames: the routing
+/// id of the connection and the data read from it. A zero-length data frame
+/// means that the connection was just established or closed by the peer.
+///
+/// Messages passed to [`SocketSend::send`] have to start with a routing id.
+/// The remaining frames are written to that connection as is. A routing id
+/// followed by a single empty frame closes the connection.
+pub struct StreamSocket {
+    backend: Arc<StreamSocketBackend>,
+    incoming: mpsc::Receiver<ZmqMessage>,
+    binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
+}
+
+impl Drop for StreamSocket {
+    fn drop(&mut self) {
+        self.backend.shutdown();
+    }
+}
+
+#[async_trait]
+impl Socket for StreamSocket {
+    fn with_options(options: SocketOptions) -> Self {
+        let (sender, receiver) = mpsc::channel(1024);
+        Self {
+            backend: Arc::new(StreamSocketBackend::new(sender, options)),
+            incoming: receiver,
+            binds: HashMap::new(),
+            connections: HashMap::new(),
+        }
+    }
+
+    fn backend(&self) -> Arc<dyn MultiPeerBackend> {
+        self.backend.clone()
+    }
+
+    fn binds(&mut self) -> &mut HashMap<Endpoint, AcceptStopHandle> {
+        &mut self.binds
+    }
+
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
+    fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.socket_monitor.lock().replace(sender);
+        receiver
+    }
+}
+
+#[async_trait]
+impl SocketRecv for StreamSocket {
+    async fn recv(&mut self) -> ZmqResult<ZmqMessage> {
+        self.incoming.next().await.ok_or(ZmqError::NoMessage)
+    }
+}
+
+#[async_trait]
+impl SocketSend for StreamSocket {
+    async fn send(&mut self, mut message: ZmqMessage) -> ZmqResult<()> {
+        if message.len() < 2 {
+            return Err(ZmqError::ReturnToSender {
+                reason: "STREAM messages must start with a routing id",
+                message,
+            });
+        }
+        let peer_id = message.pop_front().unwrap();
+        if message.len() == 1 && message.get(0).unwrap().is_empty() {
+            // Dropping the stop sender ends the receiving coroutine
+            return match self.backend.peers.remove(peer_id.as_ref()) {
+                Some((_, mut peer)) => {
+                    peer.send_queue.close().await?;
+                    Ok(())
+                }
+                None => Err(ZmqError::Other("Destination client not found by identity")),
+            };
+        }
+        match self.backend.peers.get_mut(peer_id.as_ref()) {
+            Some(mut peer) => {
+                peer.send_queue.send(Message::Message(message)).await?;
+                Ok(())
+            }
+            None => Err(ZmqError::Other("Destination client not found by identity")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tests::test_bind_to_any_port_helper;
+
+    #[async_rt::test]
+    async fn test_bind_to_any_port() -> ZmqResult<()> {
+        let s = StreamSocket::new();
+        test_bind_to_any_port_helper(s).await
+    }
+}
@@ -1,24 +1,152 @@
 use crate::codec::*;
 use crate::endpoint::Endpoint;
-use crate::error::ZmqResult;
+use crate::error::{ZmqError, ZmqResult};
 use crate::message::*;
+use crate::r#pub::{parse_subscription, subscription_message};
 use crate::transport::AcceptStopHandle;
-use crate::util::PeerIdentity;
-use crate::{MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketRecv, SocketType};
+use crate::util::{ConnectHandle, PeerIdentity};
+use crate::{
+    MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketOptions, SocketRecv, SocketType,
+};
 
 use crate::backend::GenericSocketBackend;
-use crate::fair_queue::FairQueue;
+use crate::fair_queue::{FairQueue, QueueInner};
 use async_trait::async_trait;
-use bytes::{BufMut, BytesMut};
 use futures::channel::mpsc;
-use futures::{SinkExt, StreamExt};
+use futures::StreamExt;
+use parking_lot::Mutex;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Backend shared by SUB and XSUB sockets. Remembers the subscriptions made
+/// so far so that they can be replayed to publishers connecting later on.
+pub(crate) struct SubSocketBackend {
+    pub(crate) inner: GenericSocketBackend,
+    // Topic -> number of times it was subscribed to
+    subscriptions: Mutex<HashMap<Vec<u8>, usize>>,
+}
+
+impl SubSocketBackend {
+    pub(crate) fn new(
+        fair_queue_inner: Arc<Mutex<QueueInner<ZmqRecvQueue, PeerIdentity>>>,
+        socket_type: SocketType,
+        options: SocketOptions,
+    ) -> Self {
+        Self {
+            inner: GenericSocketBackend::new(Some(fair_queue_inner), socket_type, options),
+            subscriptions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Records the `\x01topic` / `\x00topic` message if it is a subscription
+    /// and sends it to every connected publisher, in the form its protocol
+    /// version expects. Other messages are sent as is
+    pub(crate) async fn send_subscription(&self, message: ZmqMessage) -> ZmqResult<()> {
+        let (subscribe, topic) = match parse_subscription(&message) {
+            Some(parsed) => parsed,
+            None => return self.inner.send_to_all(message).await,
+        };
+        // Held until every peer got the subscription, so that peers
+        // connecting meanwhile get it exactly once, either here or from the
+        // replay in `peer_connected`
+        let mut subscriptions = self.subscriptions.lock();
+        if subscribe {
+            *subscriptions.entry(topic.to_vec()).or_insert(0) += 1;
+        } else if let Some(count) = subscriptions.get_mut(topic) {
+            *count -= 1;
+            if *count == 0 {
+                subscriptions.remove(topic);
+            }
+        }
+        // Subscriptions bypass the send HWM, so that none is ever lost and
+        // they reach publishers in the order they were made
+        let mut dead_peers = Vec::new();
+        for peer in self.inner.peers.iter() {
+            let message = subscription(peer.zmtp_version, subscribe, topic);
+            if let Err(e) = peer.send_queue.send_control(message) {
+                log::debug!("Failed to send to peer {:?}: {}", peer.key(), e);
+                dead_peers.push(peer.key().clone());
+            }
+        }
+        drop(subscriptions);
+        for peer_id in dead_peers {
+            self.inner.peer_disconnected(&peer_id);
+        }
+        Ok(())
+    }
+
+    /// ZMTP 1.0 publishers send everything, subscribers filter on their side
+    pub(crate) fn is_filtered_out(&self, peer_id: &PeerIdentity, message: &ZmqMessage) -> bool {
+        let legacy = match self.inner.peers.get(peer_id) {
+            Some(peer) => peer.zmtp_version.0 == 1,
+            None => false,
+        };
+        let topic = message.get(0).map(|frame| frame.as_ref()).unwrap_or(&[]);
+        legacy
+            && !self
+                .subscriptions
+                .lock()
+                .keys()
+                .any(|subscription| topic.starts_with(subscription))
+    }
+}
+
+impl SocketBackend for SubSocketBackend {
+    fn socket_type(&self) -> SocketType {
+        self.inner.socket_type()
+    }
+
+    fn shutdown(&self) {
+        self.inner.shutdown()
+    }
+
+    fn monitor(&self) -> &Mutex<Option<mpsc::Sender<SocketEvent>>> {
+        self.inner.monitor()
+    }
+
+    fn socket_options(&self) -> &SocketOptions {
+        self.inner.socket_options()
+    }
+}
+
+impl MultiPeerBackend for SubSocketBackend {
+    fn peer_connected(self: Arc<Self>, peer_id: &PeerIdentity, io: FramedIo) -> ZmqResult<()> {
+        let subscriptions = self.subscriptions.lock();
+        self.inner.add_peer(peer_id, io)?;
+        if let Some(peer) = self.inner.peers.get(peer_id) {
+            for (topic, count) in subscriptions.iter() {
+                for _ in 0..*count {
+                    let message = subscription(peer.zmtp_version, true, topic);
+                    peer.send_queue.send_control(message)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn peer_disconnected(&self, peer_id: &PeerIdentity) {
+        self.inner.peer_disconnected(peer_id)
+    }
+
+    fn connection_lost(&self, peer_id: &PeerIdentity, connection: ConnectionId) {
+        self.inner.connection_lost(peer_id, connection)
+    }
+}
+
+/// Subscriptions are commands since ZMTP 3.1, messages before that
+fn subscription(version: ZmtpVersion, subscribe: bool, topic: &[u8]) -> Message {
+    if version >= (3, 1) {
+        Message::Command(ZmqCommand::subscription(subscribe, topic))
+    } else {
+        Message::Message(subscription_message(subscribe, topic))
+    }
+}
+
 pub struct SubSocket {
-    backend: Arc<GenericSocketBackend>,
-    fair_queue: FairQueue<ZmqFramedRead, PeerIdentity>,
+    backend: Arc<SubSocketBackend>,
+    fair_queue: FairQueue<ZmqRecvQueue, PeerIdentity>,
     binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
 }
 
 impl Drop for SubSocket {
@@ -28,45 +156,32 @@ impl Drop for SubSocket {
 }
 
 impl SubSocket {
+    /// Subscribes to messages starting with `subscription`. Publishers that
+    /// connect later on receive the subscription as well.
     pub async fn subscribe(&mut self, subscription: &str) -> ZmqResult<()> {
-        let mut buf = BytesMut::with_capacity(subscription.len() + 1);
-        buf.put_u8(1);
-        buf.extend_from_slice(subscription.as_bytes());
-        // let message = format!("\0x1{}", subscription);
-        let message: ZmqMessage = ZmqMessage::from(buf.freeze());
-        for mut peer in self.backend.peers.iter_mut() {
-            peer.send_queue
-                .send(Message::Message(message.clone()))
-                .await?;
-        }
-        Ok(())
+        let message = subscription_message(true, subscription.as_bytes());
+        self.backend.send_subscription(message).await
     }
 
     pub async fn unsubscribe(&mut self, subscription: &str) -> ZmqResult<()> {
-        let mut buf = BytesMut::with_capacity(subscription.len() + 1);
-        buf.put_u8(0);
-        buf.extend_from_slice(subscription.as_bytes());
-        let message = ZmqMessage::from(buf.freeze());
-        for mut peer in self.backend.peers.iter_mut() {
-            peer.send_queue
-                .send(Message::Message(message.clone()))
-                .await?;
-        }
-        Ok(())
+        let message = subscription_message(false, subscription.as_bytes());
+        self.backend.send_subscription(message).await
     }
 }
 
 #[async_trait]
 impl Socket for SubSocket {
-    fn new() -> Self {
+    fn with_options(options: SocketOptions) -> Self {
         let fair_queue = FairQueue::new(true);
         Self {
-            backend: Arc::new(GenericSocketBackend::new(
-                Some(fair_queue.inner()),
+            backend: Arc::new(SubSocketBackend::new(
+                fair_queue.inner(),
                 SocketType::SUB,
+                options,
             )),
             fair_queue,
             binds: HashMap::new(),
+            connections: HashMap::new(),
         }
     }
 
@@ -78,9 +193,13 @@ impl Socket for SubSocket {
         &mut self.binds
     }
 
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
     fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
         let (sender, receiver) = mpsc::channel(1024);
-        self.backend.socket_monitor.lock().replace(sender);
+        self.backend.inner.socket_monitor.lock().replace(sender);
         receiver
     }
 }
@@ -90,11 +209,17 @@ impl SocketRecv for SubSocket {
     async fn recv(&mut self) -> ZmqResult<ZmqMessage> {
         loop {
             match self.fair_queue.next().await {
-                Some((_peer_id, Ok(Message::Message(message)))) => {
-                    return Ok(message);
+                Some((peer_id, Ok(Message::Message(message)))) => {
+                    if !self.backend.is_filtered_out(&peer_id, &message) {
+                        return Ok(message);
+                    }
+                }
+                Some((_peer_id, Ok(_))) => {}
+                Some((peer_id, Err(e))) => {
+                    log::debug!("SUB peer {:?} failed: {}", peer_id, e);
+                    self.backend.peer_disconnected(&peer_id);
                 }
-                Some((_peer_id, _)) => todo!(),
-                None => todo!(),
+                None => return Err(ZmqError::NoMessage),
             }
         }
     }
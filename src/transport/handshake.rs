@@ -0,0 +1,114 @@
+//! Shared by the transports layered over TCP, such as TLS and WebSocket,
+//! whose own handshake runs before connections are handed over to ZMTP
+
+#[cfg(feature = "tokio-runtime")]
+use tokio::net::{TcpListener, TcpStream};
+
+#[cfg(feature = "async-std-runtime")]
+use async_std::net::{TcpListener, TcpStream};
+
+use super::AcceptStopHandle;
+use crate::async_rt;
+use crate::codec::FramedIo;
+use crate::endpoint::{Endpoint, Host, Port};
+use crate::task_handle::TaskHandle;
+use crate::{ZmqError, ZmqResult};
+
+use futures::channel::mpsc;
+use futures::{select, FutureExt, StreamExt};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// TCP stream implementing the `futures` io traits
+#[cfg(feature = "tokio-runtime")]
+pub(super) type TcpIo = tokio_util::compat::Compat<TcpStream>;
+#[cfg(feature = "async-std-runtime")]
+pub(super) type TcpIo = TcpStream;
+
+#[cfg(feature = "tokio-runtime")]
+fn tcp_io(stream: TcpStream) -> TcpIo {
+    tokio_util::compat::TokioAsyncReadCompatExt::compat(stream)
+}
+
+#[cfg(feature = "async-std-runtime")]
+fn tcp_io(stream: TcpStream) -> TcpIo {
+    stream
+}
+
+pub(super) async fn connect(host: &Host, port: Port) -> ZmqResult<(TcpIo, SocketAddr)> {
+    let raw_socket = TcpStream::connect((host.to_string().as_str(), port)).await?;
+    let peer_addr = raw_socket.peer_addr()?;
+    Ok((tcp_io(raw_socket), peer_addr))
+}
+
+/// Listens like the TCP transport does, running `handshake` on every
+/// connection accepted before calling `cback`. Handshakes run in tasks of
+/// their own, so that slow peers don't hold up the others, and fail once
+/// `timeout` elapsed.
+///
+/// Returns the host and port bound to along with the stop handle
+pub(super) async fn begin_accept<T, H, F>(
+    host: Host,
+    port: Port,
+    timeout: Option<Duration>,
+    handshake: H,
+    cback: impl Fn(ZmqResult<(FramedIo, Endpoint)>) -> T + Send + 'static,
+) -> ZmqResult<(Host, Port, AcceptStopHandle)>
+where
+    T: Future<Output = ()> + Send + 'static,
+    H: Fn(TcpIo, SocketAddr) -> F + Send + 'static,
+    F: Future<Output = ZmqResult<(FramedIo, Endpoint)>> + Send + 'static,
+{
+    let listener = TcpListener::bind((host.to_string().as_str(), port)).await?;
+    let resolved_addr = listener.local_addr()?;
+    let (stop_channel, stop_callback) = futures::channel::oneshot::channel::<()>();
+    let task_handle = async_rt::task::spawn(async move {
+        let mut stop_callback = stop_callback.fuse();
+        let (handshakes, mut handshaken) = mpsc::unbounded();
+        loop {
+            select! {
+                incoming = listener.accept().fuse() => {
+                    match incoming {
+                        Ok((raw_socket, remote_addr)) => {
+                            let handshake = handshake(tcp_io(raw_socket), remote_addr);
+                            let handshakes = handshakes.clone();
+                            async_rt::task::spawn(async move {
+                                let accepted = match timeout {
+                                    Some(timeout) => select! {
+                                        accepted = handshake.fuse() => accepted,
+                                        _ = async_rt::task::sleep(timeout).fuse() => {
+                                            Err(ZmqError::Other("Handshake timed out"))
+                                        },
+                                    },
+                                    None => handshake.await,
+                                };
+                                let _ = handshakes.unbounded_send(accepted);
+                            });
+                        }
+                        Err(err) => {
+                            async_rt::task::spawn(cback(Err(err.into())));
+                        }
+                    }
+                },
+                accepted = handshaken.select_next_some() => {
+                    async_rt::task::spawn(cback(accepted));
+                },
+                _ = stop_callback => {
+                    break
+                }
+            }
+        }
+        Ok(())
+    });
+    debug_assert_ne!(resolved_addr.port(), 0);
+    let host = match host {
+        Host::Domain(name) => Host::Domain(name),
+        _ => resolved_addr.ip().into(),
+    };
+    Ok((
+        host,
+        resolved_addr.port(),
+        AcceptStopHandle(TaskHandle::new(stop_channel, task_handle)),
+    ))
+}
@@ -0,0 +1,80 @@
+use super::AcceptStopHandle;
+use crate::async_rt;
+use crate::codec::FramedIo;
+use crate::endpoint::Endpoint;
+use crate::task_handle::TaskHandle;
+use crate::ZmqResult;
+
+use futures::channel::mpsc;
+use futures::{select, FutureExt, StreamExt};
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::io;
+
+lazy_static! {
+    /// Names bound in this process, along with the queue of connections
+    /// waiting to be accepted by their listener
+    static ref LISTENERS: Mutex<HashMap<String, mpsc::UnboundedSender<FramedIo>>> =
+        Mutex::new(HashMap::new());
+}
+
+pub(crate) async fn connect(name: &str) -> ZmqResult<(FramedIo, Endpoint)> {
+    let (connected, accepted) = FramedIo::inproc_pair();
+    match LISTENERS.lock().get(name) {
+        Some(listener) if listener.unbounded_send(accepted).is_ok() => {
+            Ok((connected, Endpoint::Inproc(name.to_owned())))
+        }
+        // Reported like a TCP port nobody listens on, so that connecting
+        // keeps trying until the name gets bound
+        _ => Err(io::Error::from(io::ErrorKind::ConnectionRefused).into()),
+    }
+}
+
+pub(crate) async fn begin_accept<T>(
+    name: String,
+    cback: impl Fn(ZmqResult<(FramedIo, Endpoint)>) -> T + Send + 'static,
+) -> ZmqResult<(Endpoint, AcceptStopHandle)>
+where
+    T: std::future::Future<Output = ()> + Send + 'static,
+{
+    let (listener, mut incoming) = mpsc::unbounded();
+    {
+        let mut listeners = LISTENERS.lock();
+        if matches!(listeners.get(&name), Some(l) if !l.is_closed()) {
+            return Err(io::Error::from(io::ErrorKind::AddrInUse).into());
+        }
+        listeners.insert(name.clone(), listener.clone());
+    }
+
+    let endpoint = Endpoint::Inproc(name.clone());
+    let (stop_channel, stop_callback) = futures::channel::oneshot::channel::<()>();
+    let task_handle = async_rt::task::spawn(async move {
+        let mut stop_callback = stop_callback.fuse();
+        loop {
+            select! {
+                framed = incoming.next() => match framed {
+                    Some(framed) => {
+                        let accepted = Ok((framed, Endpoint::Inproc(name.clone())));
+                        async_rt::task::spawn(cback(accepted));
+                    }
+                    None => break,
+                },
+                _ = stop_callback => {
+                    log::debug!("Accept task received stop signal. inproc://{}", name);
+                    break
+                }
+            }
+        }
+        // The name may have been bound again in the meantime
+        let mut listeners = LISTENERS.lock();
+        if matches!(listeners.get(&name), Some(l) if l.same_receiver(&listener)) {
+            listeners.remove(&name);
+        }
+        Ok(())
+    });
+    Ok((
+        endpoint,
+        AcceptStopHandle(TaskHandle::new(stop_channel, task_handle)),
+    ))
+}
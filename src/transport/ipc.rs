@@ -10,10 +10,11 @@ use crate::async_rt;
 use crate::codec::FramedIo;
 use crate::endpoint::Endpoint;
 use crate::task_handle::TaskHandle;
-use crate::ZmqResult;
+use crate::{SocketOptions, ZmqResult};
 
 use futures::{select, FutureExt};
 use std::path::Path;
+use uuid::Uuid;
 
 pub(crate) async fn connect(path: &Path) -> ZmqResult<(FramedIo, Endpoint)> {
     let raw_socket = UnixStream::connect(path).await?;
@@ -25,20 +26,25 @@ pub(crate) async fn connect(path: &Path) -> ZmqResult<(FramedIo, Endpoint)> {
 
 pub(crate) async fn begin_accept<T>(
     path: &Path,
+    options: &SocketOptions,
     cback: impl Fn(ZmqResult<(FramedIo, Endpoint)>) -> T + Send + 'static,
 ) -> ZmqResult<(Endpoint, AcceptStopHandle)>
 where
     T: std::future::Future<Output = ()> + Send + 'static,
 {
+    // Wildcards bind to a unique path, as libzmq does
     let wildcard: &Path = "*".as_ref();
-    if path == wildcard {
-        todo!("Need to implement support for wildcard paths!");
-    }
+    let path = if path == wildcard {
+        let name = format!("zmq-{}.sock", Uuid::new_v4().to_simple());
+        options.ipc_wildcard_dir().join(name)
+    } else {
+        path.to_owned()
+    };
 
     #[cfg(feature = "tokio-runtime")]
-    let listener = UnixListener::bind(path)?;
+    let listener = UnixListener::bind(&path)?;
     #[cfg(feature = "async-std-runtime")]
-    let listener = UnixListener::bind(path).await?;
+    let listener = UnixListener::bind(&path).await?;
 
     let resolved_addr = listener.local_addr()?;
     let resolved_addr = resolved_addr.as_pathname().map(|a| a.to_owned());
@@ -1,12 +1,22 @@
+#[cfg(any(feature = "ws-transport", feature = "tls-transport"))]
+mod handshake;
+#[cfg(feature = "inproc-transport")]
+mod inproc;
 #[cfg(feature = "ipc-transport")]
 mod ipc;
 #[cfg(feature = "tcp-transport")]
 mod tcp;
+#[cfg(feature = "tls-transport")]
+pub(crate) mod tls;
+#[cfg(feature = "udp-transport")]
+mod udp;
+#[cfg(feature = "ws-transport")]
+mod ws;
 
 use crate::codec::FramedIo;
 use crate::endpoint::Endpoint;
 use crate::task_handle::TaskHandle;
-use crate::ZmqResult;
+use crate::{SocketOptions, ZmqResult};
 
 macro_rules! do_if_enabled {
     ($feature:literal, $body:expr) => {{
@@ -16,7 +26,7 @@ macro_rules! do_if_enabled {
         }
 
         #[cfg(not(feature = $feature))]
-        panic!(format!("feature \"{}\" is not enabled", $feature))
+        panic!("feature \"{}\" is not enabled", $feature)
     }};
 }
 
@@ -24,7 +34,10 @@ macro_rules! do_if_enabled {
 ///
 /// # Panics
 /// Panics if the requested endpoint uses a transport type that isn't enabled
-pub(crate) async fn connect(endpoint: &Endpoint) -> ZmqResult<(FramedIo, Endpoint)> {
+pub(crate) async fn connect(
+    endpoint: &Endpoint,
+    _options: &SocketOptions,
+) -> ZmqResult<(FramedIo, Endpoint)> {
     match endpoint {
         Endpoint::Tcp(_host, _port) => {
             do_if_enabled!("tcp-transport", tcp::connect(_host, *_port).await)
@@ -39,6 +52,18 @@ pub(crate) async fn connect(endpoint: &Endpoint) -> ZmqResult<(FramedIo, Endpoin
                 ))
             }
         ),
+        Endpoint::Inproc(_name) => {
+            do_if_enabled!("inproc-transport", inproc::connect(_name).await)
+        }
+        Endpoint::TlsTcp(_host, _port) => {
+            do_if_enabled!("tls-transport", tls::connect(_host, *_port, _options).await)
+        }
+        Endpoint::Ws(..) | Endpoint::Wss(..) => {
+            do_if_enabled!("ws-transport", ws::connect(endpoint, _options).await)
+        }
+        Endpoint::Udp(_host, _port) => {
+            do_if_enabled!("udp-transport", udp::connect(_host, *_port).await)
+        }
     }
 }
 
@@ -57,6 +82,7 @@ pub struct AcceptStopHandle(pub(crate) TaskHandle<()>);
 /// Panics if the requested endpoint uses a transport type that isn't enabled
 pub(crate) async fn begin_accept<T>(
     endpoint: Endpoint,
+    _options: &SocketOptions,
     cback: impl Fn(ZmqResult<(FramedIo, Endpoint)>) -> T + Send + 'static,
 ) -> ZmqResult<(Endpoint, AcceptStopHandle)>
 where
@@ -71,13 +97,29 @@ where
         Endpoint::Ipc(_path) => do_if_enabled!(
             "ipc-transport",
             if let Some(path) = _path {
-                ipc::begin_accept(&path, _cback).await
+                ipc::begin_accept(&path, _options, _cback).await
             } else {
                 Err(crate::error::ZmqError::Socket(
                     "Cannot begin accepting peers at an unnamed ipc socket",
                 ))
             }
         ),
+        Endpoint::Inproc(_name) => do_if_enabled!(
+            "inproc-transport",
+            inproc::begin_accept(_name, _cback).await
+        ),
+        Endpoint::TlsTcp(_host, _port) => do_if_enabled!(
+            "tls-transport",
+            tls::begin_accept(_host, _port, _options, _cback).await
+        ),
+        Endpoint::Ws(..) | Endpoint::Wss(..) => do_if_enabled!(
+            "ws-transport",
+            ws::begin_accept(endpoint, _options, _cback).await
+        ),
+        Endpoint::Udp(_host, _port) => do_if_enabled!(
+            "udp-transport",
+            udp::begin_accept(_host, _port, _cback).await
+        ),
     }
 }
 
@@ -0,0 +1,199 @@
+//! ZMTP over TLS, for `tls+tcp://` endpoints, also used by `wss://` ones.
+//! Configured through
+//! [`SocketOptionsBuilder::tls_certificate`](crate::SocketOptionsBuilder::tls_certificate)
+//! and [`SocketOptionsBuilder::tls_ca`](crate::SocketOptionsBuilder::tls_ca)
+
+use super::handshake::{self, TcpIo};
+use super::AcceptStopHandle;
+use crate::codec::FramedIo;
+use crate::endpoint::{Endpoint, Host, Port};
+use crate::error::{ZmqError, ZmqResult};
+use crate::metadata::PeerMetadata;
+use crate::SocketOptions;
+
+use futures::{AsyncRead, AsyncWrite};
+use futures_rustls::rustls::crypto::{ring, CryptoProvider};
+use futures_rustls::rustls::server::WebPkiClientVerifier;
+use futures_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use futures_rustls::{TlsAcceptor, TlsConnector, TlsStream};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn certificates(pem: &[u8]) -> ZmqResult<Vec<CertificateDer<'static>>> {
+    let certificates = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| ZmqError::Socket("Invalid PEM certificate"))?;
+    if certificates.is_empty() {
+        return Err(ZmqError::Socket("No PEM certificate found"));
+    }
+    Ok(certificates)
+}
+
+fn private_key(pem: &[u8]) -> ZmqResult<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_slice(pem).map_err(|_| ZmqError::Socket("Invalid PEM private key"))
+}
+
+fn root_store(ca: &[u8]) -> ZmqResult<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for certificate in certificates(ca)? {
+        roots
+            .add(certificate)
+            .map_err(|_| ZmqError::Socket("Invalid CA certificate"))?;
+    }
+    Ok(roots)
+}
+
+/// Configuration used to connect to TLS servers trusting `ca`, presenting
+/// `identity` if they ask for a client certificate
+pub(crate) fn client_config(
+    ca: &[u8],
+    identity: Option<(&[u8], &[u8])>,
+) -> ZmqResult<Arc<ClientConfig>> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|_| ZmqError::Socket("Unsupported TLS protocol versions"))?
+        .with_root_certificates(root_store(ca)?);
+    let config = match identity {
+        Some((chain, key)) => builder
+            .with_client_auth_cert(certificates(chain)?, private_key(key)?)
+            .map_err(|_| ZmqError::Socket("TLS certificate doesn't match its private key"))?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+/// Configuration used to accept TLS clients, which need a certificate signed
+/// by `ca` if given
+pub(crate) fn server_config(
+    chain: &[u8],
+    key: &[u8],
+    ca: Option<&[u8]>,
+) -> ZmqResult<Arc<ServerConfig>> {
+    let builder = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(|_| ZmqError::Socket("Unsupported TLS protocol versions"))?;
+    let builder = match ca {
+        Some(ca) => {
+            let verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(root_store(ca)?), provider())
+                    .build()
+                    .map_err(|_| ZmqError::Socket("Invalid CA certificate"))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let config = builder
+        .with_single_cert(certificates(chain)?, private_key(key)?)
+        .map_err(|_| ZmqError::Socket("TLS certificate doesn't match its private key"))?;
+    Ok(Arc::new(config))
+}
+
+pub(crate) async fn connect(
+    host: &Host,
+    port: Port,
+    options: &SocketOptions,
+) -> ZmqResult<(FramedIo, Endpoint)> {
+    let (raw_socket, peer_addr) = handshake::connect(host, port).await?;
+    let stream = client(raw_socket, host, options).await?;
+    Ok((
+        framed(stream),
+        Endpoint::TlsTcp(peer_addr.ip().into(), peer_addr.port()),
+    ))
+}
+
+pub(crate) async fn begin_accept<T>(
+    host: Host,
+    port: Port,
+    options: &SocketOptions,
+    cback: impl Fn(ZmqResult<(FramedIo, Endpoint)>) -> T + Send + 'static,
+) -> ZmqResult<(Endpoint, AcceptStopHandle)>
+where
+    T: std::future::Future<Output = ()> + Send + 'static,
+{
+    let acceptor = acceptor(options)?;
+    let secure = move |raw_socket: TcpIo, remote_addr: SocketAddr| {
+        let acceptor = acceptor.clone();
+        async move {
+            let stream = server(&acceptor, raw_socket).await?;
+            let peer = Endpoint::TlsTcp(remote_addr.ip().into(), remote_addr.port());
+            Ok((framed(stream), peer))
+        }
+    };
+    let (host, port, stop_handle) =
+        handshake::begin_accept(host, port, options.handshake_timeout, secure, cback).await?;
+    Ok((Endpoint::TlsTcp(host, port), stop_handle))
+}
+
+fn framed<S>(stream: TlsStream<S>) -> FramedIo
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+{
+    let metadata = peer_metadata(&stream);
+    let (read, write) = futures::AsyncReadExt::split(stream);
+    let mut framed = FramedIo::new(Box::new(read), Box::new(write));
+    framed.transport_metadata = metadata;
+    framed
+}
+
+/// Metadata holding the subject of the certificate the peer presented,
+/// which rustls verified already. Clients only present one to servers
+/// requiring it
+pub(crate) fn peer_metadata<S>(stream: &TlsStream<S>) -> PeerMetadata {
+    let mut metadata = PeerMetadata::default();
+    let certificate = stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .and_then(|chain| chain.first());
+    if let Some(certificate) = certificate {
+        if let Ok((_, certificate)) = x509_parser::parse_x509_certificate(certificate) {
+            metadata.insert(
+                "Peer-Certificate-Subject",
+                certificate.subject().to_string(),
+            );
+        }
+    }
+    metadata
+}
+
+/// Starts TLS over `stream`, checking that the server certificate is valid
+/// for `host`
+pub(crate) async fn client<S>(
+    stream: S,
+    host: &Host,
+    options: &SocketOptions,
+) -> ZmqResult<TlsStream<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let config = options.tls_client.clone().ok_or(ZmqError::Socket(
+        "Connecting over TLS needs tls_ca to be set",
+    ))?;
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|_| ZmqError::Socket("Invalid TLS server name"))?;
+    Ok(TlsConnector::from(config)
+        .connect(name, stream)
+        .await?
+        .into())
+}
+
+pub(crate) fn acceptor(options: &SocketOptions) -> ZmqResult<TlsAcceptor> {
+    let config = options.tls_server.clone().ok_or(ZmqError::Socket(
+        "Accepting TLS connections needs tls_certificate to be set",
+    ))?;
+    Ok(TlsAcceptor::from(config))
+}
+
+pub(crate) async fn server<S>(acceptor: &TlsAcceptor, stream: S) -> ZmqResult<TlsStream<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    Ok(acceptor.accept(stream).await?.into())
+}
@@ -0,0 +1,194 @@
+//! RADIO and DISH over UDP, in the datagram format of libzmq: each datagram
+//! carries a single message, made of the length of its group in one octet,
+//! the group and the body. There is no handshake, so a socket sees a single
+//! peer per endpoint, and DISH sockets can't tell RADIO ones what they joined
+
+#[cfg(feature = "tokio-runtime")]
+use tokio::net::UdpSocket;
+
+#[cfg(feature = "async-std-runtime")]
+use async_std::net::UdpSocket;
+
+use super::AcceptStopHandle;
+use crate::async_rt;
+use crate::codec::{FramedIo, Message};
+use crate::endpoint::{Endpoint, Host, Port};
+use crate::task_handle::TaskHandle;
+use crate::{ZmqMessage, ZmqResult};
+
+use bytes::Bytes;
+use futures::channel::{mpsc, oneshot};
+use futures::{select, FutureExt, StreamExt};
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// Largest payload of a UDP datagram over IPv4
+const MAX_DATAGRAM: usize = 65_507;
+
+/// Messages waiting for the socket to pick them up. Datagrams arriving once
+/// it is full are dropped
+const BUFFER: usize = 16;
+
+pub(crate) async fn connect(host: &Host, port: Port) -> ZmqResult<(FramedIo, Endpoint)> {
+    let local: SocketAddr = match host {
+        Host::Ipv6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        _ => (Ipv4Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect((host.to_string().as_str(), port)).await?;
+
+    // Nothing is ever received, the incoming channel is only closed once
+    // the socket is done sending
+    let (outgoing, to_send) = mpsc::channel(BUFFER);
+    let (closed, incoming) = mpsc::channel(0);
+    async_rt::task::spawn(async move {
+        let _closed = closed;
+        send_loop(socket, to_send).await
+    });
+    let mut framed = FramedIo::from_channels(incoming, outgoing);
+    framed.connectionless = true;
+    Ok((framed, Endpoint::Udp(host.clone(), port)))
+}
+
+pub(crate) async fn begin_accept<T>(
+    host: Host,
+    port: Port,
+    cback: impl Fn(ZmqResult<(FramedIo, Endpoint)>) -> T + Send + 'static,
+) -> ZmqResult<(Endpoint, AcceptStopHandle)>
+where
+    T: std::future::Future<Output = ()> + Send + 'static,
+{
+    // Multicast groups are joined on the interface picked by the system
+    let socket = match &host {
+        Host::Ipv4(group) if group.is_multicast() => {
+            let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)).await?;
+            socket.join_multicast_v4(*group, Ipv4Addr::UNSPECIFIED)?;
+            socket
+        }
+        Host::Ipv6(group) if group.is_multicast() => {
+            let socket = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, port)).await?;
+            socket.join_multicast_v6(group, 0)?;
+            socket
+        }
+        _ => UdpSocket::bind((host.to_string().as_str(), port)).await?,
+    };
+    let resolved_addr = socket.local_addr()?;
+    debug_assert_ne!(resolved_addr.port(), 0);
+    let endpoint = Endpoint::Udp(host, resolved_addr.port());
+
+    let (mut received, incoming) = mpsc::channel(BUFFER);
+    let (outgoing, mut discarded) = mpsc::channel(0);
+    let mut framed = FramedIo::from_channels(incoming, outgoing);
+    framed.connectionless = true;
+    async_rt::task::spawn(cback(Ok((framed, endpoint.clone()))));
+
+    let (stop_channel, stop_callback) = oneshot::channel::<()>();
+    let task_handle = async_rt::task::spawn(async move {
+        let mut stop_callback = stop_callback.fuse();
+        let mut buf = vec![0; MAX_DATAGRAM];
+        loop {
+            select! {
+                datagram = socket.recv(&mut buf).fuse() => {
+                    let len = match datagram {
+                        Ok(len) => len,
+                        Err(e) => {
+                            log::debug!("Failed to receive UDP datagram: {}", e);
+                            continue;
+                        }
+                    };
+                    let message = match message(&buf[..len]) {
+                        Some(message) => message,
+                        None => {
+                            log::debug!("Dropping malformed UDP datagram");
+                            continue;
+                        }
+                    };
+                    if let Err(e) = received.try_send(Message::Message(message)) {
+                        if e.is_disconnected() {
+                            break;
+                        }
+                        log::trace!("Dropping UDP datagram, the socket is not keeping up");
+                    }
+                },
+                // JOIN and LEAVE commands have nowhere to go
+                _ = discarded.select_next_some() => {},
+                _ = stop_callback => break,
+            }
+        }
+        Ok(())
+    });
+    Ok((
+        endpoint,
+        AcceptStopHandle(TaskHandle::new(stop_channel, task_handle)),
+    ))
+}
+
+async fn send_loop(socket: UdpSocket, mut to_send: mpsc::Receiver<Message>) {
+    while let Some(message) = to_send.next().await {
+        let message = match message {
+            Message::Message(message) => message,
+            _ => continue,
+        };
+        match datagram(&message) {
+            // Datagrams are lost all the same when no one listens, errors
+            // such as ICMP port unreachable ones are of no consequence
+            Some(datagram) => {
+                if let Err(e) = socket.send(&datagram).await {
+                    log::debug!("Failed to send UDP datagram: {}", e);
+                }
+            }
+            None => log::debug!("Dropping message that does not fit in a UDP datagram"),
+        }
+    }
+}
+
+/// Encodes a `[group, body]` message as a datagram
+fn datagram(message: &ZmqMessage) -> Option<Vec<u8>> {
+    let (group, body) = match (message.len(), message.get(0), message.get(1)) {
+        (2, Some(group), Some(body)) => (group, body),
+        _ => return None,
+    };
+    let group_len = u8::try_from(group.len()).ok()?;
+    if 1 + group.len() + body.len() > MAX_DATAGRAM {
+        return None;
+    }
+    let mut datagram = Vec::with_capacity(1 + group.len() + body.len());
+    datagram.push(group_len);
+    datagram.extend_from_slice(group);
+    datagram.extend_from_slice(body);
+    Some(datagram)
+}
+
+/// Decodes a datagram into a `[group, body]` message
+fn message(datagram: &[u8]) -> Option<ZmqMessage> {
+    let (group_len, rest) = datagram.split_first()?;
+    let group_len = usize::from(*group_len);
+    if rest.len() < group_len {
+        return None;
+    }
+    let (group, body) = rest.split_at(group_len);
+    let mut message = ZmqMessage::from(Bytes::copy_from_slice(group));
+    message.push_back(Bytes::copy_from_slice(body));
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_datagram_conversion() {
+        let mut message = ZmqMessage::from("weather");
+        message.push_back("sunny".into());
+        let encoded = datagram(&message).unwrap();
+        assert_eq!(encoded, b"\x07weathersunny");
+        let decoded = super::message(&encoded).unwrap();
+        assert_eq!(decoded.into_vec(), message.into_vec());
+
+        assert!(super::message(b"\x08weather").is_none());
+        assert!(super::message(b"").is_none());
+        let long_group = ZmqMessage::try_from(vec![Bytes::from(vec![b'a'; 256]), Bytes::new()]);
+        assert!(datagram(&long_group.unwrap()).is_none());
+        assert!(datagram(&ZmqMessage::from("no group")).is_none());
+    }
+}
@@ -0,0 +1,441 @@
+//! ZMTP over WebSocket, following ZWS 2.0, see
+//! https://rfc.zeromq.org/spec/45/
+//!
+//! There is no greeting, the security mechanism is agreed on through the
+//! WebSocket subprotocol instead. Each ZMTP frame travels in a binary
+//! message of its own, prefixed with a flags byte. The codec keeps speaking
+//! plain ZMTP, [`ZwsStream`] converts the frames on the fly.
+
+use super::handshake::{self, TcpIo};
+#[cfg(feature = "wss-transport")]
+use super::tls;
+use super::AcceptStopHandle;
+use crate::codec::FramedIo;
+use crate::endpoint::{Endpoint, Host, Port};
+use crate::error::{ZmqError, ZmqResult};
+use crate::SocketOptions;
+
+use async_tungstenite::tungstenite::client::IntoClientRequest;
+use async_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use async_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+use async_tungstenite::tungstenite::{Error as WsError, Message as WsMessage};
+use async_tungstenite::WebSocketStream;
+use bytes::{Buf, BufMut, BytesMut};
+use futures::task::{Context, Poll};
+use futures::{ready, AsyncRead, AsyncWrite, Sink, StreamExt};
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+const PROTOCOL_HEADER: &str = "Sec-WebSocket-Protocol";
+
+// ZMTP frame flags
+const ZMTP_MORE: u8 = 0x01;
+const ZMTP_LONG: u8 = 0x02;
+const ZMTP_COMMAND: u8 = 0x04;
+
+// ZWS frame flags
+const ZWS_MORE: u8 = 0x01;
+const ZWS_COMMAND: u8 = 0x02;
+
+/// Subprotocol naming the security mechanism of the connection
+fn subprotocol(options: &SocketOptions) -> String {
+    format!("ZWS2.0/{}", options.security.mechanism())
+}
+
+fn ws_error(err: WsError) -> io::Error {
+    match err {
+        WsError::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+pub(crate) async fn connect(
+    endpoint: &Endpoint,
+    options: &SocketOptions,
+) -> ZmqResult<(FramedIo, Endpoint)> {
+    let (host, port, path, secure) = match endpoint {
+        Endpoint::Ws(host, port, path) => (host, *port, path, false),
+        Endpoint::Wss(host, port, path) => (host, *port, path, true),
+        _ => unreachable!("Not a WebSocket endpoint"),
+    };
+    let (raw_socket, peer_addr) = handshake::connect(host, port).await?;
+    let peer = ws_endpoint(secure, peer_addr.ip().into(), peer_addr.port(), path);
+    if secure {
+        #[cfg(feature = "wss-transport")]
+        {
+            let stream = tls::client(raw_socket, host, options).await?;
+            let metadata = tls::peer_metadata(&stream);
+            let mut framed = client_handshake(stream, endpoint, options).await?;
+            framed.transport_metadata = metadata;
+            return Ok((framed, peer));
+        }
+        #[cfg(not(feature = "wss-transport"))]
+        return Err(ZmqError::Socket("wss:// needs the wss-transport feature"));
+    }
+    Ok((client_handshake(raw_socket, endpoint, options).await?, peer))
+}
+
+fn ws_endpoint(secure: bool, host: Host, port: Port, path: &str) -> Endpoint {
+    if secure {
+        Endpoint::Wss(host, port, path.to_string())
+    } else {
+        Endpoint::Ws(host, port, path.to_string())
+    }
+}
+
+async fn client_handshake<S>(
+    stream: S,
+    endpoint: &Endpoint,
+    options: &SocketOptions,
+) -> ZmqResult<FramedIo>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let protocol = subprotocol(options);
+    let mut request = endpoint
+        .to_string()
+        .into_client_request()
+        .map_err(ws_error)?;
+    request.headers_mut().insert(
+        PROTOCOL_HEADER,
+        HeaderValue::from_str(&protocol).expect("Invalid subprotocol"),
+    );
+    let (ws, response) = async_tungstenite::client_async(request, stream)
+        .await
+        .map_err(ws_error)?;
+    match response.headers().get(PROTOCOL_HEADER) {
+        Some(accepted) if accepted == protocol.as_str() => Ok(ZwsStream::framed(ws)),
+        _ => Err(ZmqError::Other("Peer uses a different security mechanism")),
+    }
+}
+
+async fn server_handshake<S>(stream: S, path: String, protocol: String) -> ZmqResult<FramedIo>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // The error type is tungstenite's
+    #[allow(clippy::result_large_err)]
+    let callback = move |request: &Request, mut response: Response| {
+        fn refuse(status: StatusCode) -> ErrorResponse {
+            let mut response = ErrorResponse::new(status.canonical_reason().map(String::from));
+            *response.status_mut() = status;
+            response
+        }
+        if request.uri().path() != path {
+            return Err(refuse(StatusCode::NOT_FOUND));
+        }
+        let offered = request
+            .headers()
+            .get_all(PROTOCOL_HEADER)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|offered| offered.trim() == protocol);
+        if !offered {
+            return Err(refuse(StatusCode::BAD_REQUEST));
+        }
+        response.headers_mut().insert(
+            PROTOCOL_HEADER,
+            HeaderValue::from_str(&protocol).expect("Invalid subprotocol"),
+        );
+        Ok(response)
+    };
+    let ws = async_tungstenite::accept_hdr_async(stream, callback)
+        .await
+        .map_err(ws_error)?;
+    Ok(ZwsStream::framed(ws))
+}
+
+pub(crate) async fn begin_accept<T>(
+    endpoint: Endpoint,
+    options: &SocketOptions,
+    cback: impl Fn(ZmqResult<(FramedIo, Endpoint)>) -> T + Send + 'static,
+) -> ZmqResult<(Endpoint, AcceptStopHandle)>
+where
+    T: std::future::Future<Output = ()> + Send + 'static,
+{
+    let (host, port, path, secure) = match endpoint {
+        Endpoint::Ws(host, port, path) => (host, port, path, false),
+        Endpoint::Wss(host, port, path) => (host, port, path, true),
+        _ => unreachable!("Not a WebSocket endpoint"),
+    };
+    #[cfg(feature = "wss-transport")]
+    let acceptor = if secure {
+        Some(tls::acceptor(options)?)
+    } else {
+        None
+    };
+    #[cfg(not(feature = "wss-transport"))]
+    if secure {
+        return Err(ZmqError::Socket("wss:// needs the wss-transport feature"));
+    }
+    let protocol = subprotocol(options);
+    let accept_path = path.clone();
+    let upgrade = move |raw_socket: TcpIo, remote_addr: SocketAddr| {
+        let peer = ws_endpoint(
+            secure,
+            remote_addr.ip().into(),
+            remote_addr.port(),
+            &accept_path,
+        );
+        let path = accept_path.clone();
+        let protocol = protocol.clone();
+        #[cfg(feature = "wss-transport")]
+        let acceptor = acceptor.clone();
+        async move {
+            #[cfg(feature = "wss-transport")]
+            if let Some(acceptor) = acceptor {
+                let stream = tls::server(&acceptor, raw_socket).await?;
+                let metadata = tls::peer_metadata(&stream);
+                let mut framed = server_handshake(stream, path, protocol).await?;
+                framed.transport_metadata = metadata;
+                return Ok((framed, peer));
+            }
+            Ok((server_handshake(raw_socket, path, protocol).await?, peer))
+        }
+    };
+    let (host, port, stop_handle) =
+        handshake::begin_accept(host, port, options.handshake_timeout, upgrade, cback).await?;
+    Ok((ws_endpoint(secure, host, port, &path), stop_handle))
+}
+
+/// Byte stream of ZMTP frames over a WebSocket connection. Frames read from
+/// it are rebuilt from the ZWS messages received, frames written to it are
+/// sent as ZWS messages once complete
+struct ZwsStream<S> {
+    ws: WebSocketStream<S>,
+    /// ZMTP frames not read yet
+    read_buf: BytesMut,
+    /// Start of a ZMTP frame being written
+    write_buf: BytesMut,
+    /// Messages waiting for the WebSocket to be ready
+    outgoing: VecDeque<WsMessage>,
+}
+
+impl<S> ZwsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    fn framed(ws: WebSocketStream<S>) -> FramedIo {
+        let stream = ZwsStream {
+            ws,
+            read_buf: BytesMut::new(),
+            write_buf: BytesMut::new(),
+            outgoing: VecDeque::new(),
+        };
+        let (read, write) = futures::AsyncReadExt::split(stream);
+        FramedIo::new(Box::new(read), Box::new(write))
+    }
+
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.outgoing.is_empty() {
+            ready!(Pin::new(&mut self.ws).poll_ready(cx)).map_err(ws_error)?;
+            let message = self.outgoing.pop_front().unwrap();
+            Pin::new(&mut self.ws)
+                .start_send(message)
+                .map_err(ws_error)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Appends the ZMTP frame carried by a ZWS message to `dst`
+fn zmtp_frame(message: &[u8], dst: &mut BytesMut) -> io::Result<()> {
+    let (flags, body) = message
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Empty ZWS frame"))?;
+    let mut zmtp_flags = flags & ZWS_MORE;
+    if flags & ZWS_COMMAND != 0 {
+        zmtp_flags |= ZMTP_COMMAND;
+    }
+    dst.reserve(9 + body.len());
+    if body.len() > 255 {
+        dst.put_u8(zmtp_flags | ZMTP_LONG);
+        dst.put_u64(body.len() as u64);
+    } else {
+        dst.put_u8(zmtp_flags);
+        dst.put_u8(body.len() as u8);
+    }
+    dst.extend_from_slice(body);
+    Ok(())
+}
+
+/// Takes the first ZMTP frame out of `src` as a ZWS message, if complete
+fn zws_frame(src: &mut BytesMut) -> Option<Vec<u8>> {
+    let flags = *src.first()?;
+    let (header_len, body_len) = if flags & ZMTP_LONG != 0 {
+        if src.len() < 9 {
+            return None;
+        }
+        let mut len = [0u8; 8];
+        len.copy_from_slice(&src[1..9]);
+        (9, u64::from_be_bytes(len) as usize)
+    } else {
+        (2, *src.get(1)? as usize)
+    };
+    if src.len() < header_len + body_len {
+        return None;
+    }
+    src.advance(header_len);
+    let mut message = Vec::with_capacity(1 + body_len);
+    let mut zws_flags = flags & ZMTP_MORE;
+    if flags & ZMTP_COMMAND != 0 {
+        zws_flags |= ZWS_COMMAND;
+    }
+    message.push(zws_flags);
+    message.extend_from_slice(&src.split_to(body_len));
+    Some(message)
+}
+
+impl<S> AsyncRead for ZwsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        while this.read_buf.is_empty() {
+            match ready!(this.ws.poll_next_unpin(cx)) {
+                Some(Ok(WsMessage::Binary(message))) => zmtp_frame(&message, &mut this.read_buf)?,
+                Some(Ok(WsMessage::Close(_))) | None => return Poll::Ready(Ok(0)),
+                // Pings are answered by tungstenite, text isn't used by ZWS
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Poll::Ready(Err(ws_error(err))),
+            }
+        }
+        let len = buf.len().min(this.read_buf.len());
+        buf[..len].copy_from_slice(&this.read_buf.split_to(len));
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<S> AsyncWrite for ZwsStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        // Nothing more is taken while earlier messages wait for the
+        // WebSocket, so that a slow peer pushes back on the send queue
+        ready!(this.poll_send(cx))?;
+        this.write_buf.extend_from_slice(buf);
+        while let Some(message) = zws_frame(&mut this.write_buf) {
+            this.outgoing.push_back(WsMessage::Binary(message.into()));
+        }
+        // Whatever can't be sent right away goes out on flush or on the
+        // next write
+        if let Poll::Ready(Err(err)) = this.poll_send(cx) {
+            return Poll::Ready(Err(err));
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send(cx))?;
+        Pin::new(&mut this.ws).poll_flush(cx).map_err(ws_error)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_send(cx))?;
+        Pin::new(&mut this.ws).poll_close(cx).map_err(ws_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_tungstenite::tungstenite::protocol::Role;
+
+    /// Connection whose peer never reads nor writes anything
+    struct Stalled;
+
+    impl AsyncRead for Stalled {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Pending
+        }
+    }
+
+    impl AsyncWrite for Stalled {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            _buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Pending
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Pending
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_frame_conversion() {
+        let long_body = vec![7u8; 300];
+        let mut zws = vec![ZWS_MORE];
+        zws.extend_from_slice(&long_body);
+
+        let mut zmtp = BytesMut::new();
+        zmtp_frame(&[ZWS_COMMAND, 1, 2], &mut zmtp).unwrap();
+        zmtp_frame(&zws, &mut zmtp).unwrap();
+        assert_eq!(&zmtp[..5], &[ZMTP_COMMAND, 2, 1, 2, ZMTP_MORE | ZMTP_LONG]);
+        assert_eq!(&zmtp[5..13], &300u64.to_be_bytes());
+
+        // Frames are only converted back once complete
+        let mut partial = BytesMut::from(&zmtp[..20]);
+        assert_eq!(zws_frame(&mut partial), Some(vec![ZWS_COMMAND, 1, 2]));
+        assert_eq!(zws_frame(&mut partial), None);
+        partial.extend_from_slice(&zmtp[20..]);
+        assert_eq!(zws_frame(&mut partial), Some(zws));
+        assert!(partial.is_empty());
+
+        assert!(zmtp_frame(&[], &mut zmtp).is_err());
+    }
+
+    #[test]
+    fn test_writes_wait_for_slow_peers() {
+        let ws = futures::executor::block_on(WebSocketStream::from_raw_socket(
+            Stalled,
+            Role::Server,
+            None,
+        ));
+        let mut stream = ZwsStream {
+            ws,
+            read_buf: BytesMut::new(),
+            write_buf: BytesMut::new(),
+            outgoing: VecDeque::new(),
+        };
+        let mut frame = BytesMut::new();
+        let mut zws = vec![0];
+        zws.resize(1 << 16, 7);
+        zmtp_frame(&zws, &mut frame).unwrap();
+
+        let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+        let mut accepted = 0;
+        while let Poll::Ready(written) = Pin::new(&mut stream).poll_write(&mut cx, &frame) {
+            assert_eq!(written.unwrap(), frame.len());
+            accepted += 1;
+            assert!(accepted < 100, "Writes never waited for the peer");
+        }
+        assert_eq!(stream.outgoing.len(), 1);
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+#[derive(Debug)]
+struct Node<K> {
+    // Subscribers of the prefix ending at this node and how many times each
+    // of them subscribed to it
+    subscribers: HashMap<K, usize>,
+    children: HashMap<u8, Node<K>>,
+}
+
+impl<K> Default for Node<K> {
+    fn default() -> Self {
+        Self {
+            subscribers: HashMap::new(),
+            children: HashMap::new(),
+        }
+    }
+}
+
+impl<K> Node<K> {
+    fn is_empty(&self) -> bool {
+        self.subscribers.is_empty() && self.children.is_empty()
+    }
+}
+
+// Nodes nest once per topic byte, dropping them recursively could overflow
+// the stack
+impl<K> Drop for Node<K> {
+    fn drop(&mut self) {
+        let mut descendants: Vec<_> = self.children.drain().map(|(_, child)| child).collect();
+        while let Some(mut node) = descendants.pop() {
+            descendants.extend(node.children.drain().map(|(_, child)| child));
+        }
+    }
+}
+
+/// Prefix trie holding the subscriptions of all peers of a publisher.
+///
+/// Duplicate subscriptions of the same peer are reference counted: a topic
+/// subscribed twice has to be unsubscribed twice before it goes away.
+/// Looking up the subscribers of a message is proportional to the length of
+/// its topic, regardless of the number of subscriptions.
+#[derive(Debug)]
+pub(crate) struct SubscriptionTrie<K> {
+    root: Node<K>,
+}
+
+impl<K> Default for SubscriptionTrie<K> {
+    fn default() -> Self {
+        Self {
+            root: Node::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> SubscriptionTrie<K> {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a subscription. Returns true if nobody was subscribed to this
+    /// exact prefix before
+    pub(crate) fn add(&mut self, prefix: &[u8], key: &K) -> bool {
+        let mut node = &mut self.root;
+        for byte in prefix {
+            node = node.children.entry(*byte).or_default();
+        }
+        let first = node.subscribers.is_empty();
+        *node.subscribers.entry(key.clone()).or_insert(0) += 1;
+        first
+    }
+
+    /// Removes one reference to a subscription. Returns false if the key
+    /// wasn't subscribed to this prefix
+    pub(crate) fn remove(&mut self, prefix: &[u8], key: &K) -> bool {
+        let mut node = &mut self.root;
+        for byte in prefix {
+            node = match node.children.get_mut(byte) {
+                Some(child) => child,
+                None => return false,
+            };
+        }
+        match node.subscribers.get_mut(key) {
+            Some(count) => {
+                *count -= 1;
+                if *count == 0 {
+                    node.subscribers.remove(key);
+                }
+            }
+            None => return false,
+        }
+        self.prune(prefix);
+        true
+    }
+
+    /// Removes all subscriptions of the key. Returns the prefixes it was
+    /// subscribed to
+    pub(crate) fn remove_key(&mut self, key: &K) -> Vec<Vec<u8>> {
+        let mut removed = Vec::new();
+        // Depth first, `prefix` leads to the node being visited. Topics are
+        // chosen by peers, walking them recursively could overflow the stack
+        let mut prefix = Vec::new();
+        let mut stack = vec![(0, None, &mut self.root)];
+        while let Some((depth, byte, node)) = stack.pop() {
+            prefix.truncate(depth);
+            prefix.extend(byte);
+            let Node {
+                subscribers,
+                children,
+            } = node;
+            if subscribers.remove(key).is_some() {
+                removed.push(prefix.clone());
+            }
+            for (byte, child) in children.iter_mut() {
+                stack.push((prefix.len(), Some(*byte), child));
+            }
+        }
+        for prefix in &removed {
+            self.prune(prefix);
+        }
+        removed
+    }
+
+    /// Drops the node of the prefix if nothing is left in it, along with the
+    /// ancestors that only lead to it
+    fn prune(&mut self, prefix: &[u8]) {
+        // Depth of the deepest ancestor that has to stay
+        let mut kept = 0;
+        let mut node = &self.root;
+        for (depth, byte) in prefix.iter().enumerate() {
+            if !node.subscribers.is_empty() || node.children.len() > 1 {
+                kept = depth;
+            }
+            node = match node.children.get(byte) {
+                Some(child) => child,
+                None => return,
+            };
+        }
+        if prefix.is_empty() || !node.is_empty() {
+            return;
+        }
+        let mut node = &mut self.root;
+        for byte in &prefix[..kept] {
+            node = node.children.get_mut(byte).expect("Pruned prefix is gone");
+        }
+        node.children.remove(&prefix[kept]);
+    }
+
+    /// Returns true if anybody is subscribed to this exact prefix
+    pub(crate) fn contains(&self, prefix: &[u8]) -> bool {
+        let mut node = &self.root;
+        for byte in prefix {
+            node = match node.children.get(byte) {
+                Some(child) => child,
+                None => return false,
+            };
+        }
+        !node.subscribers.is_empty()
+    }
+
+    /// Returns every key with a subscription matching the beginning of
+    /// `topic`
+    pub(crate) fn matches(&self, topic: &[u8]) -> HashSet<K> {
+        let mut keys: HashSet<K> = self.root.subscribers.keys().cloned().collect();
+        let mut node = &self.root;
+        for byte in topic {
+            node = match node.children.get(byte) {
+                Some(child) => child,
+                None => break,
+            };
+            keys.extend(node.subscribers.keys().cloned());
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_prefixes() {
+        let mut trie = SubscriptionTrie::new();
+        assert!(trie.add(b"ab", &1));
+        assert!(trie.add(b"abc", &2));
+        assert!(trie.add(b"", &3));
+        assert!(!trie.add(b"ab", &2));
+
+        assert_eq!(trie.matches(b"abcd"), [1, 2, 3].iter().cloned().collect());
+        assert_eq!(trie.matches(b"ab"), [1, 2, 3].iter().cloned().collect());
+        // Topics shorter than a subscription don't match it
+        assert_eq!(trie.matches(b"a"), [3].iter().cloned().collect());
+        assert_eq!(trie.matches(b""), [3].iter().cloned().collect());
+        assert_eq!(trie.matches(b"x"), [3].iter().cloned().collect());
+    }
+
+    #[test]
+    fn test_duplicates_are_reference_counted() {
+        let mut trie = SubscriptionTrie::new();
+        assert!(trie.add(b"topic", &1));
+        assert!(!trie.add(b"topic", &1));
+
+        assert!(trie.remove(b"topic", &1));
+        assert!(trie.contains(b"topic"));
+        assert_eq!(trie.matches(b"topic"), [1].iter().cloned().collect());
+
+        assert!(trie.remove(b"topic", &1));
+        assert!(!trie.contains(b"topic"));
+        assert!(trie.matches(b"topic").is_empty());
+        assert!(!trie.remove(b"topic", &1));
+        assert!(trie.root.is_empty());
+    }
+
+    #[test]
+    fn test_remove_key() {
+        let mut trie = SubscriptionTrie::new();
+        trie.add(b"a", &1);
+        trie.add(b"abc", &1);
+        trie.add(b"abc", &1);
+        trie.add(b"ab", &2);
+
+        let mut removed = trie.remove_key(&1);
+        removed.sort();
+        assert_eq!(removed, vec![b"a".to_vec(), b"abc".to_vec()]);
+        assert!(!trie.contains(b"a"));
+        assert!(!trie.contains(b"abc"));
+        assert_eq!(trie.matches(b"abcd"), [2].iter().cloned().collect());
+
+        assert_eq!(trie.remove_key(&2), vec![b"ab".to_vec()]);
+        assert!(trie.root.is_empty());
+    }
+
+    #[test]
+    fn test_long_topics() {
+        // Deep enough to overflow the stack of a recursive walk
+        let topic = vec![b'x'; 1 << 20];
+        let mut trie = SubscriptionTrie::new();
+        assert!(trie.add(&topic, &1));
+        assert!(trie.add(&topic[..10], &2));
+        assert_eq!(trie.matches(&topic), [1, 2].iter().cloned().collect());
+        assert!(trie.remove(&topic, &1));
+        assert!(!trie.contains(&topic));
+        assert!(trie.contains(&topic[..10]));
+
+        assert!(trie.add(&topic, &1));
+        assert_eq!(trie.remove_key(&1), vec![topic.clone()]);
+        assert_eq!(trie.remove_key(&2), vec![topic[..10].to_vec()]);
+        assert!(trie.root.is_empty());
+
+        // Dropped with the subscription still in place
+        trie.add(&topic, &1);
+    }
+}
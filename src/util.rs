@@ -1,23 +1,35 @@
-use crate::codec::{CodecResult, FramedIo};
+use crate::codec::mechanism::ZmqMechanism;
+use crate::codec::{
+    CodecResult, ConnectionId, FramedIo, GreetingPart, Liveness, ZmqRecvQueue, ZmqSendQueue,
+};
+use crate::security::Security;
 use crate::*;
 
+use crate::task_handle::TaskHandle;
 use bytes::Bytes;
+
+use futures::channel::oneshot;
 use futures::stream::StreamExt;
-use futures::SinkExt;
-use futures_codec::FramedRead;
-use num_traits::Pow;
+use futures::{FutureExt, SinkExt};
 use rand::Rng;
-use std::convert::{TryFrom, TryInto};
+use std::borrow::Borrow;
+use std::convert::TryFrom;
 use std::sync::Arc;
+use std::time::Instant;
 use uuid::Uuid;
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Clone)]
 pub struct PeerIdentity(Vec<u8>);
 
 impl PeerIdentity {
+    /// Generates a random identity. Like the ones libzmq generates, it starts
+    /// with a zero byte so it never collides with one chosen by a peer
     pub fn new() -> Self {
         let id = Uuid::new_v4();
-        Self(id.as_bytes().to_vec())
+        let mut data = Vec::with_capacity(17);
+        data.push(0);
+        data.extend_from_slice(id.as_bytes());
+        Self(data)
     }
 }
 
@@ -37,12 +49,26 @@ impl TryFrom<Vec<u8>> for PeerIdentity {
             Err(ZmqError::Other(
                 "ZMQ_IDENTITY should not be more than 255 bytes long",
             ))
+        } else if data[0] == 0 {
+            // Reserved for generated identities, see
+            // https://rfc.zeromq.org/spec/23/#the-identity-property
+            Err(ZmqError::Other(
+                "ZMQ_IDENTITY should not start with a zero byte",
+            ))
         } else {
             Ok(Self(data))
         }
     }
 }
 
+/// Lets ROUTER and STREAM sockets look their peers up by the routing id
+/// frame of a message, generated identities included
+impl Borrow<[u8]> for PeerIdentity {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl From<PeerIdentity> for Vec<u8> {
     fn from(p_id: PeerIdentity) -> Self {
         p_id.0
@@ -57,8 +83,8 @@ impl From<PeerIdentity> for Bytes {
 
 pub(crate) struct Peer {
     pub(crate) _identity: PeerIdentity,
-    pub(crate) send_queue: FramedWrite<Box<dyn FrameableWrite>, ZmqCodec>,
-    pub(crate) recv_queue: FramedRead<Box<dyn FrameableRead>, ZmqCodec>,
+    pub(crate) send_queue: ZmqSendQueue,
+    pub(crate) recv_queue: ZmqRecvQueue,
 }
 
 const COMPATIBILITY_MATRIX: [u8; 121] = [
@@ -84,8 +110,19 @@ const COMPATIBILITY_MATRIX: [u8; 121] = [
 /// assert!(sockets_compatible(SocketType::REQ, SocketType::REP));
 /// assert!(sockets_compatible(SocketType::DEALER, SocketType::ROUTER));
 /// assert!(!sockets_compatible(SocketType::PUB, SocketType::REP));
+/// assert!(sockets_compatible(SocketType::RADIO, SocketType::DISH));
+/// assert!(!sockets_compatible(SocketType::PUB, SocketType::DISH));
 /// ```
 pub fn sockets_compatible(one: SocketType, another: SocketType) -> bool {
+    match (one, another) {
+        (SocketType::RADIO, SocketType::DISH) | (SocketType::DISH, SocketType::RADIO) => {
+            return true
+        }
+        (SocketType::RADIO | SocketType::DISH, _) | (_, SocketType::RADIO | SocketType::DISH) => {
+            return false
+        }
+        _ => {}
+    }
     let row_index = one.to_usize().unwrap();
     let col_index = another.to_usize().unwrap();
     COMPATIBILITY_MATRIX[row_index * 11 + col_index] != 0
@@ -98,7 +135,7 @@ fn negotiate_version(greeting: Option<CodecResult<Message>>) -> ZmqResult<ZmtpVe
     let my_version = ZmqGreeting::default().version;
 
     match greeting {
-        Some(Ok(Message::Greeting(peer))) => {
+        Some(Ok(Message::Greeting(GreetingPart::Rest(peer)))) => {
             if peer.version >= my_version {
                 // A peer MUST accept higher protocol versions as valid. That is,
                 // a ZMTP peer MUST accept protocol versions greater or equal to 3.0.
@@ -108,12 +145,16 @@ fn negotiate_version(greeting: Option<CodecResult<Message>>) -> ZmqResult<ZmtpVe
                 // A peer SHALL always use its own protocol (including framing)
                 // when talking to an equal or higher protocol peer.
                 Ok(my_version)
-            } else {
+            } else if peer.version.0 == my_version.0 {
+                // ZMTP 3.0 shares the framing of 3.1, only the commands it
+                // knows differ
+                Ok(peer.version)
+            } else if peer.version == (2, 0) || peer.version == (1, 0) {
                 // A peer MAY downgrade its protocol to talk to a lower protocol peer.
-                //
+                Ok(peer.version)
+            } else {
                 // If a peer cannot downgrade its protocol to match its peer, it MUST
                 // close the connection.
-                // TODO: implement interoperability with older protocol versions
                 Err(ZmqError::UnsupportedVersion(peer.version))
             }
         }
@@ -121,49 +162,163 @@ fn negotiate_version(greeting: Option<CodecResult<Message>>) -> ZmqResult<ZmtpVe
     }
 }
 
-pub(crate) async fn greet_exchange(raw_socket: &mut FramedIo) -> ZmqResult<ZmtpVersion> {
+/// Exchanges greetings part by part, see [`GreetingPart`], and switches the
+/// framing of the connection to the version agreed on
+pub(crate) async fn greet_exchange(
+    raw_socket: &mut FramedIo,
+    security: &Security,
+    socket_type: SocketType,
+    identity: Option<&PeerIdentity>,
+) -> ZmqResult<ZmtpVersion> {
+    let identity: Bytes = identity.map_or_else(Bytes::new, |id| id.clone().into());
+    raw_socket
+        .write_half
+        .send(Message::Greeting(GreetingPart::Signature {
+            identity_len: identity.len(),
+        }))
+        .await?;
+    match raw_socket.read_half.next().await {
+        Some(Ok(Message::Greeting(GreetingPart::Signature { .. }))) => {}
+        Some(Ok(Message::Greeting(GreetingPart::Unversioned))) => {
+            if security.mechanism() != ZmqMechanism::NULL {
+                return Err(ZmqError::Other("Peer uses a different security mechanism"));
+            }
+            // Our signature was the header of our identity frame, ZMTP 1.0
+            // peers expect its body next
+            raw_socket.write_half.encoder_mut().set_raw();
+            raw_socket
+                .write_half
+                .send(Message::Message(ZmqMessage::from(identity)))
+                .await?;
+            raw_socket.write_half.encoder_mut().set_zmtp_version((1, 0));
+            return Ok((1, 0));
+        }
+        Some(Err(e)) => return Err(e.into()),
+        _ => return Err(ZmqError::Other("Failed Greeting exchange")),
+    }
+
+    let mut greeting = ZmqGreeting {
+        mechanism: security.mechanism(),
+        as_server: security.as_server(),
+        ..ZmqGreeting::default()
+    };
     raw_socket
         .write_half
-        .send(Message::Greeting(ZmqGreeting::default()))
+        .send(Message::Greeting(GreetingPart::Major(greeting.version.0)))
+        .await?;
+    match raw_socket.read_half.next().await {
+        Some(Ok(Message::Greeting(GreetingPart::Major(major)))) if major < 2 => {
+            // ZMTP 1.0 and 2.0 peers, whose major versions are 0 and 1,
+            // expect our socket type instead
+            greeting.version = (major + 1, 0);
+            greeting.socket_type = Some(socket_type);
+        }
+        Some(Ok(Message::Greeting(GreetingPart::Major(_)))) => {}
+        Some(Err(e)) => return Err(e.into()),
+        _ => return Err(ZmqError::Other("Failed Greeting exchange")),
+    }
+    raw_socket
+        .write_half
+        .send(Message::Greeting(GreetingPart::Rest(greeting)))
         .await?;
 
     let greeting: Option<CodecResult<Message>> = raw_socket.read_half.next().await;
-    negotiate_version(greeting)
+    if let Some(Ok(Message::Greeting(GreetingPart::Rest(peer)))) = &greeting {
+        if peer.mechanism != security.mechanism() {
+            return Err(ZmqError::Other("Peer uses a different security mechanism"));
+        }
+        if let Some(peer_type) = peer.socket_type {
+            if !sockets_compatible(socket_type, peer_type) {
+                return Err(ZmqError::Other(
+                    "Provided sockets combination is not compatible",
+                ));
+            }
+        }
+    }
+    let version = negotiate_version(greeting)?;
+    raw_socket
+        .write_half
+        .encoder_mut()
+        .set_zmtp_version(version);
+    if version.0 < 3 {
+        // ZMTP 1.0 and 2.0 greetings end with the identity frame
+        raw_socket
+            .write_half
+            .send(Message::Message(ZmqMessage::from(identity)))
+            .await?;
+    }
+    Ok(version)
+}
+
+/// Takes the place of the security handshake with ZMTP 1.0 and 2.0 peers.
+/// They have neither security mechanisms nor metadata, only the identity
+/// frame ending their greeting
+async fn legacy_handshake(
+    raw_socket: &mut FramedIo,
+    options: &SocketOptions,
+) -> ZmqResult<PeerIdentity> {
+    if options.zap_handler.is_some() {
+        // Like libzmq, which has no way to authenticate them either
+        return Err(ZmqError::AuthenticationFailed(
+            "ZAP needs ZMTP 3.0 peers".into(),
+        ));
+    }
+    match raw_socket.read_half.next().await {
+        Some(Ok(Message::Message(identity))) => {
+            PeerIdentity::try_from(identity.get(0).map_or_else(Vec::new, |id| id.to_vec()))
+        }
+        Some(Err(e)) => Err(e.into()),
+        _ => Err(ZmqError::Other("Failed to receive the peer identity")),
+    }
+}
+
+/// Checks the metadata a peer sent in its READY or INITIATE command and
+/// returns the peer's identity along with the metadata
+pub(crate) fn check_peer_metadata(
+    socket_type: SocketType,
+    command: &ZmqCommand,
+) -> ZmqResult<(PeerIdentity, PeerMetadata)> {
+    let properties = command.properties()?;
+    let other_sock_type = properties
+        .get("Socket-Type")
+        .and_then(|x| std::str::from_utf8(x).ok())
+        .map(SocketType::try_from)
+        .unwrap_or(Err(ZmqError::Other("Failed to parse other socket type")))?;
+
+    let peer_id = match properties.get("Identity") {
+        Some(identity) => PeerIdentity::try_from(identity.to_vec())?,
+        None => PeerIdentity::new(),
+    };
+
+    if sockets_compatible(socket_type, other_sock_type) {
+        let mut metadata = PeerMetadata::default();
+        for (property, value) in properties {
+            metadata.insert(property, value);
+        }
+        Ok((peer_id, metadata))
+    } else {
+        Err(ZmqError::Other(
+            "Provided sockets combination is not compatible",
+        ))
+    }
 }
 
 pub(crate) async fn ready_exchange(
     raw_socket: &mut FramedIo,
     socket_type: SocketType,
-) -> ZmqResult<PeerIdentity> {
-    let ready = ZmqCommand::ready(socket_type);
+    options: &SocketOptions,
+) -> ZmqResult<(PeerIdentity, PeerMetadata)> {
+    let ready = ZmqCommand::ready(socket_type, options);
     raw_socket.write_half.send(Message::Command(ready)).await?;
 
     let ready_repl: Option<CodecResult<Message>> = raw_socket.read_half.next().await;
     match ready_repl {
-        Some(Ok(Message::Command(command))) => match command.name {
-            ZmqCommandName::READY => {
-                let other_sock_type = command
-                    .properties
-                    .get("Socket-Type")
-                    .map(|x| SocketType::try_from(x.as_str()))
-                    .unwrap_or(Err(ZmqError::Other("Failed to parse other socket type")))?;
-
-                let peer_id = command
-                    .properties
-                    .get("Identity")
-                    .map_or_else(PeerIdentity::new, |x| {
-                        x.clone().into_bytes().try_into().unwrap()
-                    });
-
-                if sockets_compatible(socket_type, other_sock_type) {
-                    Ok(peer_id)
-                } else {
-                    Err(ZmqError::Other(
-                        "Provided sockets combination is not compatible",
-                    ))
-                }
-            }
-        },
+        Some(Ok(Message::Command(command))) if command.name == ZmqCommandName::READY => {
+            check_peer_metadata(socket_type, &command)
+        }
+        Some(Ok(Message::Command(command))) if command.name == ZmqCommandName::ERROR => {
+            Err(ZmqError::AuthenticationFailed(command.error_reason()))
+        }
         Some(Ok(_)) => Err(ZmqError::Other("Failed to confirm ready state")),
         Some(Err(e)) => Err(e.into()),
         None => Err(ZmqError::Other("No reply from server")),
@@ -172,35 +327,303 @@ pub(crate) async fn ready_exchange(
 
 pub(crate) async fn peer_connected(
     mut raw_socket: FramedIo,
+    address: &Endpoint,
     backend: Arc<dyn MultiPeerBackend>,
 ) -> ZmqResult<PeerIdentity> {
-    greet_exchange(&mut raw_socket).await?;
-    let peer_id = ready_exchange(&mut raw_socket, backend.socket_type()).await?;
-    backend.peer_connected(&peer_id, raw_socket);
+    if backend.socket_type() == SocketType::STREAM {
+        // STREAM sockets talk to plain TCP peers, there is no ZMTP handshake
+        raw_socket.read_half.decoder_mut().set_raw();
+        raw_socket.write_half.encoder_mut().set_raw();
+        let metadata = connection_metadata(&raw_socket, address, PeerMetadata::default());
+        raw_socket.read_half.decoder_mut().set_metadata(metadata);
+        let peer_id = PeerIdentity::new();
+        backend.peer_connected(&peer_id, raw_socket)?;
+        return Ok(peer_id);
+    }
+    if let Endpoint::Udp(..) = address {
+        // Datagrams carry messages as they are, there is no handshake
+        let peer_id = PeerIdentity::new();
+        backend.peer_connected(&peer_id, raw_socket)?;
+        return Ok(peer_id);
+    }
+    let options = backend.socket_options();
+    raw_socket
+        .read_half
+        .decoder_mut()
+        .set_max_msg_size(options.max_msg_size);
+    let handshake = async {
+        let version = match address {
+            // ZWS 2.0 has no greeting, the WebSocket handshake agreed on the
+            // security mechanism already. Subscriptions are sent as messages,
+            // as in ZMTP 3.0
+            Endpoint::Ws(..) | Endpoint::Wss(..) => {
+                let version = (3, 0);
+                raw_socket.read_half.decoder_mut().set_zmtp_version(version);
+                raw_socket
+                    .write_half
+                    .encoder_mut()
+                    .set_zmtp_version(version);
+                version
+            }
+            _ => {
+                greet_exchange(
+                    &mut raw_socket,
+                    &options.security,
+                    backend.socket_type(),
+                    options.peer_id.as_ref(),
+                )
+                .await?
+            }
+        };
+        let (peer_id, metadata) = if version.0 < 3 {
+            let peer_id = legacy_handshake(&mut raw_socket, options).await?;
+            (peer_id, PeerMetadata::default())
+        } else {
+            options
+                .security
+                .handshake(&mut raw_socket, backend.socket_type(), options, address)
+                .await?
+        };
+        Ok::<_, ZmqError>((version, peer_id, metadata))
+    };
+    let (version, peer_id, metadata) = match options.handshake_timeout {
+        Some(timeout) => futures::select! {
+            peer = handshake.fuse() => peer?,
+            _ = async_rt::task::sleep(timeout).fuse() => {
+                return Err(ZmqError::Other("Handshake timed out"));
+            },
+        },
+        None => handshake.await?,
+    };
+    let metadata = connection_metadata(&raw_socket, address, metadata);
+    raw_socket
+        .read_half
+        .decoder_mut()
+        .set_metadata(metadata.clone());
+    notify_monitor(
+        &backend,
+        SocketEvent::HandshakeSucceeded(peer_id.clone(), metadata),
+    );
+    let probe = matches!(
+        backend.socket_type(),
+        SocketType::ROUTER | SocketType::DEALER | SocketType::REQ
+    );
+    if probe && options.probe_router {
+        raw_socket
+            .write_half
+            .send(Message::Message(ZmqMessage::from(Bytes::new())))
+            .await?;
+    }
+    raw_socket.zmtp_version = version;
+    let liveness = raw_socket.liveness.clone();
+    let connection = raw_socket.connection_id;
+    backend.clone().peer_connected(&peer_id, raw_socket)?;
+    if heartbeats(version) {
+        spawn_heartbeat(backend, peer_id.clone(), connection, liveness);
+    }
     Ok(peer_id)
 }
 
-pub(crate) async fn connect_forever(endpoint: Endpoint) -> ZmqResult<(FramedIo, Endpoint)> {
-    let mut try_num: u64 = 0;
+/// Adds what the connection tells about the peer to its metadata: the
+/// `Peer-Address` of TCP, TLS and WebSocket peers and the properties set by
+/// the transport
+fn connection_metadata(
+    raw_socket: &FramedIo,
+    address: &Endpoint,
+    mut metadata: PeerMetadata,
+) -> PeerMetadata {
+    if let Endpoint::Tcp(host, _)
+    | Endpoint::TlsTcp(host, _)
+    | Endpoint::Ws(host, ..)
+    | Endpoint::Wss(host, ..) = address
+    {
+        metadata.insert("Peer-Address", host.to_string());
+    }
+    for (property, value) in raw_socket.transport_metadata.iter() {
+        metadata.insert(property, value.clone());
+    }
+    metadata
+}
+
+/// PING and PONG commands came with ZMTP 3.1, older peers don't know them
+fn heartbeats(version: ZmtpVersion) -> bool {
+    version >= (3, 1)
+}
+
+/// Sends a PING to the peer every `heartbeat_ivl` and drops it if it stays
+/// silent for `heartbeat_timeout` after one. Stops once the connection is
+/// gone
+fn spawn_heartbeat(
+    backend: Arc<dyn MultiPeerBackend>,
+    peer_id: PeerIdentity,
+    connection: ConnectionId,
+    liveness: Arc<Liveness>,
+) {
+    let options = backend.socket_options();
+    let (ivl, timeout) = match (options.heartbeat_ivl(), options.heartbeat_timeout()) {
+        (Some(ivl), Some(timeout)) => (ivl, timeout),
+        _ => return,
+    };
+    let ttl = options.heartbeat_ttl().unwrap_or_default();
+    async_rt::task::spawn(async move {
+        let mut next_ping = Instant::now() + ivl;
+        // Time of the first PING the peer didn't answer yet
+        let mut unanswered: Option<Instant> = None;
+        loop {
+            let wake_up = match unanswered {
+                Some(sent) => next_ping.min(sent + timeout),
+                None => next_ping,
+            };
+            async_rt::task::sleep(wake_up.saturating_duration_since(Instant::now())).await;
+            let now = Instant::now();
+            if let Some(sent) = unanswered {
+                if liveness.last_received() >= sent {
+                    unanswered = None;
+                } else if now >= sent + timeout {
+                    log::debug!("Peer {:?} timed out", peer_id);
+                    backend.connection_lost(&peer_id, connection);
+                    // REP backends report disconnections themselves
+                    if backend.socket_type() != SocketType::REP {
+                        notify_monitor(&backend, SocketEvent::Disconnected(peer_id));
+                    }
+                    return;
+                }
+            }
+            if now >= next_ping {
+                if !liveness.send_command(ZmqCommand::ping(ttl, &[])) {
+                    return;
+                }
+                unanswered.get_or_insert(now);
+                next_ping = now + ivl;
+            }
+        }
+    });
+}
+
+fn notify_monitor(backend: &Arc<dyn MultiPeerBackend>, event: SocketEvent) {
+    if let Some(monitor) = backend.monitor().lock().as_mut() {
+        let _ = monitor.try_send(event);
+    }
+}
+
+/// Sleeps before the next connection attempt, letting the monitor know
+async fn delay_reconnect(backend: &Arc<dyn MultiPeerBackend>, try_num: u32) {
+    notify_monitor(backend, SocketEvent::ConnectDelayed);
+    let delay = backend.socket_options().reconnect_delay(try_num);
+    let jitter = {
+        let mut rng = rand::thread_rng();
+        delay.mul_f64(rng.gen_range(0.0f64, 0.1f64))
+    };
+    async_rt::task::sleep(delay + jitter).await;
+    notify_monitor(backend, SocketEvent::ConnectRetried);
+}
+
+pub(crate) async fn connect_forever(
+    endpoint: &Endpoint,
+    backend: &Arc<dyn MultiPeerBackend>,
+) -> ZmqResult<(FramedIo, Endpoint)> {
+    let mut try_num: u32 = 0;
     loop {
-        match transport::connect(&endpoint).await {
+        match transport::connect(endpoint, backend.socket_options()).await {
             Ok(res) => return Ok(res),
             Err(ZmqError::Network(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
-                if try_num < 5 {
-                    try_num += 1;
-                }
-                let delay = {
-                    let mut rng = rand::thread_rng();
-                    std::f64::consts::E.pow(try_num as f64 / 3.0) + rng.gen_range(0.0f64, 0.1f64)
-                };
-                async_rt::task::sleep(std::time::Duration::from_secs_f64(delay)).await;
-                continue;
+                delay_reconnect(backend, try_num).await;
+                try_num = try_num.saturating_add(1);
             }
             Err(e) => return Err(e),
         }
     }
 }
 
+/// UDP only carries messages from RADIO sockets, which connect, to DISH
+/// ones, which bind
+pub(crate) fn check_transport(
+    endpoint: &Endpoint,
+    socket_type: SocketType,
+    bind: bool,
+) -> ZmqResult<()> {
+    match (endpoint, socket_type, bind) {
+        (Endpoint::Udp(..), SocketType::DISH, true) => Ok(()),
+        (Endpoint::Udp(..), SocketType::RADIO, false) => Ok(()),
+        (Endpoint::Udp(..), _, true) => Err(ZmqError::Socket(
+            "Only DISH sockets can bind to udp:// endpoints",
+        )),
+        (Endpoint::Udp(..), _, false) => Err(ZmqError::Socket(
+            "Only RADIO sockets can connect to udp:// endpoints",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Connects to the endpoint and hands the new peer over to the backend.
+/// Also returns the connection and a receiver that completes once it is
+/// lost
+pub(crate) async fn connect_peer(
+    endpoint: &Endpoint,
+    backend: Arc<dyn MultiPeerBackend>,
+) -> ZmqResult<(PeerIdentity, ConnectionId, oneshot::Receiver<()>)> {
+    let (socket, resolved) = connect_forever(endpoint, &backend).await?;
+    let (socket, closed) = socket.notify_on_close();
+    let connection = socket.connection_id;
+    let peer_id = peer_connected(socket, &resolved, backend.clone()).await?;
+    notify_monitor(&backend, SocketEvent::Connected(resolved, peer_id.clone()));
+    Ok((peer_id, connection, closed))
+}
+
+/// Keeps a connected endpoint alive, see [`Socket::connect`]. Dropping it
+/// stops reconnecting to the endpoint, [`Socket::disconnect`] also closes
+/// the current connection.
+///
+/// The reconnection coroutine returns the peer it is currently connected
+/// to and the connection serving it, if any
+pub struct ConnectHandle(pub(crate) TaskHandle<Option<(PeerIdentity, ConnectionId)>>);
+
+/// Spawns a coroutine re-dialing `endpoint` every time the connection to it
+/// is lost, until the returned handle is dropped or shut down
+pub(crate) fn spawn_reconnect(
+    endpoint: Endpoint,
+    backend: Arc<dyn MultiPeerBackend>,
+    connection: (PeerIdentity, ConnectionId, oneshot::Receiver<()>),
+) -> ConnectHandle {
+    let (stop_channel, stop_receiver) = oneshot::channel::<()>();
+    let join_handle = async_rt::task::spawn(async move {
+        let mut stop_receiver = stop_receiver.fuse();
+        let (mut peer_id, mut connection, closed) = connection;
+        let mut closed = closed.fuse();
+        loop {
+            futures::select_biased! {
+                _ = stop_receiver => return Ok(Some((peer_id, connection))),
+                _ = closed => {},
+            }
+            log::debug!("Lost connection to {}, reconnecting", endpoint);
+            backend.connection_lost(&peer_id, connection);
+
+            let mut try_num: u32 = 0;
+            loop {
+                futures::select_biased! {
+                    _ = stop_receiver => return Ok(None),
+                    _ = delay_reconnect(&backend, try_num).fuse() => {},
+                }
+                try_num = try_num.saturating_add(1);
+                let connected = futures::select_biased! {
+                    _ = stop_receiver => return Ok(None),
+                    connected = connect_peer(&endpoint, backend.clone()).fuse() => connected,
+                };
+                match connected {
+                    Ok((new_peer_id, new_connection, new_closed)) => {
+                        peer_id = new_peer_id;
+                        connection = new_connection;
+                        closed = new_closed.fuse();
+                        break;
+                    }
+                    Err(e) => log::debug!("Failed to reconnect to {}: {}", endpoint, e),
+                }
+            }
+        }
+    });
+    ConnectHandle(TaskHandle::new(stop_channel, join_handle))
+}
+
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
@@ -265,11 +688,12 @@ pub(crate) mod tests {
     }
 
     fn new_greeting(version: ZmtpVersion) -> CodecResult<Message> {
-        Ok(Message::Greeting(ZmqGreeting {
+        Ok(Message::Greeting(GreetingPart::Rest(ZmqGreeting {
             version,
             mechanism: ZmqMechanism::PLAIN,
             as_server: false,
-        }))
+            socket_type: None,
+        })))
     }
 
     #[test]
@@ -284,17 +708,30 @@ pub(crate) mod tests {
     #[test]
     fn negotiate_version_peer_is_using_a_newer_version() {
         // if the other end is using a newer protocol version, they should adjust to us
-        let peer_version = (3, 1);
+        let peer_version = (3, 2);
         let expected = ZmqGreeting::default().version;
         let actual = negotiate_version(Some(new_greeting(peer_version))).unwrap();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn negotiate_version_peer_is_using_an_older_minor_version() {
+        let actual = negotiate_version(Some(new_greeting((3, 0)))).unwrap();
+        assert_eq!(actual, (3, 0));
+    }
+
     #[test]
     fn negotiate_version_peer_is_using_an_older_version() {
-        // if the other end is using an older protocol version, we should adjust to
-        // them, but interoperability with older peers is not implemented at the
-        // moment, so we just give up immediately, which is allowed by the spec
+        // if the other end is using an older protocol version, we adjust to them
+        for &peer_version in &[(2, 0), (1, 0)] {
+            let actual = negotiate_version(Some(new_greeting(peer_version))).unwrap();
+            assert_eq!(actual, peer_version);
+        }
+    }
+
+    #[test]
+    fn negotiate_version_peer_is_using_an_unknown_older_version() {
+        // there never was a ZMTP 2.1, we give up on it, which is allowed by the spec
         let peer_version = (2, 1);
         let actual = negotiate_version(Some(new_greeting(peer_version)));
         match actual {
@@ -303,6 +740,39 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn heartbeats_need_zmtp_3_1() {
+        assert!(heartbeats((3, 1)));
+        assert!(!heartbeats((3, 0)));
+        assert!(!heartbeats((2, 0)));
+        assert!(!heartbeats((1, 0)));
+    }
+
+    #[test]
+    fn generated_identities_are_reserved() {
+        let id: Vec<u8> = PeerIdentity::new().into();
+        assert_eq!(id[0], 0);
+        assert!(PeerIdentity::try_from(id).is_err());
+    }
+
+    #[test]
+    fn check_peer_metadata_validates_identity() {
+        let mut properties = ZmqCommand::metadata(SocketType::DEALER, &SocketOptions::default());
+        properties.insert("Identity".into(), Bytes::from(vec![b'x'; 300]));
+        let command = ZmqCommand::with_properties(ZmqCommandName::READY, &properties);
+        assert!(check_peer_metadata(SocketType::ROUTER, &command).is_err());
+
+        properties.insert("Identity".into(), Bytes::from_static(b"\0id"));
+        let command = ZmqCommand::with_properties(ZmqCommandName::READY, &properties);
+        assert!(check_peer_metadata(SocketType::ROUTER, &command).is_err());
+
+        properties.insert("Identity".into(), Bytes::from_static(b"id"));
+        let command = ZmqCommand::with_properties(ZmqCommandName::READY, &properties);
+        let (peer_id, metadata) = check_peer_metadata(SocketType::ROUTER, &command).unwrap();
+        assert_eq!(Vec::from(peer_id), b"id");
+        assert_eq!(metadata.get_str("socket-type"), Some("DEALER"));
+    }
+
     #[test]
     fn negotiate_version_invalid_greeting() {
         // could not read the greeting message
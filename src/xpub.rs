@@ -0,0 +1,156 @@
+use crate::endpoint::Endpoint;
+use crate::error::{ZmqError, ZmqResult};
+use crate::message::*;
+use crate::r#pub::PubSocketBackend;
+use crate::transport::AcceptStopHandle;
+use crate::util::{ConnectHandle, PeerIdentity};
+use crate::{
+    MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketOptions, SocketRecv, SocketSend,
+    SocketType,
+};
+
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Same as [`crate::PubSocket`], except that subscriptions are exposed to the
+/// application: [`SocketRecv::recv`] returns the `\x01topic` (subscribe) and
+/// `\x00topic` (unsubscribe) messages sent by subscribers, as well as any
+/// other message they send upstream.
+///
+/// By default only subscriptions that change the overall set of topics are
+/// passed on, see [`crate::SocketOptionsBuilder::xpub_verbose`] and
+/// [`crate::SocketOptionsBuilder::xpub_verboser`]. As in libzmq, the XPUB
+/// options can also be changed on a running socket through its setters.
+pub struct XPubSocket {
+    backend: Arc<PubSocketBackend>,
+    upstream: mpsc::Receiver<(PeerIdentity, ZmqMessage)>,
+    // Subscriber whose message was returned last by `recv`. Manual
+    // subscriptions are applied to it
+    last_peer: Option<PeerIdentity>,
+    binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
+}
+
+impl Drop for XPubSocket {
+    fn drop(&mut self) {
+        self.backend.shutdown();
+    }
+}
+
+impl XPubSocket {
+    /// Equivalent of `ZMQ_XPUB_VERBOSE`. Pass on every subscribe message,
+    /// not only the ones for topics nobody was subscribed to yet.
+    pub fn set_verbose(&mut self, verbose: bool) {
+        let mut options = self.backend.xpub_options.lock();
+        options.verbose_subs = verbose;
+        options.verbose_unsubs = false;
+    }
+
+    /// Equivalent of `ZMQ_XPUB_VERBOSER`. Pass on every subscribe and
+    /// unsubscribe message.
+    pub fn set_verboser(&mut self, verboser: bool) {
+        let mut options = self.backend.xpub_options.lock();
+        options.verbose_subs = verboser;
+        options.verbose_unsubs = verboser;
+    }
+
+    /// Equivalent of `ZMQ_XPUB_MANUAL`. Subscription messages are passed on
+    /// but not applied. The application is expected to call
+    /// [`XPubSocket::subscribe`] / [`XPubSocket::unsubscribe`] instead.
+    pub fn set_manual(&mut self, manual: bool) {
+        self.backend.xpub_options.lock().manual = manual;
+    }
+
+    /// Equivalent of `ZMQ_XPUB_WELCOME_MSG`. The message is sent to every
+    /// subscriber as soon as it connects. Subscribers have to be subscribed
+    /// to it in order to receive it.
+    pub fn set_welcome_msg(&mut self, message: Option<ZmqMessage>) {
+        self.backend.xpub_options.lock().welcome_msg = message;
+    }
+
+    /// Subscribes the peer that sent the last message returned by `recv` to
+    /// `subscription`. Only available in manual mode.
+    pub fn subscribe(&mut self, subscription: &str) -> ZmqResult<()> {
+        let peer_id = self.manual_peer()?;
+        self.backend.subscribe(&peer_id, subscription.as_bytes());
+        Ok(())
+    }
+
+    /// Unsubscribes the peer that sent the last message returned by `recv`
+    /// from `subscription`. Only available in manual mode.
+    pub fn unsubscribe(&mut self, subscription: &str) -> ZmqResult<()> {
+        let peer_id = self.manual_peer()?;
+        self.backend.unsubscribe(&peer_id, subscription.as_bytes());
+        Ok(())
+    }
+
+    fn manual_peer(&self) -> ZmqResult<PeerIdentity> {
+        if !self.backend.xpub_options.lock().manual {
+            return Err(ZmqError::Socket(
+                "Subscriptions can only be set on XPUB sockets in manual mode",
+            ));
+        }
+        self.last_peer
+            .clone()
+            .ok_or(ZmqError::Socket("No subscription received yet"))
+    }
+}
+
+#[async_trait]
+impl SocketSend for XPubSocket {
+    async fn send(&mut self, message: ZmqMessage) -> ZmqResult<()> {
+        self.backend.publish(message)
+    }
+}
+
+#[async_trait]
+impl SocketRecv for XPubSocket {
+    async fn recv(&mut self) -> ZmqResult<ZmqMessage> {
+        match self.upstream.next().await {
+            Some((peer_id, message)) => {
+                self.last_peer = Some(peer_id);
+                Ok(message)
+            }
+            None => Err(ZmqError::NoMessage),
+        }
+    }
+}
+
+#[async_trait]
+impl Socket for XPubSocket {
+    fn with_options(options: SocketOptions) -> Self {
+        let (sender, receiver) = mpsc::channel(1024);
+        Self {
+            backend: Arc::new(PubSocketBackend::new(
+                SocketType::XPUB,
+                Some(sender),
+                options,
+            )),
+            upstream: receiver,
+            last_peer: None,
+            binds: HashMap::new(),
+            connections: HashMap::new(),
+        }
+    }
+
+    fn backend(&self) -> Arc<dyn MultiPeerBackend> {
+        self.backend.clone()
+    }
+
+    fn binds(&mut self) -> &mut HashMap<Endpoint, AcceptStopHandle> {
+        &mut self.binds
+    }
+
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
+    fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.monitor().lock().replace(sender);
+        receiver
+    }
+}
@@ -0,0 +1,97 @@
+use crate::codec::{Message, ZmqRecvQueue};
+use crate::fair_queue::FairQueue;
+use crate::sub::SubSocketBackend;
+use crate::transport::AcceptStopHandle;
+use crate::util::{ConnectHandle, PeerIdentity};
+use crate::{
+    Endpoint, MultiPeerBackend, Socket, SocketBackend, SocketEvent, SocketOptions, SocketRecv,
+    SocketSend, SocketType, ZmqError, ZmqMessage, ZmqResult,
+};
+
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Same as [`crate::SubSocket`], except that subscriptions are sent as raw
+/// messages: `\x01topic` subscribes, `\x00topic` unsubscribes. Whatever is
+/// passed to [`SocketSend::send`] is forwarded to every connected publisher.
+/// Publishers filter what they send, except legacy ZMTP 1.0 ones which send
+/// everything: their publications are filtered locally on receipt.
+/// Subscriptions are replayed to publishers that connect later on.
+pub struct XSubSocket {
+    backend: Arc<SubSocketBackend>,
+    fair_queue: FairQueue<ZmqRecvQueue, PeerIdentity>,
+    binds: HashMap<Endpoint, AcceptStopHandle>,
+    connections: HashMap<Endpoint, ConnectHandle>,
+}
+
+impl Drop for XSubSocket {
+    fn drop(&mut self) {
+        self.backend.shutdown()
+    }
+}
+
+#[async_trait]
+impl Socket for XSubSocket {
+    fn with_options(options: SocketOptions) -> Self {
+        let fair_queue = FairQueue::new(true);
+        Self {
+            backend: Arc::new(SubSocketBackend::new(
+                fair_queue.inner(),
+                SocketType::XSUB,
+                options,
+            )),
+            fair_queue,
+            binds: HashMap::new(),
+            connections: HashMap::new(),
+        }
+    }
+
+    fn backend(&self) -> Arc<dyn MultiPeerBackend> {
+        self.backend.clone()
+    }
+
+    fn binds(&mut self) -> &mut HashMap<Endpoint, AcceptStopHandle> {
+        &mut self.binds
+    }
+
+    fn connections(&mut self) -> &mut HashMap<Endpoint, ConnectHandle> {
+        &mut self.connections
+    }
+
+    fn monitor(&mut self) -> mpsc::Receiver<SocketEvent> {
+        let (sender, receiver) = mpsc::channel(1024);
+        self.backend.inner.socket_monitor.lock().replace(sender);
+        receiver
+    }
+}
+
+#[async_trait]
+impl SocketSend for XSubSocket {
+    async fn send(&mut self, message: ZmqMessage) -> ZmqResult<()> {
+        self.backend.send_subscription(message).await
+    }
+}
+
+#[async_trait]
+impl SocketRecv for XSubSocket {
+    async fn recv(&mut self) -> ZmqResult<ZmqMessage> {
+        loop {
+            match self.fair_queue.next().await {
+                Some((peer_id, Ok(Message::Message(message)))) => {
+                    if !self.backend.is_filtered_out(&peer_id, &message) {
+                        return Ok(message);
+                    }
+                }
+                Some((_peer_id, Ok(_))) => {}
+                Some((peer_id, Err(e))) => {
+                    log::debug!("XSUB peer {:?} failed: {}", peer_id, e);
+                    self.backend.peer_disconnected(&peer_id);
+                }
+                None => return Err(ZmqError::NoMessage),
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{CurveKeyPair, SocketEvent, SocketOptions, ZmqError, ZmqMessage};
+
+use bytes::Bytes;
+use futures::StreamExt;
+use std::convert::TryInto;
+
+fn curve_server(keypair: &CurveKeyPair) -> SocketOptions {
+    SocketOptions::builder()
+        .curve_server(keypair.clone())
+        .build()
+        .unwrap()
+}
+
+fn curve_client(server_key: [u8; 32]) -> SocketOptions {
+    SocketOptions::builder()
+        .curve_client(server_key, CurveKeyPair::new())
+        .build()
+        .unwrap()
+}
+
+#[async_rt::test]
+async fn test_curve_req_rep() {
+    pretty_env_logger::try_init().ok();
+
+    let server = CurveKeyPair::new();
+    let mut rep_socket = zeromq::RepSocket::with_options(curve_server(&server));
+    let endpoint = rep_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut req_socket = zeromq::ReqSocket::with_options(curve_client(server.public_key));
+    req_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+
+    for i in 0..10u32 {
+        req_socket
+            .send(ZmqMessage::from(format!("Hello {}", i)))
+            .await
+            .unwrap();
+        let request: String = rep_socket.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(request, format!("Hello {}", i));
+        rep_socket
+            .send(ZmqMessage::from(format!("World {}", i)))
+            .await
+            .unwrap();
+        let reply: String = req_socket.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(reply, format!("World {}", i));
+    }
+}
+
+#[async_rt::test]
+async fn test_curve_multipart_and_large_frames() {
+    pretty_env_logger::try_init().ok();
+
+    let server = CurveKeyPair::new();
+    let mut pull_socket = zeromq::PullSocket::with_options(curve_server(&server));
+    let endpoint = pull_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut push_socket = zeromq::PushSocket::with_options(curve_client(server.public_key));
+    push_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+
+    let large = Bytes::from(vec![42u8; 100_000]);
+    let mut message = ZmqMessage::from("header");
+    message.push_back(large.clone());
+    message.push_back(Bytes::new());
+    push_socket.send(message).await.unwrap();
+
+    let received = pull_socket.recv().await.unwrap();
+    assert_eq!(received.len(), 3);
+    assert_eq!(received.get(0).unwrap(), &Bytes::from("header"));
+    assert_eq!(received.get(1).unwrap(), &large);
+    assert!(received.get(2).unwrap().is_empty());
+}
+
+#[async_rt::test]
+async fn test_curve_wrong_server_key() {
+    pretty_env_logger::try_init().ok();
+
+    let server = CurveKeyPair::new();
+    let mut rep_socket = zeromq::RepSocket::with_options(curve_server(&server));
+    let mut monitor = rep_socket.monitor();
+    let endpoint = rep_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let impostor = CurveKeyPair::new();
+    let mut req_socket = zeromq::ReqSocket::with_options(curve_client(impostor.public_key));
+    assert!(req_socket.connect(&endpoint.to_string()).await.is_err());
+    loop {
+        match monitor.next().await.unwrap() {
+            SocketEvent::AcceptFailed(ZmqError::AuthenticationFailed(_)) => break,
+            SocketEvent::AcceptFailed(e) => panic!("Unexpected error {:?}", e),
+            _ => {}
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_curve_server_refuses_null_client() {
+    pretty_env_logger::try_init().ok();
+
+    let server = CurveKeyPair::new();
+    let mut rep_socket = zeromq::RepSocket::with_options(curve_server(&server));
+    let endpoint = rep_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut req_socket = zeromq::ReqSocket::new();
+    assert!(req_socket.connect(&endpoint.to_string()).await.is_err());
+}
@@ -0,0 +1,103 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{CurveKeyPair, SocketOptions, ZmqMessage};
+
+use bytes::Bytes;
+use futures::channel::oneshot;
+use std::convert::TryInto;
+
+#[test]
+fn test_z85_matches_libzmq() {
+    let keypair = CurveKeyPair::new();
+    let encoded = zeromq::z85::encode(&keypair.public_key).unwrap();
+    assert_eq!(encoded, zmq::z85_encode(&keypair.public_key).unwrap());
+    assert_eq!(
+        zeromq::z85::decode(&encoded).unwrap(),
+        zmq::z85_decode(&encoded).unwrap()
+    );
+}
+
+#[async_rt::test]
+async fn test_their_curve_server_our_curve_client() {
+    pretty_env_logger::try_init().ok();
+
+    let server = CurveKeyPair::new();
+    let ctx = zmq::Context::new();
+    let their_rep = ctx.socket(zmq::REP).expect("Couldn't make rep socket");
+    their_rep.set_curve_server(true).unwrap();
+    their_rep.set_curve_secretkey(&server.secret_key).unwrap();
+    their_rep.bind("tcp://127.0.0.1:0").expect("Failed to bind");
+    let endpoint = their_rep.get_last_endpoint().unwrap().unwrap();
+
+    let options = SocketOptions::builder()
+        .curve_client(server.public_key, CurveKeyPair::new())
+        .build()
+        .unwrap();
+    let mut our_req = zeromq::ReqSocket::with_options(options);
+    our_req.connect(&endpoint).await.expect("Failed to connect");
+
+    let their_thread = std::thread::spawn(move || {
+        for _ in 0..3 {
+            let request = their_rep.recv_multipart(0).expect("Failed to recv");
+            assert_eq!(request, vec![b"Hello".to_vec(), vec![7u8; 1000]]);
+            their_rep.send("World", 0).expect("Failed to send");
+        }
+    });
+    for _ in 0..3 {
+        let mut request = ZmqMessage::from("Hello");
+        request.push_back(Bytes::from(vec![7u8; 1000]));
+        our_req.send(request).await.unwrap();
+        let reply: String = our_req.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(reply, "World");
+    }
+    their_thread.join().unwrap();
+}
+
+#[async_rt::test]
+async fn test_their_curve_client_our_curve_server() {
+    pretty_env_logger::try_init().ok();
+
+    let server = CurveKeyPair::new();
+    let options = SocketOptions::builder()
+        .curve_server(server.clone())
+        .build()
+        .unwrap();
+    let mut our_rep = zeromq::RepSocket::with_options(options);
+    let endpoint = our_rep
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let client = zmq::CurveKeyPair::new().unwrap();
+        let ctx = zmq::Context::new();
+        let their_req = ctx.socket(zmq::REQ).expect("Couldn't make req socket");
+        their_req.set_curve_serverkey(&server.public_key).unwrap();
+        their_req.set_curve_publickey(&client.public_key).unwrap();
+        their_req.set_curve_secretkey(&client.secret_key).unwrap();
+        their_req
+            .connect(&endpoint.to_string())
+            .expect("Failed to connect");
+        let mut replies = Vec::new();
+        for i in 0..3 {
+            their_req
+                .send(format!("Hello {}", i).as_str(), 0)
+                .expect("Failed to send");
+            replies.push(their_req.recv_multipart(0).expect("Failed to recv"));
+        }
+        let _ = sender.send(replies);
+    });
+
+    for i in 0..3 {
+        let request: String = our_rep.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(request, format!("Hello {}", i));
+        let mut reply = ZmqMessage::from("World");
+        reply.push_back(Bytes::from(vec![i as u8; 300]));
+        our_rep.send(reply).await.unwrap();
+    }
+    let replies = receiver.await.unwrap();
+    for (i, reply) in replies.into_iter().enumerate() {
+        assert_eq!(reply, vec![b"World".to_vec(), vec![i as u8; 300]]);
+    }
+}
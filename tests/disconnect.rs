@@ -0,0 +1,126 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{Endpoint, SocketOptions, StreamSocket, ZmqError, ZmqMessage};
+
+use futures::FutureExt;
+use std::time::Duration;
+
+/// Raw STREAM sockets report connects and disconnects of their peers as
+/// empty messages, which makes them handy for watching connections
+async fn setup_watcher() -> (StreamSocket, Endpoint) {
+    let mut watcher = StreamSocket::new();
+    let endpoint = watcher
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    (watcher, endpoint)
+}
+
+async fn expect_notification(watcher: &mut StreamSocket) -> ZmqMessage {
+    let message = watcher.recv().await.unwrap();
+    assert_eq!(message.len(), 2);
+    assert!(message.get(1).unwrap().is_empty());
+    message
+}
+
+fn fast_reconnect() -> SocketOptions {
+    SocketOptions::builder()
+        .reconnect_ivl(Duration::from_millis(10))
+        .build()
+        .unwrap()
+}
+
+#[async_rt::test]
+async fn test_disconnect() {
+    pretty_env_logger::try_init().ok();
+
+    let (mut first_watcher, first_endpoint) = setup_watcher().await;
+    let (mut second_watcher, second_endpoint) = setup_watcher().await;
+
+    let mut socket = StreamSocket::with_options(fast_reconnect());
+    socket
+        .connect(&first_endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    socket
+        .connect(&second_endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    let connected = expect_notification(&mut first_watcher).await;
+    expect_notification(&mut second_watcher).await;
+    assert_eq!(socket.connections().len(), 2);
+
+    socket
+        .disconnect(first_endpoint.clone())
+        .await
+        .expect("Failed to disconnect");
+    let disconnected = expect_notification(&mut first_watcher).await;
+    assert_eq!(connected.get(0), disconnected.get(0));
+
+    let connections: Vec<_> = socket.connections().keys().cloned().collect();
+    assert_eq!(connections, vec![second_endpoint.clone()]);
+
+    // No reconnection to a disconnected endpoint
+    futures::select! {
+        message = first_watcher.recv().fuse() => panic!("Unexpected {:?}", message),
+        _ = async_rt::task::sleep(Duration::from_millis(200)).fuse() => {},
+    }
+
+    match socket.disconnect(first_endpoint).await {
+        Err(ZmqError::NoSuchConnection(_)) => {}
+        other => panic!("Unexpected {:?}", other),
+    }
+
+    assert!(socket.disconnect_all().await.is_empty());
+    assert!(socket.connections().is_empty());
+    expect_notification(&mut second_watcher).await;
+}
+
+#[async_rt::test]
+async fn test_connect_twice() {
+    pretty_env_logger::try_init().ok();
+
+    let (mut watcher, endpoint) = setup_watcher().await;
+
+    let mut socket = StreamSocket::with_options(fast_reconnect());
+    socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    assert!(socket.connect(&endpoint.to_string()).await.is_err());
+    expect_notification(&mut watcher).await;
+    assert_eq!(socket.connections().len(), 1);
+
+    socket
+        .disconnect(endpoint)
+        .await
+        .expect("Failed to disconnect");
+    expect_notification(&mut watcher).await;
+
+    // No peer is left behind to reconnect
+    futures::select! {
+        message = watcher.recv().fuse() => panic!("Unexpected {:?}", message),
+        _ = async_rt::task::sleep(Duration::from_millis(200)).fuse() => {},
+    }
+}
+
+#[async_rt::test]
+async fn test_close_tears_down_connections() {
+    pretty_env_logger::try_init().ok();
+
+    let (mut watcher, endpoint) = setup_watcher().await;
+
+    let mut socket = StreamSocket::with_options(fast_reconnect());
+    socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    expect_notification(&mut watcher).await;
+
+    assert!(socket.close().await.is_empty());
+    expect_notification(&mut watcher).await;
+}
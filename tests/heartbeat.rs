@@ -0,0 +1,154 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{SocketEvent, SocketOptions, ZmqMessage};
+
+use futures::channel::oneshot;
+use futures::{FutureExt, StreamExt};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+fn heartbeat_options() -> SocketOptions {
+    SocketOptions::builder()
+        .heartbeat_ivl(Some(Duration::from_millis(50)))
+        .heartbeat_timeout(Some(Duration::from_millis(100)))
+        .build()
+        .unwrap()
+}
+
+/// Completes the ZMTP 3.1 handshake as a socket of the given type, then
+/// never reads or writes anything again unless told to, like a peer whose
+/// host went away
+fn silent_peer(address: &str, socket_type: &str) -> TcpStream {
+    let mut stream = TcpStream::connect(address).unwrap();
+    let mut greeting = [0u8; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[11] = 1;
+    greeting[12..16].copy_from_slice(b"NULL");
+    stream.write_all(&greeting).unwrap();
+    stream.read_exact(&mut greeting).unwrap();
+
+    let mut ready = vec![0x04, 22 + socket_type.len() as u8, 5];
+    ready.extend_from_slice(b"READY");
+    ready.push(11);
+    ready.extend_from_slice(b"Socket-Type");
+    ready.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+    ready.extend_from_slice(socket_type.as_bytes());
+    stream.write_all(&ready).unwrap();
+    stream
+}
+
+#[async_rt::test]
+async fn test_silent_peer_is_disconnected() {
+    pretty_env_logger::try_init().ok();
+
+    let mut pub_socket = zeromq::PubSocket::with_options(heartbeat_options());
+    let mut monitor = pub_socket.monitor();
+    let endpoint = pub_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let address = endpoint.to_string().trim_start_matches("tcp://").to_owned();
+
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(silent_peer(&address, "SUB"));
+    });
+    let stream = receiver.await.unwrap();
+    let disconnected =
+        async { while !matches!(monitor.next().await.unwrap(), SocketEvent::Disconnected(_)) {} };
+    futures::select! {
+        _ = disconnected.fuse() => {},
+        _ = async_rt::task::sleep(Duration::from_secs(5)).fuse() => {
+            panic!("Silent peer was not disconnected");
+        },
+    }
+    drop(stream);
+}
+
+#[async_rt::test]
+async fn test_heartbeats_keep_idle_peers_connected() {
+    pretty_env_logger::try_init().ok();
+
+    let mut pub_socket = zeromq::PubSocket::with_options(heartbeat_options());
+    let mut pub_monitor = pub_socket.monitor();
+    let endpoint = pub_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut sub_socket = zeromq::SubSocket::with_options(heartbeat_options());
+    let mut sub_monitor = sub_socket.monitor();
+    sub_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    sub_socket.subscribe("").await.unwrap();
+
+    async_rt::task::sleep(Duration::from_millis(500)).await;
+    pub_socket
+        .send(ZmqMessage::from("still here"))
+        .await
+        .unwrap();
+    let message = sub_socket.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), b"still here");
+
+    for monitor in [&mut pub_monitor, &mut sub_monitor].iter_mut() {
+        while let Ok(event) = monitor.try_recv() {
+            assert!(
+                !matches!(event, SocketEvent::Disconnected(_)),
+                "Unexpected event {:?}",
+                event
+            );
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_rep_drops_peer_past_its_ttl() {
+    pretty_env_logger::try_init().ok();
+
+    let mut rep_socket = zeromq::RepSocket::new();
+    let mut monitor = rep_socket.monitor();
+    let endpoint = rep_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let address = endpoint.to_string().trim_start_matches("tcp://").to_owned();
+
+    // A PING with a TTL of 100ms, after which nothing comes
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let mut stream = silent_peer(&address, "REQ");
+        stream
+            .write_all(&[0x04, 7, 4, b'P', b'I', b'N', b'G', 0, 1])
+            .unwrap();
+        let _ = sender.send(stream);
+    });
+    let stream = receiver.await.unwrap();
+    futures::select! {
+        request = rep_socket.recv().fuse() => panic!("Unexpected request {:?}", request),
+        _ = async_rt::task::sleep(Duration::from_millis(500)).fuse() => {},
+    }
+    let mut disconnected = false;
+    while let Ok(event) = monitor.try_recv() {
+        disconnected |= matches!(event, SocketEvent::Disconnected(_));
+    }
+    assert!(disconnected, "Peer past its TTL was not disconnected");
+    drop(stream);
+
+    // The socket keeps serving other peers
+    let mut req_socket = zeromq::ReqSocket::new();
+    req_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    req_socket.send(ZmqMessage::from("Hello")).await.unwrap();
+    let request = rep_socket.recv().await.unwrap();
+    assert_eq!(request.get(0).unwrap().as_ref(), b"Hello");
+    rep_socket.send(ZmqMessage::from("World")).await.unwrap();
+    let reply = req_socket.recv().await.unwrap();
+    assert_eq!(reply.get(0).unwrap().as_ref(), b"World");
+}
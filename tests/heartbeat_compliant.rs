@@ -0,0 +1,101 @@
+mod compliance;
+use compliance::{get_monitor_event, setup_monitor};
+
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{SocketEvent, SocketOptions, ZmqMessage};
+
+use futures::channel::oneshot;
+use std::time::Duration;
+
+/// Events libzmq reported so far, without blocking
+fn their_events(monitor: &zmq::Socket) -> Vec<zmq::SocketEvent> {
+    let mut events = Vec::new();
+    while monitor
+        .poll(zmq::POLLIN, 0)
+        .expect("Failed to poll monitor")
+        > 0
+    {
+        events.push(get_monitor_event(monitor).0);
+    }
+    events
+}
+
+#[async_rt::test]
+async fn test_we_answer_their_pings() {
+    pretty_env_logger::try_init().ok();
+
+    let mut our_router = zeromq::RouterSocket::new();
+    let endpoint = our_router
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let ctx = zmq::Context::new();
+    let their_dealer = ctx
+        .socket(zmq::DEALER)
+        .expect("Couldn't make dealer socket");
+    their_dealer.set_heartbeat_ivl(50).unwrap();
+    their_dealer.set_heartbeat_timeout(100).unwrap();
+    let their_monitor = setup_monitor(&ctx, &their_dealer, "inproc://their-monitor");
+    their_dealer
+        .connect(&endpoint.to_string())
+        .expect("Failed to connect");
+
+    // Stay idle for a while, only PONGs keep the connection up
+    async_rt::task::sleep(Duration::from_millis(500)).await;
+    let events = their_events(&their_monitor);
+    assert!(events.contains(&zmq::SocketEvent::HANDSHAKE_SUCCEEDED));
+    assert!(
+        !events.contains(&zmq::SocketEvent::DISCONNECTED),
+        "{:?}",
+        events
+    );
+
+    their_dealer.send("Hello", 0).expect("Failed to send");
+    let message = our_router.recv().await.unwrap();
+    assert_eq!(message.get(1).unwrap().as_ref(), b"Hello");
+}
+
+#[async_rt::test]
+async fn test_they_answer_our_pings() {
+    pretty_env_logger::try_init().ok();
+
+    let ctx = zmq::Context::new();
+    let their_router = ctx
+        .socket(zmq::ROUTER)
+        .expect("Couldn't make router socket");
+    their_router
+        .bind("tcp://127.0.0.1:0")
+        .expect("Failed to bind");
+    let endpoint = their_router.get_last_endpoint().unwrap().unwrap();
+
+    let options = SocketOptions::builder()
+        .heartbeat_ivl(Some(Duration::from_millis(50)))
+        .heartbeat_timeout(Some(Duration::from_millis(100)))
+        .heartbeat_ttl(Some(Duration::from_secs(1)))
+        .build()
+        .unwrap();
+    let mut our_dealer = zeromq::DealerSocket::with_options(options);
+    let mut our_monitor = our_dealer.monitor();
+    our_dealer
+        .connect(&endpoint)
+        .await
+        .expect("Failed to connect");
+
+    async_rt::task::sleep(Duration::from_millis(500)).await;
+    while let Ok(event) = our_monitor.try_recv() {
+        assert!(
+            !matches!(event, SocketEvent::Disconnected(_)),
+            "Unexpected event {:?}",
+            event
+        );
+    }
+
+    our_dealer.send(ZmqMessage::from("Hello")).await.unwrap();
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(their_router.recv_multipart(0).expect("Failed to recv"));
+    });
+    assert_eq!(receiver.await.unwrap()[1], b"Hello");
+}
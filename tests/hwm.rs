@@ -0,0 +1,216 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::util::PeerIdentity;
+use zeromq::{SocketOptions, ZmqError, ZmqMessage};
+
+use bytes::Bytes;
+use futures::FutureExt;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+// Large enough for TCP buffers not to absorb all the messages sent
+const NUM_MSGS: usize = 1000;
+const MSG_SIZE: usize = 64 * 1024;
+
+fn low_hwm() -> SocketOptions {
+    SocketOptions::builder()
+        .send_hwm(5)
+        .recv_hwm(5)
+        .build()
+        .unwrap()
+}
+
+fn payload() -> ZmqMessage {
+    ZmqMessage::from(Bytes::from(vec![0u8; MSG_SIZE]))
+}
+
+/// Receives until nothing arrives for a while, returns the number of
+/// messages received
+async fn drain<S: SocketRecv>(socket: &mut S) -> usize {
+    let mut received = 0;
+    loop {
+        futures::select! {
+            message = socket.recv().fuse() => {
+                message.unwrap();
+                received += 1;
+            },
+            _ = async_rt::task::sleep(Duration::from_millis(500)).fuse() => return received,
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_pub_drops_for_slow_subscriber() {
+    pretty_env_logger::try_init().ok();
+
+    let mut pub_socket = zeromq::PubSocket::with_options(low_hwm());
+    let endpoint = pub_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let mut sub_socket = zeromq::SubSocket::with_options(low_hwm());
+    sub_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    sub_socket.subscribe("").await.unwrap();
+
+    // Wait for the subscription to reach the publisher
+    loop {
+        pub_socket.send(ZmqMessage::from("ready")).await.unwrap();
+        futures::select! {
+            message = sub_socket.recv().fuse() => {
+                message.unwrap();
+                break;
+            },
+            _ = async_rt::task::sleep(Duration::from_millis(50)).fuse() => {},
+        }
+    }
+    drain(&mut sub_socket).await;
+
+    // Publishing never waits for the subscriber
+    for _ in 0..NUM_MSGS {
+        pub_socket.send(payload()).await.unwrap();
+    }
+    let received = drain(&mut sub_socket).await;
+    assert!(received > 0);
+    assert!(received < NUM_MSGS, "Received {} messages", received);
+}
+
+#[async_rt::test]
+async fn test_push_skips_full_peer() {
+    pretty_env_logger::try_init().ok();
+
+    let mut slow_pull = zeromq::PullSocket::with_options(low_hwm());
+    let slow_endpoint = slow_pull
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let mut fast_pull = zeromq::PullSocket::new();
+    let fast_endpoint = fast_pull
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut push_socket = zeromq::PushSocket::with_options(low_hwm());
+    push_socket
+        .connect(&slow_endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    push_socket
+        .connect(&fast_endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+
+    let fast_consumer = async_rt::task::spawn(async move { drain(&mut fast_pull).await });
+    for _ in 0..NUM_MSGS {
+        push_socket.send(payload()).await.unwrap();
+    }
+    let fast_received = fast_consumer.await.unwrap();
+    let slow_received = drain(&mut slow_pull).await;
+
+    // Nothing is lost, the slow peer just gets less
+    assert_eq!(fast_received + slow_received, NUM_MSGS);
+    assert!(
+        slow_received < fast_received,
+        "Slow peer received {} messages, fast one {}",
+        slow_received,
+        fast_received
+    );
+}
+
+#[async_rt::test]
+async fn test_push_blocks_when_all_peers_full() {
+    pretty_env_logger::try_init().ok();
+
+    let mut pull_socket = zeromq::PullSocket::with_options(low_hwm());
+    let endpoint = pull_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let mut push_socket = zeromq::PushSocket::with_options(low_hwm());
+    push_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+
+    let mut sent = 0;
+    while sent < NUM_MSGS {
+        futures::select! {
+            res = push_socket.send(payload()).fuse() => res.unwrap(),
+            _ = async_rt::task::sleep(Duration::from_millis(500)).fuse() => break,
+        }
+        sent += 1;
+    }
+    assert!(sent < NUM_MSGS, "Sending never blocked");
+
+    // Everything queued before blocking gets delivered and there's room again
+    assert_eq!(drain(&mut pull_socket).await, sent);
+    push_socket.send(ZmqMessage::from("more")).await.unwrap();
+    assert_eq!(drain(&mut pull_socket).await, 1);
+}
+
+async fn setup_router_with_slow_dealer(
+    options: SocketOptions,
+) -> (zeromq::RouterSocket, zeromq::DealerSocket) {
+    let mut router = zeromq::RouterSocket::with_options(options);
+    let endpoint = router
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let options = SocketOptions::builder()
+        .peer_identity(PeerIdentity::try_from(b"slow".to_vec()).unwrap())
+        .recv_hwm(5)
+        .build()
+        .unwrap();
+    let mut dealer = zeromq::DealerSocket::with_options(options);
+    dealer
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    dealer.send(ZmqMessage::from("hello")).await.unwrap();
+    router.recv().await.unwrap();
+    (router, dealer)
+}
+
+fn routed_payload() -> ZmqMessage {
+    let mut message = payload();
+    message.push_front(Bytes::from_static(b"slow"));
+    message
+}
+
+#[async_rt::test]
+async fn test_router_drops_for_slow_peer() {
+    pretty_env_logger::try_init().ok();
+
+    let (mut router, mut dealer) = setup_router_with_slow_dealer(low_hwm()).await;
+    for _ in 0..NUM_MSGS {
+        router.send(routed_payload()).await.unwrap();
+    }
+    let received = drain(&mut dealer).await;
+    assert!(received > 0);
+    assert!(received < NUM_MSGS, "Received {} messages", received);
+}
+
+#[async_rt::test]
+async fn test_router_mandatory_fails_for_slow_peer() {
+    pretty_env_logger::try_init().ok();
+
+    let options = SocketOptions::builder()
+        .send_hwm(5)
+        .router_mandatory(true)
+        .build()
+        .unwrap();
+    let (mut router, mut dealer) = setup_router_with_slow_dealer(options).await;
+    let mut sent = 0;
+    loop {
+        match router.send(routed_payload()).await {
+            Ok(()) => sent += 1,
+            Err(ZmqError::BufferFull(_)) => break,
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+        assert!(sent < NUM_MSGS, "Sending never failed");
+    }
+    // Nothing accepted for sending is lost
+    assert_eq!(drain(&mut dealer).await, sent);
+}
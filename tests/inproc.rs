@@ -0,0 +1,99 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{Endpoint, SocketEvent, ZmqError, ZmqMessage};
+
+use futures::StreamExt;
+use std::convert::TryInto;
+use std::time::Duration;
+
+#[async_rt::test]
+async fn test_inproc_req_rep() {
+    pretty_env_logger::try_init().ok();
+
+    let mut rep = zeromq::RepSocket::new();
+    let mut monitor = rep.monitor();
+    let endpoint = rep.bind("inproc://req-rep").await.expect("Failed to bind");
+    assert_eq!(endpoint, Endpoint::Inproc("req-rep".into()));
+
+    let mut req = zeromq::ReqSocket::new();
+    req.connect("inproc://req-rep")
+        .await
+        .expect("Failed to connect");
+    let metadata = loop {
+        if let SocketEvent::HandshakeSucceeded(_, metadata) = monitor.next().await.unwrap() {
+            break metadata;
+        }
+    };
+    assert_eq!(metadata.get_str("Socket-Type"), Some("REQ"));
+
+    for i in 0..10 {
+        req.send(format!("Hello {}", i).into()).await.unwrap();
+        let request = rep.recv().await.unwrap();
+        assert!(request.metadata().is_some());
+        let request: String = request.try_into().unwrap();
+        assert_eq!(request, format!("Hello {}", i));
+        rep.send(ZmqMessage::from("World")).await.unwrap();
+        let reply: String = req.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(reply, "World");
+    }
+}
+
+#[async_rt::test]
+async fn test_inproc_connect_before_bind() {
+    pretty_env_logger::try_init().ok();
+
+    let mut push = zeromq::PushSocket::new();
+    let mut pull = zeromq::PullSocket::new();
+    let (connected, bound) = futures::join!(push.connect("inproc://connect-first"), async {
+        async_rt::task::sleep(Duration::from_millis(50)).await;
+        pull.bind("inproc://connect-first").await
+    });
+    connected.expect("Failed to connect");
+    bound.expect("Failed to bind");
+
+    push.send(ZmqMessage::from("Hello")).await.unwrap();
+    let message: String = pull.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(message, "Hello");
+}
+
+#[async_rt::test]
+async fn test_inproc_incompatible_sockets() {
+    pretty_env_logger::try_init().ok();
+
+    let mut publisher = zeromq::PubSocket::new();
+    let mut monitor = publisher.monitor();
+    publisher
+        .bind("inproc://incompatible")
+        .await
+        .expect("Failed to bind");
+
+    let mut req = zeromq::ReqSocket::new();
+    assert!(req.connect("inproc://incompatible").await.is_err());
+    loop {
+        match monitor.next().await.unwrap() {
+            SocketEvent::AcceptFailed(_) => break,
+            SocketEvent::HandshakeSucceeded(..) => panic!("REQ peers should be refused"),
+            _ => {}
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_inproc_name_in_use() {
+    pretty_env_logger::try_init().ok();
+
+    let mut first = zeromq::PullSocket::new();
+    let endpoint = first.bind("inproc://in-use").await.expect("Failed to bind");
+    let mut second = zeromq::PullSocket::new();
+    match second.bind("inproc://in-use").await {
+        Err(ZmqError::Network(e)) => assert_eq!(e.kind(), std::io::ErrorKind::AddrInUse),
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    // The name is free again once unbound
+    first.unbind(endpoint).await.unwrap();
+    second
+        .bind("inproc://in-use")
+        .await
+        .expect("Failed to bind");
+}
@@ -0,0 +1,60 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{Endpoint, SocketOptions, ZmqMessage};
+
+use std::convert::TryInto;
+use std::path::PathBuf;
+
+fn bound_path(endpoint: &Endpoint) -> PathBuf {
+    match endpoint {
+        Endpoint::Ipc(Some(path)) => path.clone(),
+        other => panic!("Unexpected endpoint {}", other),
+    }
+}
+
+#[async_rt::test]
+async fn test_ipc_wildcard_bind() {
+    pretty_env_logger::try_init().ok();
+
+    let mut first = zeromq::PullSocket::new();
+    let mut second = zeromq::PullSocket::new();
+    let first_endpoint = first.bind("ipc://*").await.expect("Failed to bind");
+    let second_endpoint = second.bind("ipc://*").await.expect("Failed to bind");
+    let first_path = bound_path(&first_endpoint);
+    let second_path = bound_path(&second_endpoint);
+    assert_ne!(first_path, second_path);
+    assert!(first_path.starts_with(std::env::temp_dir()));
+    assert!(first_path.exists());
+
+    let mut push = zeromq::PushSocket::new();
+    push.connect(&first_endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    push.send(ZmqMessage::from("Hello")).await.unwrap();
+    let message: String = first.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(message, "Hello");
+
+    first.unbind(first_endpoint).await.unwrap();
+    assert!(!first_path.exists());
+    assert!(second.close().await.is_empty());
+    assert!(!second_path.exists());
+}
+
+#[async_rt::test]
+async fn test_ipc_wildcard_dir() {
+    pretty_env_logger::try_init().ok();
+
+    let dir = std::env::temp_dir().join(format!("zmq-wildcard-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let options = SocketOptions::builder()
+        .ipc_wildcard_dir(&dir)
+        .build()
+        .unwrap();
+    let mut pull = zeromq::PullSocket::with_options(options);
+    let endpoint = pull.bind("ipc://*").await.expect("Failed to bind");
+    let path = bound_path(&endpoint);
+    assert_eq!(path.parent(), Some(dir.as_path()));
+    assert!(pull.close().await.is_empty());
+    assert!(!path.exists());
+    std::fs::remove_dir(&dir).unwrap();
+}
@@ -0,0 +1,218 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{SocketEvent, ZmqMessage};
+
+use futures::channel::{mpsc, oneshot};
+use futures::StreamExt;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const DEALER: u8 = 5;
+const ROUTER: u8 = 6;
+
+/// Does what libzmq 3.2 does: ZMTP 2.0 greeting, then the identity frame
+fn zmtp_2_0_peer(address: &str, socket_type: u8, identity: &[u8]) -> TcpStream {
+    let mut stream = TcpStream::connect(address).unwrap();
+    let mut signature = [0u8; 10];
+    signature[0] = 0xff;
+    signature[9] = 0x7f;
+    stream.write_all(&signature).unwrap();
+    stream.read_exact(&mut signature).unwrap();
+    assert_eq!(signature[0], 0xff);
+    assert_eq!(signature[9], 0x7f);
+
+    stream.write_all(&[0x01, socket_type]).unwrap();
+    let mut rest = [0u8; 2];
+    stream.read_exact(&mut rest).unwrap();
+    assert_eq!(rest, [3, ROUTER]);
+
+    let mut frame = vec![0x00, identity.len() as u8];
+    frame.extend_from_slice(identity);
+    stream.write_all(&frame).unwrap();
+    // Our identity
+    assert_eq!(read_frame(&mut stream, false), (false, Vec::new()));
+    stream
+}
+
+/// Does what libzmq 2.x does: no greeting, only the identity frame
+fn zmtp_1_0_peer(address: &str, identity: &[u8]) -> TcpStream {
+    let mut stream = TcpStream::connect(address).unwrap();
+    let mut frame = vec![identity.len() as u8 + 1, 0x00];
+    frame.extend_from_slice(identity);
+    stream.write_all(&frame).unwrap();
+    // Our signature, read as the header of our empty identity frame
+    let mut header = [0u8; 10];
+    stream.read_exact(&mut header).unwrap();
+    assert_eq!(header[0], 0xff);
+    assert_eq!(u64::from_be_bytes(header[1..9].try_into().unwrap()), 1);
+    stream
+}
+
+/// Reads a frame, returning its MORE flag and body
+fn read_frame(stream: &mut TcpStream, zmtp_1_0: bool) -> (bool, Vec<u8>) {
+    let mut byte = [0u8; 1];
+    let (more, len) = if zmtp_1_0 {
+        stream.read_exact(&mut byte).unwrap();
+        let len = byte[0] as usize;
+        stream.read_exact(&mut byte).unwrap();
+        (byte[0] & 0x01 != 0, len - 1)
+    } else {
+        stream.read_exact(&mut byte).unwrap();
+        let more = byte[0] & 0x01 != 0;
+        stream.read_exact(&mut byte).unwrap();
+        (more, byte[0] as usize)
+    };
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).unwrap();
+    (more, body)
+}
+
+fn write_frame(stream: &mut TcpStream, zmtp_1_0: bool, body: &[u8]) {
+    let mut frame = if zmtp_1_0 {
+        vec![body.len() as u8 + 1, 0x00]
+    } else {
+        vec![0x00, body.len() as u8]
+    };
+    frame.extend_from_slice(body);
+    stream.write_all(&frame).unwrap();
+}
+
+async fn bind_router() -> (zeromq::RouterSocket, mpsc::Receiver<SocketEvent>, String) {
+    let mut router = zeromq::RouterSocket::new();
+    let monitor = router.monitor();
+    let endpoint = router
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let address = endpoint.to_string().trim_start_matches("tcp://").to_owned();
+    (router, monitor, address)
+}
+
+async fn router_round_trip(router: &mut zeromq::RouterSocket, identity: &[u8]) {
+    let message = router.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), identity);
+    assert_eq!(message.get(1).unwrap().as_ref(), b"hello");
+
+    let mut reply = ZmqMessage::from(bytes::Bytes::copy_from_slice(identity));
+    reply.push_back("world".into());
+    router.send(reply).await.unwrap();
+}
+
+#[async_rt::test]
+async fn test_zmtp_2_0_peer() {
+    pretty_env_logger::try_init().ok();
+
+    let (mut router, mut monitor, address) = bind_router().await;
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let mut stream = zmtp_2_0_peer(&address, DEALER, b"legacy");
+        write_frame(&mut stream, false, b"hello");
+        let _ = sender.send(read_frame(&mut stream, false));
+    });
+
+    router_round_trip(&mut router, b"legacy").await;
+    assert_eq!(receiver.await.unwrap(), (false, b"world".to_vec()));
+    loop {
+        if let SocketEvent::HandshakeSucceeded(peer_id, _) = monitor.next().await.unwrap() {
+            assert_eq!(Vec::from(peer_id), b"legacy");
+            break;
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_zmtp_2_0_incompatible_peer() {
+    pretty_env_logger::try_init().ok();
+
+    let (_router, mut monitor, address) = bind_router().await;
+    std::thread::spawn(move || {
+        let mut stream = TcpStream::connect(address).unwrap();
+        let mut greeting = [0u8; 12];
+        greeting[0] = 0xff;
+        greeting[9] = 0x7f;
+        greeting[10] = 0x01;
+        // PUB
+        greeting[11] = 1;
+        stream.write_all(&greeting).unwrap();
+        let _ = stream.read(&mut greeting);
+    });
+    loop {
+        match monitor.next().await.unwrap() {
+            SocketEvent::AcceptFailed(_) => break,
+            SocketEvent::HandshakeSucceeded(..) => panic!("PUB peers should be refused"),
+            _ => {}
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_zmtp_1_0_peer() {
+    pretty_env_logger::try_init().ok();
+
+    let (mut router, _monitor, address) = bind_router().await;
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let mut stream = zmtp_1_0_peer(&address, b"ancient");
+        write_frame(&mut stream, true, b"hello");
+        let _ = sender.send(read_frame(&mut stream, true));
+    });
+
+    router_round_trip(&mut router, b"ancient").await;
+    assert_eq!(receiver.await.unwrap(), (false, b"world".to_vec()));
+}
+
+#[async_rt::test]
+async fn test_zmtp_1_0_subscriber_gets_everything() {
+    pretty_env_logger::try_init().ok();
+
+    let mut pub_socket = zeromq::PubSocket::new();
+    let mut monitor = pub_socket.monitor();
+    let endpoint = pub_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let address = endpoint.to_string().trim_start_matches("tcp://").to_owned();
+
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let mut stream = zmtp_1_0_peer(&address, b"");
+        let _ = sender.send(read_frame(&mut stream, true));
+    });
+    while !matches!(
+        monitor.next().await.unwrap(),
+        SocketEvent::HandshakeSucceeded(..)
+    ) {}
+    async_rt::task::sleep(Duration::from_millis(50)).await;
+
+    pub_socket.send(ZmqMessage::from("news")).await.unwrap();
+    assert_eq!(receiver.await.unwrap(), (false, b"news".to_vec()));
+}
+
+#[async_rt::test]
+async fn test_zmtp_1_0_publisher_is_filtered() {
+    pretty_env_logger::try_init().ok();
+
+    let mut sub_socket = zeromq::SubSocket::new();
+    let endpoint = sub_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    sub_socket.subscribe("A").await.unwrap();
+    let address = endpoint.to_string().trim_start_matches("tcp://").to_owned();
+
+    let (done, wait) = std::sync::mpsc::channel::<()>();
+    std::thread::spawn(move || {
+        let mut stream = zmtp_1_0_peer(&address, b"");
+        write_frame(&mut stream, true, b"B dropped");
+        write_frame(&mut stream, true, b"A kept");
+        // Keep the connection open until the subscriber is done
+        let _ = wait.recv();
+        drop(stream);
+    });
+
+    let message = sub_socket.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), b"A kept");
+    drop(done);
+}
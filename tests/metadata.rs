@@ -0,0 +1,65 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::util::PeerIdentity;
+use zeromq::{SocketEvent, SocketOptions, ZmqMessage};
+
+use futures::StreamExt;
+use std::convert::TryInto;
+
+#[async_rt::test]
+async fn test_metadata_is_exchanged() {
+    pretty_env_logger::try_init().ok();
+
+    let options = SocketOptions::builder()
+        .metadata("X-Service", "broker")
+        .build()
+        .unwrap();
+    let mut router = zeromq::RouterSocket::with_options(options);
+    let mut monitor = router.monitor();
+    let endpoint = router
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let identity: PeerIdentity = b"worker".to_vec().try_into().unwrap();
+    let options = SocketOptions::builder()
+        .peer_identity(identity)
+        .metadata("X-Service", "worker")
+        .metadata("X-Version", "1.2.3")
+        .build()
+        .unwrap();
+    let mut dealer = zeromq::DealerSocket::with_options(options);
+    dealer
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+
+    let metadata = loop {
+        if let SocketEvent::HandshakeSucceeded(_, metadata) = monitor.next().await.unwrap() {
+            break metadata;
+        }
+    };
+    assert_eq!(metadata.get_str("x-service"), Some("worker"));
+    assert_eq!(metadata.get_str("Socket-Type"), Some("DEALER"));
+    assert_eq!(metadata.get_str("Identity"), Some("worker"));
+    assert_eq!(metadata.peer_address(), Some("127.0.0.1"));
+    assert_eq!(metadata.user_id(), None);
+
+    let request = ZmqMessage::from("Hello");
+    assert!(request.metadata().is_none());
+    dealer.send(request).await.unwrap();
+    let message = router.recv().await.unwrap();
+    let metadata = message
+        .metadata()
+        .expect("Received messages carry metadata");
+    assert_eq!(metadata.get_str("X-Version"), Some("1.2.3"));
+    assert_eq!(metadata.peer_address(), Some("127.0.0.1"));
+
+    let mut reply = ZmqMessage::from("World");
+    reply.push_front(message.get(0).unwrap().clone());
+    router.send(reply).await.unwrap();
+    let reply = dealer.recv().await.unwrap();
+    let metadata = reply.metadata().expect("Received messages carry metadata");
+    assert_eq!(metadata.get_str("X-Service"), Some("broker"));
+    assert_eq!(metadata.get_str("Socket-Type"), Some("ROUTER"));
+}
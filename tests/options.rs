@@ -0,0 +1,205 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::util::PeerIdentity;
+use zeromq::{SocketEvent, SocketOptions, ZmqMessage};
+
+use futures::channel::oneshot;
+use futures::FutureExt;
+use std::convert::{TryFrom, TryInto};
+use std::io::Read;
+use std::net::TcpStream;
+use std::time::Duration;
+
+#[async_rt::test]
+async fn test_identity_is_sent_to_router() {
+    pretty_env_logger::try_init().ok();
+
+    let mut router = zeromq::RouterSocket::new();
+    let endpoint = router
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let identity = PeerIdentity::try_from(b"dealer-1".to_vec()).unwrap();
+    let options = SocketOptions::builder()
+        .peer_identity(identity)
+        .build()
+        .unwrap();
+    let mut dealer = zeromq::DealerSocket::with_options(options);
+    dealer
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    dealer.send(ZmqMessage::from("Hello")).await.unwrap();
+
+    let message = router.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), b"dealer-1");
+    assert_eq!(message.get(1).unwrap().as_ref(), b"Hello");
+
+    // The identity can be used to route replies
+    let mut reply = ZmqMessage::from("World");
+    reply.push_front(bytes::Bytes::from_static(b"dealer-1"));
+    router.send(reply).await.unwrap();
+    let reply: String = dealer.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(reply, "World");
+}
+
+#[async_rt::test]
+async fn test_identity_is_sent_to_their_router() {
+    pretty_env_logger::try_init().ok();
+
+    let ctx = zmq::Context::new();
+    let their_router = ctx.socket(zmq::ROUTER).unwrap();
+    their_router.bind("tcp://127.0.0.1:0").unwrap();
+    let endpoint = their_router.get_last_endpoint().unwrap().unwrap();
+
+    let identity = PeerIdentity::try_from(b"our-dealer".to_vec()).unwrap();
+    let options = SocketOptions::builder()
+        .peer_identity(identity)
+        .build()
+        .unwrap();
+    let mut our_dealer = zeromq::DealerSocket::with_options(options);
+    our_dealer
+        .connect(&endpoint)
+        .await
+        .expect("Failed to connect");
+    our_dealer.send(ZmqMessage::from("Hello")).await.unwrap();
+
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(their_router.recv_multipart(0).unwrap());
+    });
+    let message = receiver.await.unwrap();
+    assert_eq!(message, vec![b"our-dealer".to_vec(), b"Hello".to_vec()]);
+}
+
+#[async_rt::test]
+async fn test_max_msg_size() {
+    pretty_env_logger::try_init().ok();
+
+    let options = SocketOptions::builder()
+        .max_msg_size(Some(16))
+        .build()
+        .unwrap();
+    let mut pull = zeromq::PullSocket::with_options(options);
+    let endpoint = pull
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let options = SocketOptions::builder()
+        .reconnect_ivl(Duration::from_millis(10))
+        .build()
+        .unwrap();
+    let mut push = zeromq::PushSocket::with_options(options);
+    push.connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    push.send(ZmqMessage::from("way too long for the receiver"))
+        .await
+        .unwrap();
+
+    // The oversized message costs the connection, small ones make it
+    // through once the sender has reconnected
+    loop {
+        let _ = push.send(ZmqMessage::from("small")).await;
+        futures::select! {
+            message = pull.recv().fuse() => {
+                let payload: String = message.unwrap().try_into().unwrap();
+                assert_eq!(payload, "small");
+                break;
+            },
+            _ = async_rt::task::sleep(Duration::from_millis(50)).fuse() => {},
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_max_msg_size_rep() {
+    pretty_env_logger::try_init().ok();
+
+    let options = SocketOptions::builder()
+        .max_msg_size(Some(16))
+        .build()
+        .unwrap();
+    let mut rep = zeromq::RepSocket::with_options(options);
+    let mut monitor = rep.monitor();
+    let endpoint = rep.bind("tcp://127.0.0.1:0").await.expect("Failed to bind");
+
+    // The oversized request costs its sender the connection
+    let mut greedy = zeromq::ReqSocket::new();
+    greedy
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    greedy
+        .send(ZmqMessage::from("way too long for the receiver"))
+        .await
+        .unwrap();
+    futures::select! {
+        request = rep.recv().fuse() => panic!("Unexpected request {:?}", request),
+        _ = async_rt::task::sleep(Duration::from_millis(300)).fuse() => {},
+    }
+    let mut disconnected = false;
+    while let Ok(event) = monitor.try_recv() {
+        disconnected |= matches!(event, SocketEvent::Disconnected(_));
+    }
+    assert!(disconnected, "Peer sending an oversized frame was kept");
+
+    // Other peers are still served
+    let mut req = zeromq::ReqSocket::new();
+    req.connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    req.send(ZmqMessage::from("small")).await.unwrap();
+    let request: String = rep.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(request, "small");
+    rep.send(ZmqMessage::from("reply")).await.unwrap();
+    let reply: String = req.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(reply, "reply");
+}
+
+#[async_rt::test]
+async fn test_handshake_timeout() {
+    pretty_env_logger::try_init().ok();
+
+    let options = SocketOptions::builder()
+        .handshake_timeout(Some(Duration::from_millis(100)))
+        .build()
+        .unwrap();
+    let mut rep = zeromq::RepSocket::with_options(options);
+    let endpoint = rep.bind("tcp://127.0.0.1:0").await.expect("Failed to bind");
+    let addr = endpoint.to_string().replace("tcp://", "");
+
+    // A client that never completes the handshake gets disconnected
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let mut client = TcpStream::connect(addr).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut received = Vec::new();
+        let result = client.read_to_end(&mut received);
+        let _ = sender.send(result.map(|_| received.len()));
+    });
+    let received = receiver.await.unwrap().expect("Connection wasn't closed");
+    // Only our signature made it through, the rest of our greeting waits
+    // for the client's
+    assert_eq!(received, 10);
+}
+
+#[test]
+fn test_invalid_options() {
+    assert!(SocketOptions::builder()
+        .reconnect_ivl(Duration::from_secs(0))
+        .build()
+        .is_err());
+    assert!(PeerIdentity::try_from(vec![1u8; 256]).is_err());
+    // Reserved for generated identities
+    assert!(PeerIdentity::try_from(b"\0id".to_vec()).is_err());
+    // Application metadata names must start with X-
+    assert!(SocketOptions::builder()
+        .metadata("Service", "broker")
+        .build()
+        .is_err());
+}
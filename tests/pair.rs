@@ -0,0 +1,60 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::ZmqMessage;
+
+use std::convert::TryInto;
+
+#[async_rt::test]
+async fn test_pair_sockets() {
+    pretty_env_logger::try_init().ok();
+
+    async fn helper(bind_addr: &'static str) {
+        let mut bound = zeromq::PairSocket::new();
+        let endpoint = bound
+            .bind(bind_addr)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to bind to {}: {}", bind_addr, e));
+
+        let mut connected = zeromq::PairSocket::new();
+        connected
+            .connect(endpoint.to_string().as_str())
+            .await
+            .expect("Failed to connect");
+
+        for i in 0..10i32 {
+            connected
+                .send(ZmqMessage::from(format!("Ping {}", i)))
+                .await
+                .unwrap();
+            let ping: String = bound.recv().await.unwrap().try_into().unwrap();
+            assert_eq!(ping, format!("Ping {}", i));
+
+            bound
+                .send(ZmqMessage::from(format!("Pong {}", i)))
+                .await
+                .unwrap();
+            let pong: String = connected.recv().await.unwrap().try_into().unwrap();
+            assert_eq!(pong, format!("Pong {}", i));
+        }
+
+        // A second peer must be refused while the first one is attached
+        let mut intruder = zeromq::PairSocket::new();
+        intruder
+            .connect(endpoint.to_string().as_str())
+            .await
+            .expect("Failed to connect");
+        let _ = intruder.send(ZmqMessage::from("Intruder")).await;
+        connected
+            .send(ZmqMessage::from("Still here"))
+            .await
+            .unwrap();
+        let msg: String = bound.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(msg, "Still here");
+
+        assert!(connected.close().await.is_empty());
+        assert!(bound.close().await.is_empty());
+    }
+
+    let addrs = vec!["tcp://127.0.0.1:0", "tcp://[::1]:0", "ipc://pair-test.sock"];
+    futures::future::join_all(addrs.into_iter().map(helper)).await;
+}
@@ -0,0 +1,158 @@
+mod compliance;
+use compliance::{get_monitor_event, setup_monitor};
+
+use std::convert::TryInto;
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::ZmqMessage;
+
+use futures::channel::oneshot;
+use futures::StreamExt;
+
+/// Returns (socket, bound_endpoint, monitor)
+fn setup_their_pair(bind_endpoint: &str) -> (zmq::Socket, String, zmq::Socket) {
+    let ctx = zmq::Context::new();
+    let their_pair = ctx.socket(zmq::PAIR).expect("Couldn't make pair socket");
+    their_pair.bind(bind_endpoint).expect("Failed to bind");
+
+    let resolved_bind = their_pair.get_last_endpoint().unwrap().unwrap();
+
+    let their_monitor = setup_monitor(&ctx, &their_pair, "inproc://their-monitor");
+
+    (their_pair, resolved_bind, their_monitor)
+}
+
+fn run_their_pair(their_pair: zmq::Socket, num_msgs: u32) -> std::thread::JoinHandle<zmq::Socket> {
+    assert_eq!(their_pair.get_socket_type().unwrap(), zmq::PAIR);
+    std::thread::spawn(move || {
+        for i in 0..num_msgs {
+            let msg = their_pair.recv_msg(0).expect("Failed to recv");
+            assert_eq!(msg.as_str().unwrap(), format!("Ping: {}", i));
+            their_pair
+                .send(&format!("Pong: {}", i), 0)
+                .expect("Failed to send");
+        }
+        println!("Finished pair task");
+        their_pair
+    })
+}
+
+async fn run_our_pair(our_pair: &mut zeromq::PairSocket, num_msgs: u32) {
+    for i in 0..num_msgs {
+        let ms: String = format!("Ping: {}", i);
+        our_pair
+            .send(ZmqMessage::from(ms))
+            .await
+            .expect("Failed to send");
+        let reply: String = our_pair
+            .recv()
+            .await
+            .expect("Failed to recv")
+            .try_into()
+            .unwrap();
+        assert_eq!(reply, format!("Pong: {}", i));
+    }
+}
+
+#[async_rt::test]
+async fn test_their_pair_our_pair() {
+    let (their_pair, bind_endpoint, their_monitor) = setup_their_pair("tcp://127.0.0.1:0");
+    println!("Their pair was bound to {}", bind_endpoint);
+
+    let mut our_pair = zeromq::PairSocket::new();
+    our_pair
+        .connect(&bind_endpoint)
+        .await
+        .expect("Failed to connect");
+    assert_eq!(
+        zmq::SocketEvent::ACCEPTED,
+        get_monitor_event(&their_monitor).0
+    );
+    assert_eq!(
+        zmq::SocketEvent::HANDSHAKE_SUCCEEDED,
+        get_monitor_event(&their_monitor).0
+    );
+
+    const NUM_MSGS: u32 = 64;
+
+    let their_join_handle = run_their_pair(their_pair, NUM_MSGS);
+    run_our_pair(&mut our_pair, NUM_MSGS).await;
+    let _their_pair = their_join_handle
+        .join()
+        .expect("Their pair terminated with an error!");
+    assert_eq!(our_pair.close().await.len(), 0);
+}
+
+#[async_rt::test]
+async fn test_our_pair_their_pair() {
+    let mut our_pair = zeromq::PairSocket::new();
+    let mut monitor = our_pair.monitor();
+    let bind_endpoint = our_pair
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let ctx = zmq::Context::new();
+    let their_pair = ctx.socket(zmq::PAIR).expect("Couldn't make pair socket");
+    their_pair
+        .connect(&bind_endpoint.to_string())
+        .expect("Failed to connect");
+    // Our side must have the peer attached before it is able to send
+    loop {
+        if let zeromq::SocketEvent::Accepted(_, _) = monitor.next().await.unwrap() {
+            break;
+        }
+    }
+
+    const NUM_MSGS: u32 = 64;
+
+    let their_join_handle = run_their_pair(their_pair, NUM_MSGS);
+    run_our_pair(&mut our_pair, NUM_MSGS).await;
+    let _their_pair = their_join_handle
+        .join()
+        .expect("Their pair terminated with an error!");
+    assert_eq!(our_pair.close().await.len(), 0);
+}
+
+#[async_rt::test]
+async fn test_our_pair_refuses_second_peer() {
+    let mut our_pair = zeromq::PairSocket::new();
+    let mut monitor = our_pair.monitor();
+    let bind_endpoint = our_pair
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind")
+        .to_string();
+
+    let ctx = zmq::Context::new();
+    let first = ctx.socket(zmq::PAIR).expect("Couldn't make pair socket");
+    first.connect(&bind_endpoint).expect("Failed to connect");
+    first.send("first", 0).expect("Failed to send");
+    let msg: String = our_pair.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(msg, "first");
+
+    let second = ctx.socket(zmq::PAIR).expect("Couldn't make pair socket");
+    second.connect(&bind_endpoint).expect("Failed to connect");
+    second.send("second", 0).expect("Failed to send");
+    loop {
+        match monitor.next().await.expect("Monitor closed") {
+            zeromq::SocketEvent::AcceptFailed(_) => break,
+            zeromq::SocketEvent::Listening(_)
+            | zeromq::SocketEvent::HandshakeSucceeded(_, _)
+            | zeromq::SocketEvent::Accepted(_, _) => {}
+            e => panic!("Unexpected event {:?}", e),
+        }
+    }
+
+    first.send("first again", 0).expect("Failed to send");
+    let msg: String = our_pair.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(msg, "first again");
+
+    our_pair.send("reply".into()).await.unwrap();
+    // Sending only queues the message, keep the runtime free to write it
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(first.recv_msg(0).unwrap());
+    });
+    assert_eq!(receiver.await.unwrap().as_str(), Some("reply"));
+}
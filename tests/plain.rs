@@ -0,0 +1,92 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{SocketEvent, SocketOptions, ZmqError, ZmqMessage};
+
+use futures::StreamExt;
+use std::convert::TryInto;
+
+fn plain_server() -> SocketOptions {
+    SocketOptions::builder()
+        .plain_server(|username: &str, password: &str| username == "admin" && password == "secret")
+        .build()
+        .unwrap()
+}
+
+fn plain_client(username: &str, password: &str) -> SocketOptions {
+    SocketOptions::builder()
+        .plain_client(username, password)
+        .build()
+        .unwrap()
+}
+
+#[async_rt::test]
+async fn test_plain_authentication() {
+    pretty_env_logger::try_init().ok();
+
+    let mut rep_socket = zeromq::RepSocket::with_options(plain_server());
+    let endpoint = rep_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut req_socket = zeromq::ReqSocket::with_options(plain_client("admin", "secret"));
+    req_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+
+    req_socket.send(ZmqMessage::from("Hello")).await.unwrap();
+    let request: String = rep_socket.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(request, "Hello");
+    rep_socket.send(ZmqMessage::from("World")).await.unwrap();
+    let reply: String = req_socket.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(reply, "World");
+}
+
+#[async_rt::test]
+async fn test_plain_wrong_password() {
+    pretty_env_logger::try_init().ok();
+
+    let mut rep_socket = zeromq::RepSocket::with_options(plain_server());
+    let mut monitor = rep_socket.monitor();
+    let endpoint = rep_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut req_socket = zeromq::ReqSocket::with_options(plain_client("admin", "guess"));
+    match req_socket.connect(&endpoint.to_string()).await {
+        Err(ZmqError::AuthenticationFailed(reason)) => assert_eq!(reason, "400"),
+        other => panic!("Unexpected {:?}", other),
+    }
+    loop {
+        match monitor.next().await.unwrap() {
+            SocketEvent::AcceptFailed(ZmqError::AuthenticationFailed(_)) => break,
+            SocketEvent::AcceptFailed(e) => panic!("Unexpected error {:?}", e),
+            _ => {}
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_plain_server_refuses_null_client() {
+    pretty_env_logger::try_init().ok();
+
+    let mut rep_socket = zeromq::RepSocket::with_options(plain_server());
+    let endpoint = rep_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut req_socket = zeromq::ReqSocket::new();
+    assert!(req_socket.connect(&endpoint.to_string()).await.is_err());
+}
+
+#[test]
+fn test_plain_credentials_too_long() {
+    let username = "a".repeat(256);
+    assert!(SocketOptions::builder()
+        .plain_client(username, "secret")
+        .build()
+        .is_err());
+}
@@ -0,0 +1,122 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{SocketOptions, ZmqMessage};
+
+use futures::channel::oneshot;
+use std::convert::TryInto;
+
+/// libzmq hands PLAIN credentials to a ZAP handler, see
+/// https://rfc.zeromq.org/spec/27/. This one accepts admin/secret
+fn run_their_zap_handler(ctx: &zmq::Context) {
+    let handler = ctx.socket(zmq::REP).expect("Couldn't make rep socket");
+    handler
+        .bind("inproc://zeromq.zap.01")
+        .expect("Failed to bind");
+    std::thread::spawn(move || loop {
+        let request = match handler.recv_multipart(0) {
+            Ok(request) => request,
+            Err(_) => return,
+        };
+        assert_eq!(request[5], b"PLAIN");
+        let accepted = request[6] == b"admin" && request[7] == b"secret";
+        let (status, text) = if accepted {
+            ("200", "OK")
+        } else {
+            ("400", "Invalid username or password")
+        };
+        let reply: Vec<&[u8]> = vec![
+            b"1.0",
+            &request[1],
+            status.as_bytes(),
+            text.as_bytes(),
+            b"admin",
+            b"",
+        ];
+        handler.send_multipart(reply, 0).expect("Failed to reply");
+    });
+}
+
+#[async_rt::test]
+async fn test_their_plain_server_our_plain_client() {
+    pretty_env_logger::try_init().ok();
+
+    let ctx = zmq::Context::new();
+    run_their_zap_handler(&ctx);
+    let their_rep = ctx.socket(zmq::REP).expect("Couldn't make rep socket");
+    their_rep.set_plain_server(true).unwrap();
+    their_rep.bind("tcp://127.0.0.1:0").expect("Failed to bind");
+    let endpoint = their_rep.get_last_endpoint().unwrap().unwrap();
+
+    let options = SocketOptions::builder()
+        .plain_client("admin", "secret")
+        .build()
+        .unwrap();
+    let mut our_req = zeromq::ReqSocket::with_options(options);
+    our_req.connect(&endpoint).await.expect("Failed to connect");
+
+    let their_thread = std::thread::spawn(move || {
+        let request = their_rep.recv_msg(0).expect("Failed to recv");
+        assert_eq!(request.as_str(), Some("Hello"));
+        their_rep.send("World", 0).expect("Failed to send");
+    });
+    our_req.send(ZmqMessage::from("Hello")).await.unwrap();
+    let reply: String = our_req.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(reply, "World");
+    their_thread.join().unwrap();
+}
+
+#[async_rt::test]
+async fn test_their_plain_server_rejects_our_client() {
+    pretty_env_logger::try_init().ok();
+
+    let ctx = zmq::Context::new();
+    run_their_zap_handler(&ctx);
+    let their_rep = ctx.socket(zmq::REP).expect("Couldn't make rep socket");
+    their_rep.set_plain_server(true).unwrap();
+    their_rep.bind("tcp://127.0.0.1:0").expect("Failed to bind");
+    let endpoint = their_rep.get_last_endpoint().unwrap().unwrap();
+
+    let options = SocketOptions::builder()
+        .plain_client("admin", "guess")
+        .build()
+        .unwrap();
+    let mut our_req = zeromq::ReqSocket::with_options(options);
+    match our_req.connect(&endpoint).await {
+        Err(zeromq::ZmqError::AuthenticationFailed(reason)) => assert_eq!(reason, "400"),
+        other => panic!("Unexpected {:?}", other),
+    }
+}
+
+#[async_rt::test]
+async fn test_their_plain_client_our_plain_server() {
+    pretty_env_logger::try_init().ok();
+
+    let options = SocketOptions::builder()
+        .plain_server(|username: &str, password: &str| username == "admin" && password == "secret")
+        .build()
+        .unwrap();
+    let mut our_rep = zeromq::RepSocket::with_options(options);
+    let endpoint = our_rep
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let ctx = zmq::Context::new();
+        let their_req = ctx.socket(zmq::REQ).expect("Couldn't make req socket");
+        their_req.set_plain_username(Some("admin")).unwrap();
+        their_req.set_plain_password(Some("secret")).unwrap();
+        their_req
+            .connect(&endpoint.to_string())
+            .expect("Failed to connect");
+        their_req.send("Hello", 0).expect("Failed to send");
+        let reply = their_req.recv_msg(0).expect("Failed to recv");
+        let _ = sender.send(reply.as_str().unwrap().to_string());
+    });
+
+    let request: String = our_rep.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(request, "Hello");
+    our_rep.send(ZmqMessage::from("World")).await.unwrap();
+    assert_eq!(receiver.await.unwrap(), "World");
+}
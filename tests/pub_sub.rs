@@ -102,3 +102,234 @@ async fn test_pub_sub_sockets() {
     ];
     futures::future::join_all(addrs.into_iter().map(helper)).await;
 }
+
+/// Publishes `payload` every few milliseconds until told to stop
+fn spawn_publisher(
+    mut pub_socket: zeromq::PubSocket,
+    payload: &'static str,
+) -> (oneshot::Sender<()>, async_rt::task::JoinHandle<()>) {
+    let (stop_sender, mut stop) = oneshot::channel::<()>();
+    let handle = async_rt::task::spawn(async move {
+        while let Ok(None) = stop.try_recv() {
+            pub_socket
+                .send(ZmqMessage::from(payload))
+                .await
+                .expect("Failed to send");
+            async_rt::task::sleep(Duration::from_millis(10)).await;
+        }
+        pub_socket.close().await;
+    });
+    (stop_sender, handle)
+}
+
+async fn recv_payload(sub_socket: &mut zeromq::SubSocket) -> String {
+    let message = sub_socket.recv().await.unwrap();
+    String::from_utf8(message.get(0).unwrap().to_vec()).unwrap()
+}
+
+#[async_rt::test]
+async fn test_subscribe_before_connect() {
+    pretty_env_logger::try_init().ok();
+
+    let mut pub_socket = zeromq::PubSocket::new();
+    let endpoint = pub_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut sub_socket = zeromq::SubSocket::new();
+    sub_socket
+        .subscribe("A")
+        .await
+        .expect("Failed to subscribe");
+    sub_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+
+    let (stop, handle) = spawn_publisher(pub_socket, "A message");
+    assert_eq!(recv_payload(&mut sub_socket).await, "A message");
+    stop.send(()).unwrap();
+    handle.await.unwrap();
+}
+
+#[async_rt::test]
+async fn test_subscriptions_beyond_send_hwm() {
+    pretty_env_logger::try_init().ok();
+
+    let mut pub_socket = zeromq::PubSocket::new();
+    let endpoint = pub_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    // Subscriptions are replayed to the publisher regardless of the HWM
+    let options = zeromq::SocketOptions::builder()
+        .send_hwm(4)
+        .build()
+        .unwrap();
+    let mut sub_socket = zeromq::SubSocket::with_options(options);
+    for i in 0..10 {
+        sub_socket
+            .subscribe(&format!("T{}", i))
+            .await
+            .expect("Failed to subscribe");
+    }
+    sub_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+
+    let (stop, handle) = spawn_publisher(pub_socket, "T9 message");
+    assert_eq!(recv_payload(&mut sub_socket).await, "T9 message");
+    stop.send(()).unwrap();
+    handle.await.unwrap();
+}
+
+#[async_rt::test]
+async fn test_publisher_restart() {
+    pretty_env_logger::try_init().ok();
+
+    let mut sub_socket = zeromq::SubSocket::new();
+    let endpoint = sub_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    sub_socket
+        .subscribe("A")
+        .await
+        .expect("Failed to subscribe");
+
+    for payload in &["A first", "A second"] {
+        let mut pub_socket = zeromq::PubSocket::new();
+        pub_socket
+            .connect(&endpoint.to_string())
+            .await
+            .expect("Failed to connect");
+        let (stop, handle) = spawn_publisher(pub_socket, payload);
+        // Skip whatever is left over from the previous publisher
+        while recv_payload(&mut sub_socket).await != *payload {}
+        stop.send(()).unwrap();
+        handle.await.unwrap();
+    }
+}
+
+#[async_rt::test]
+async fn test_topics_shorter_than_subscription() {
+    pretty_env_logger::try_init().ok();
+
+    let mut pub_socket = zeromq::PubSocket::new();
+    let endpoint = pub_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut sub_socket = zeromq::SubSocket::new();
+    sub_socket
+        .subscribe("long topic")
+        .await
+        .expect("Failed to subscribe");
+    sub_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    async_rt::task::sleep(Duration::from_millis(100)).await;
+
+    // Neither of these match, and neither should bring the publisher down
+    pub_socket.send(ZmqMessage::from("long")).await.unwrap();
+    pub_socket.send(ZmqMessage::from("")).await.unwrap();
+    pub_socket
+        .send(ZmqMessage::from("long topic message"))
+        .await
+        .unwrap();
+    assert_eq!(recv_payload(&mut sub_socket).await, "long topic message");
+}
+
+/// Reads one frame, returning its flags and body
+fn read_frame(stream: &mut std::net::TcpStream) -> (u8, Vec<u8>) {
+    use std::io::Read;
+    let mut flags = [0u8; 1];
+    stream.read_exact(&mut flags).unwrap();
+    let size = if flags[0] & 0x02 != 0 {
+        let mut size = [0u8; 8];
+        stream.read_exact(&mut size).unwrap();
+        u64::from_be_bytes(size) as usize
+    } else {
+        let mut size = [0u8; 1];
+        stream.read_exact(&mut size).unwrap();
+        size[0] as usize
+    };
+    let mut body = vec![0u8; size];
+    stream.read_exact(&mut body).unwrap();
+    (flags[0], body)
+}
+
+#[async_rt::test]
+async fn test_subscriptions_to_zmtp_3_0_peers() {
+    use std::io::{Read, Write};
+    pretty_env_logger::try_init().ok();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut greeting = [0u8; 64];
+        greeting[0] = 0xff;
+        greeting[9] = 0x7f;
+        greeting[10] = 3;
+        greeting[11] = 0;
+        greeting[12..16].copy_from_slice(b"NULL");
+        stream.write_all(&greeting).unwrap();
+        stream.read_exact(&mut greeting).unwrap();
+
+        let mut ready = vec![0x04, 25, 5];
+        ready.extend_from_slice(b"READY");
+        ready.push(11);
+        ready.extend_from_slice(b"Socket-Type");
+        ready.extend_from_slice(&3u32.to_be_bytes());
+        ready.extend_from_slice(b"PUB");
+        stream.write_all(&ready).unwrap();
+        // Their READY
+        read_frame(&mut stream);
+        let _ = sender.send(read_frame(&mut stream));
+    });
+
+    let mut sub_socket = zeromq::SubSocket::new();
+    sub_socket
+        .connect(&format!("tcp://{}", address))
+        .await
+        .expect("Failed to connect");
+    sub_socket.subscribe("A").await.unwrap();
+
+    // A message rather than a SUBSCRIBE command, ZMTP 3.0 has none
+    let (flags, body) = receiver.await.unwrap();
+    assert_eq!(flags, 0);
+    assert_eq!(body, b"\x01A");
+}
+
+#[async_rt::test]
+async fn test_xpub_ignores_unknown_cancel() {
+    pretty_env_logger::try_init().ok();
+
+    let mut xpub_socket = zeromq::XPubSocket::new();
+    let endpoint = xpub_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let mut sub_socket = zeromq::SubSocket::new();
+    sub_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+
+    sub_socket.subscribe("A").await.unwrap();
+    let message = xpub_socket.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), b"\x01A");
+
+    // Cancelling a topic the subscriber never subscribed to isn't reported
+    sub_socket.unsubscribe("B").await.unwrap();
+    sub_socket.unsubscribe("A").await.unwrap();
+    let message = xpub_socket.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), b"\x00A");
+}
@@ -3,6 +3,7 @@ use compliance::{get_monitor_event, setup_monitor};
 
 use zeromq::__async_rt as async_rt;
 use zeromq::prelude::*;
+use zeromq::ZmqMessage;
 
 use std::time::Duration;
 
@@ -125,3 +126,32 @@ async fn test_their_pub_our_sub() {
         }
     }
 }
+
+#[async_rt::test]
+async fn test_our_pub_their_sub() {
+    let mut our_pub = zeromq::PubSocket::new();
+    let endpoint = our_pub
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let ctx = zmq::Context::new();
+    let their_sub = ctx.socket(zmq::SUB).expect("Couldn't make sub socket");
+    their_sub
+        .connect(&endpoint.to_string())
+        .expect("Failed to connect");
+    // libzmq subscribes with SUBSCRIBE and CANCEL commands, as of ZMTP 3.1
+    their_sub.set_subscribe(b"A").unwrap();
+    their_sub.set_subscribe(b"B").unwrap();
+    their_sub.set_unsubscribe(b"B").unwrap();
+    async_rt::task::sleep(Duration::from_millis(100)).await;
+
+    for payload in &["B dropped", "A kept"] {
+        our_pub.send(ZmqMessage::from(*payload)).await.unwrap();
+    }
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(their_sub.recv_bytes(0).unwrap());
+    });
+    assert_eq!(receiver.await.unwrap(), b"A kept");
+}
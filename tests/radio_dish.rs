@@ -0,0 +1,193 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{Endpoint, SocketOptions, ZmqMessage};
+
+use futures::channel::oneshot;
+use futures::{select, FutureExt};
+use std::convert::TryInto;
+use std::time::Duration;
+
+fn grouped(group: &str, body: &str) -> ZmqMessage {
+    let mut message = ZmqMessage::from(body);
+    message.set_group(group);
+    message
+}
+
+/// Sends `message` until the dish gets something. Joins take a moment to
+/// reach radios, and UDP peers show up once the bind picked them up
+async fn send_until_received(
+    radio: &mut zeromq::RadioSocket,
+    dish: &mut zeromq::DishSocket,
+    message: ZmqMessage,
+) -> ZmqMessage {
+    loop {
+        radio.send(message.clone()).await.unwrap();
+        select! {
+            received = dish.recv().fuse() => return received.unwrap(),
+            _ = async_rt::task::sleep(Duration::from_millis(50)).fuse() => {},
+        }
+    }
+}
+
+async fn assert_nothing_received(dish: &mut zeromq::DishSocket) {
+    select! {
+        received = dish.recv().fuse() => panic!("Unexpected message {:?}", received),
+        _ = async_rt::task::sleep(Duration::from_millis(200)).fuse() => {},
+    }
+}
+
+async fn check_groups(radio: &mut zeromq::RadioSocket, dish: &mut zeromq::DishSocket) {
+    dish.join("weather").await.unwrap();
+    dish.join("traffic").await.unwrap();
+    let received = send_until_received(radio, dish, grouped("weather", "sunny")).await;
+    assert_eq!(received.group(), Some("weather"));
+    let body: String = received.try_into().unwrap();
+    assert_eq!(body, "sunny");
+
+    radio.send(grouped("sports", "dropped")).await.unwrap();
+    radio.send(grouped("traffic", "jammed")).await.unwrap();
+    let received = dish.recv().await.unwrap();
+    assert_eq!(received.group(), Some("traffic"));
+    let body: String = received.try_into().unwrap();
+    assert_eq!(body, "jammed");
+
+    dish.leave("weather").await.unwrap();
+    async_rt::task::sleep(Duration::from_millis(100)).await;
+    radio.send(grouped("weather", "rainy")).await.unwrap();
+    assert_nothing_received(dish).await;
+}
+
+#[async_rt::test]
+async fn test_radio_dish_tcp() {
+    pretty_env_logger::try_init().ok();
+
+    let mut dish = zeromq::DishSocket::new();
+    let endpoint = dish.bind("tcp://127.0.0.1:0").await.unwrap();
+    let mut radio = zeromq::RadioSocket::new();
+    radio.connect(&endpoint.to_string()).await.unwrap();
+    check_groups(&mut radio, &mut dish).await;
+}
+
+#[async_rt::test]
+async fn test_radio_dish_join_replayed() {
+    pretty_env_logger::try_init().ok();
+
+    // Groups joined before the radio binds reach it once the dish connects
+    let mut dish = zeromq::DishSocket::new();
+    dish.join("weather").await.unwrap();
+    let mut radio = zeromq::RadioSocket::new();
+    let endpoint = radio.bind("tcp://127.0.0.1:0").await.unwrap();
+    dish.connect(&endpoint.to_string()).await.unwrap();
+    let received = send_until_received(&mut radio, &mut dish, grouped("weather", "sunny")).await;
+    assert_eq!(received.group(), Some("weather"));
+}
+
+#[async_rt::test]
+async fn test_radio_dish_joins_beyond_send_hwm() {
+    pretty_env_logger::try_init().ok();
+
+    // Joins are replayed to the radio regardless of the HWM
+    let options = SocketOptions::builder().send_hwm(4).build().unwrap();
+    let mut dish = zeromq::DishSocket::with_options(options);
+    for i in 0..10 {
+        dish.join(&format!("group-{}", i)).await.unwrap();
+    }
+    let mut radio = zeromq::RadioSocket::new();
+    let endpoint = radio.bind("tcp://127.0.0.1:0").await.unwrap();
+    dish.connect(&endpoint.to_string()).await.unwrap();
+    let received = send_until_received(&mut radio, &mut dish, grouped("group-9", "last")).await;
+    assert_eq!(received.group(), Some("group-9"));
+}
+
+#[async_rt::test]
+async fn test_radio_dish_udp() {
+    pretty_env_logger::try_init().ok();
+
+    let mut dish = zeromq::DishSocket::new();
+    let endpoint = dish.bind("udp://127.0.0.1:0").await.unwrap();
+    assert!(matches!(endpoint, Endpoint::Udp(_, port) if port != 0));
+    let mut radio = zeromq::RadioSocket::new();
+    radio.connect(&endpoint.to_string()).await.unwrap();
+    check_groups(&mut radio, &mut dish).await;
+}
+
+#[async_rt::test]
+async fn test_radio_dish_udp_multicast() {
+    pretty_env_logger::try_init().ok();
+
+    let mut dish = zeromq::DishSocket::new();
+    let endpoint = dish.bind("udp://239.255.42.1:0").await.unwrap();
+    assert!(endpoint.to_string().starts_with("udp://239.255.42.1:"));
+    dish.join("weather").await.unwrap();
+    let mut radio = zeromq::RadioSocket::new();
+    radio.connect(&endpoint.to_string()).await.unwrap();
+    let received = send_until_received(&mut radio, &mut dish, grouped("weather", "sunny")).await;
+    assert_eq!(received.group(), Some("weather"));
+}
+
+#[async_rt::test]
+async fn test_udp_datagram_format() {
+    pretty_env_logger::try_init().ok();
+
+    // Datagrams are the group length in one octet, the group and the body
+    let mut dish = zeromq::DishSocket::new();
+    let endpoint = dish.bind("udp://127.0.0.1:0").await.unwrap();
+    dish.join("weather").await.unwrap();
+    let sender = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let target = endpoint.to_string().replace("udp://", "");
+    let received = loop {
+        sender.send_to(b"\x07weathersunny", &target).unwrap();
+        select! {
+            received = dish.recv().fuse() => break received.unwrap(),
+            _ = async_rt::task::sleep(Duration::from_millis(50)).fuse() => {},
+        }
+    };
+    assert_eq!(received.group(), Some("weather"));
+    assert_eq!(received.get(0).unwrap().as_ref(), b"sunny");
+
+    let receiver = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut radio = zeromq::RadioSocket::new();
+    radio
+        .connect(&format!("udp://{}", receiver.local_addr().unwrap()))
+        .await
+        .unwrap();
+    let (datagram, received) = oneshot::channel();
+    std::thread::spawn(move || {
+        let mut buf = [0; 64];
+        let len = receiver.recv(&mut buf).expect("Failed to recv");
+        let _ = datagram.send(buf[..len].to_vec());
+    });
+    radio.send(grouped("weather", "cloudy")).await.unwrap();
+    assert_eq!(received.await.unwrap(), b"\x07weathercloudy");
+}
+
+#[async_rt::test]
+async fn test_radio_dish_errors() {
+    pretty_env_logger::try_init().ok();
+
+    let mut radio = zeromq::RadioSocket::new();
+    assert!(radio.send(ZmqMessage::from("no group")).await.is_err());
+    assert!(radio.send(grouped(&"a".repeat(256), "body")).await.is_err());
+    let mut multipart = grouped("weather", "sunny");
+    multipart.push_back("and warm".into());
+    assert!(radio.send(multipart).await.is_err());
+    assert!(radio
+        .send(grouped("weather", "nobody listens"))
+        .await
+        .is_ok());
+
+    let mut dish = zeromq::DishSocket::new();
+    dish.join("weather").await.unwrap();
+    assert!(dish.join("weather").await.is_err());
+    assert!(dish.leave("traffic").await.is_err());
+    assert!(dish.join(&"a".repeat(256)).await.is_err());
+
+    // UDP carries messages from connected radios to bound dishes only
+    assert!(radio.bind("udp://127.0.0.1:0").await.is_err());
+    let mut publisher = zeromq::PubSocket::new();
+    assert!(publisher.connect("udp://127.0.0.1:5555").await.is_err());
+    assert!(dish.connect("udp://127.0.0.1:5555").await.is_err());
+}
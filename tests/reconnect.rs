@@ -0,0 +1,120 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{SocketEvent, SocketOptions, ZmqMessage};
+
+use futures::{FutureExt, StreamExt};
+use std::convert::TryInto;
+use std::time::Duration;
+
+fn fast_reconnect() -> SocketOptions {
+    SocketOptions::builder()
+        .reconnect_ivl(Duration::from_millis(10))
+        .reconnect_ivl_max(Some(Duration::from_millis(100)))
+        .build()
+        .unwrap()
+}
+
+/// Sends until `receiver` gets a message through. Messages sent while the
+/// connection is down may get lost
+async fn ping(sender: &mut zeromq::PushSocket, receiver: &mut zeromq::PullSocket) {
+    loop {
+        let _ = sender.send(ZmqMessage::from("ping")).await;
+        futures::select! {
+            message = receiver.recv().fuse() => {
+                let payload: String = message.unwrap().try_into().unwrap();
+                assert_eq!(payload, "ping");
+                return;
+            },
+            _ = async_rt::task::sleep(Duration::from_millis(50)).fuse() => {},
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_reconnect_after_peer_restart() {
+    pretty_env_logger::try_init().ok();
+
+    let mut pull_socket = zeromq::PullSocket::new();
+    let endpoint = pull_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut push_socket = zeromq::PushSocket::with_options(fast_reconnect());
+    let mut push_monitor = push_socket.monitor();
+    push_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    ping(&mut push_socket, &mut pull_socket).await;
+
+    // Bring the peer down and up again on the same port
+    drop(pull_socket);
+    let mut pull_socket = zeromq::PullSocket::new();
+    loop {
+        match pull_socket.bind(&endpoint.to_string()).await {
+            Ok(_) => break,
+            Err(_) => async_rt::task::sleep(Duration::from_millis(10)).await,
+        }
+    }
+    ping(&mut push_socket, &mut pull_socket).await;
+
+    let mut connected = 0;
+    let mut delayed = false;
+    let mut retried = false;
+    while connected < 2 {
+        match push_monitor.next().await.unwrap() {
+            SocketEvent::Connected(_, _) => connected += 1,
+            SocketEvent::ConnectDelayed => delayed = true,
+            SocketEvent::ConnectRetried => retried = true,
+            _ => {}
+        }
+    }
+    assert!(delayed);
+    assert!(retried);
+}
+
+#[async_rt::test]
+async fn test_connect_before_bind() {
+    pretty_env_logger::try_init().ok();
+
+    // Grab a free port, then release it
+    let endpoint = {
+        let mut placeholder = zeromq::PullSocket::new();
+        let endpoint = placeholder
+            .bind("tcp://127.0.0.1:0")
+            .await
+            .expect("Failed to bind");
+        placeholder.close().await;
+        endpoint
+    };
+
+    let mut push_socket = zeromq::PushSocket::with_options(fast_reconnect());
+    let mut push_monitor = push_socket.monitor();
+    let connect_endpoint = endpoint.to_string();
+    let connect = async_rt::task::spawn(async move {
+        push_socket
+            .connect(&connect_endpoint)
+            .await
+            .expect("Failed to connect");
+        push_socket
+    });
+
+    // Connection attempts keep failing until somebody binds
+    assert!(matches!(
+        push_monitor.next().await.unwrap(),
+        SocketEvent::ConnectDelayed
+    ));
+    assert!(matches!(
+        push_monitor.next().await.unwrap(),
+        SocketEvent::ConnectRetried
+    ));
+
+    let mut pull_socket = zeromq::PullSocket::new();
+    pull_socket
+        .bind(&endpoint.to_string())
+        .await
+        .expect("Failed to bind");
+    let mut push_socket = connect.await.unwrap();
+    ping(&mut push_socket, &mut pull_socket).await;
+}
@@ -1,8 +1,9 @@
 use zeromq::__async_rt as async_rt;
 use zeromq::prelude::*;
-use zeromq::{RepSocket, ZmqMessage};
+use zeromq::{RepSocket, SocketOptions, ZmqError, ZmqMessage};
 
-use futures::StreamExt;
+use bytes::Bytes;
+use futures::{FutureExt, StreamExt};
 use std::error::Error;
 use std::str;
 use std::time::Duration;
@@ -56,7 +57,8 @@ async fn test_req_rep_sockets() -> Result<(), Box<dyn Error>> {
     }
     req_socket.close().await;
     let events: Vec<_> = monitor.collect().await;
-    assert_eq!(2, events.len(), "{:?}", &events);
+    // Listening, HandshakeSucceeded and Accepted
+    assert_eq!(3, events.len(), "{:?}", &events);
     Ok(())
 }
 
@@ -101,3 +103,114 @@ async fn test_many_req_rep_sockets() -> Result<(), Box<dyn Error>> {
     }
     Ok(())
 }
+
+#[async_rt::test]
+async fn test_req_strict_by_default() -> Result<(), Box<dyn Error>> {
+    pretty_env_logger::try_init().ok();
+
+    let mut router_socket = zeromq::RouterSocket::new();
+    let endpoint = router_socket.bind("tcp://127.0.0.1:0").await?;
+    let mut req_socket = zeromq::ReqSocket::new();
+    req_socket.connect(&endpoint.to_string()).await?;
+
+    req_socket.send(ZmqMessage::from("first")).await?;
+    match req_socket.send(ZmqMessage::from("second")).await {
+        Err(ZmqError::ReturnToSender { .. }) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+    Ok(())
+}
+
+#[async_rt::test]
+async fn test_req_lazy_pirate() -> Result<(), Box<dyn Error>> {
+    pretty_env_logger::try_init().ok();
+
+    let mut router_socket = zeromq::RouterSocket::new();
+    let endpoint = router_socket.bind("tcp://127.0.0.1:0").await?;
+
+    let options = SocketOptions::builder()
+        .req_relaxed(true)
+        .req_correlate(true)
+        .build()?;
+    let mut req_socket = zeromq::ReqSocket::with_options(options);
+    req_socket.connect(&endpoint.to_string()).await?;
+
+    req_socket.send(ZmqMessage::from("first")).await?;
+    let first = router_socket.recv().await?;
+    // [peer, request id, delimiter, body]
+    assert_eq!(first.len(), 4);
+    assert_eq!(first.get(3).unwrap().as_ref(), b"first");
+
+    // The server is too slow, give up on the reply and try again
+    futures::select! {
+        _ = req_socket.recv().fuse() => panic!("No reply was sent yet"),
+        _ = async_rt::task::sleep(Duration::from_millis(100)).fuse() => {},
+    }
+    req_socket.send(ZmqMessage::from("second")).await?;
+    let second = router_socket.recv().await?;
+    assert_ne!(first.get(1), second.get(1));
+
+    // The late reply to the first request is discarded
+    for (request, body) in &[(first, "late"), (second, "on time")] {
+        let mut reply = ZmqMessage::from(request.get(0).unwrap().clone());
+        reply.push_back(request.get(1).unwrap().clone());
+        reply.push_back(Bytes::new());
+        reply.push_back(Bytes::from(*body));
+        router_socket.send(reply).await?;
+    }
+    let reply = req_socket.recv().await?;
+    assert_eq!(reply.get(0).unwrap().as_ref(), b"on time");
+    Ok(())
+}
+
+#[async_rt::test]
+async fn test_req_discards_malformed_replies() -> Result<(), Box<dyn Error>> {
+    pretty_env_logger::try_init().ok();
+
+    let mut router_socket = zeromq::RouterSocket::new();
+    let endpoint = router_socket.bind("tcp://127.0.0.1:0").await?;
+
+    let options = SocketOptions::builder().req_correlate(true).build()?;
+    let mut req_socket = zeromq::ReqSocket::with_options(options);
+    req_socket.connect(&endpoint.to_string()).await?;
+
+    req_socket.send(ZmqMessage::from("request")).await?;
+    let request = router_socket.recv().await?;
+
+    // Replies missing the delimiter or the body are dropped, the request
+    // stays in progress until a well formed one comes
+    let replies: [&[&[u8]]; 4] = [&[], &[b""], &[b"no delimiter"], &[b"", b"reply"]];
+    for frames in replies.iter() {
+        let mut reply = ZmqMessage::from(request.get(0).unwrap().clone());
+        reply.push_back(request.get(1).unwrap().clone());
+        for frame in frames.iter() {
+            reply.push_back(Bytes::copy_from_slice(frame));
+        }
+        router_socket.send(reply).await?;
+    }
+    let reply = req_socket.recv().await?;
+    assert_eq!(reply.len(), 1);
+    assert_eq!(reply.get(0).unwrap().as_ref(), b"reply");
+    Ok(())
+}
+
+#[async_rt::test]
+async fn test_rep_ignores_router_probes() -> Result<(), Box<dyn Error>> {
+    pretty_env_logger::try_init().ok();
+
+    let mut rep_socket = zeromq::RepSocket::new();
+    let endpoint = rep_socket.bind("tcp://127.0.0.1:0").await?;
+
+    // The empty message probing for a ROUTER isn't a request
+    let options = SocketOptions::builder().probe_router(true).build()?;
+    let mut req_socket = zeromq::ReqSocket::with_options(options);
+    req_socket.connect(&endpoint.to_string()).await?;
+
+    req_socket.send(ZmqMessage::from("Hello")).await?;
+    let request = rep_socket.recv().await?;
+    assert_eq!(request.get(0).unwrap().as_ref(), b"Hello");
+    rep_socket.send(ZmqMessage::from("World")).await?;
+    let reply = req_socket.recv().await?;
+    assert_eq!(reply.get(0).unwrap().as_ref(), b"World");
+    Ok(())
+}
@@ -4,7 +4,7 @@ use compliance::{get_monitor_event, setup_monitor};
 use std::convert::TryInto;
 use zeromq::__async_rt as async_rt;
 use zeromq::prelude::*;
-use zeromq::ZmqMessage;
+use zeromq::{SocketOptions, ZmqMessage};
 
 /// Returns (socket, bound_endpoint, monitor)
 fn setup_their_rep(bind_endpoint: &str) -> (zmq::Socket, String, zmq::Socket) {
@@ -19,8 +19,8 @@ fn setup_their_rep(bind_endpoint: &str) -> (zmq::Socket, String, zmq::Socket) {
     (their_rep, resolved_bind, their_monitor)
 }
 
-async fn setup_our_req(bind_endpoint: &str) -> zeromq::ReqSocket {
-    let mut our_req = zeromq::ReqSocket::new();
+async fn setup_our_req(bind_endpoint: &str, options: SocketOptions) -> zeromq::ReqSocket {
+    let mut our_req = zeromq::ReqSocket::with_options(options);
     our_req
         .connect(bind_endpoint)
         .await
@@ -61,7 +61,7 @@ async fn test_their_rep_our_req() {
     let (their_rep, bind_endpoint, their_monitor) = setup_their_rep("tcp://127.0.0.1:0");
     println!("Their rep was bound to {}", bind_endpoint);
 
-    let mut our_req = setup_our_req(&bind_endpoint).await;
+    let mut our_req = setup_our_req(&bind_endpoint, SocketOptions::default()).await;
     assert_eq!(
         zmq::SocketEvent::ACCEPTED,
         get_monitor_event(&their_monitor).0
@@ -83,3 +83,22 @@ async fn test_their_rep_our_req() {
     // TODO: check that socket disconnected via monitor when we implement that
     // functionality
 }
+
+#[async_rt::test]
+async fn test_their_rep_our_correlating_req() {
+    let (their_rep, bind_endpoint, _their_monitor) = setup_their_rep("tcp://127.0.0.1:0");
+    let options = SocketOptions::builder()
+        .req_relaxed(true)
+        .req_correlate(true)
+        .build()
+        .unwrap();
+    let mut our_req = setup_our_req(&bind_endpoint, options).await;
+
+    // Their REP sends the request id back along with the rest of the envelope
+    const NUM_MSGS: u32 = 16;
+    let their_join_handle = run_their_rep(their_rep, NUM_MSGS);
+    run_our_req(&mut our_req, NUM_MSGS).await;
+    their_join_handle
+        .join()
+        .expect("Their rep terminated with an error!");
+}
@@ -0,0 +1,250 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::util::PeerIdentity;
+use zeromq::{SocketEvent, SocketOptions, ZmqError, ZmqMessage};
+
+use futures::channel::mpsc;
+use futures::{FutureExt, StreamExt};
+use std::convert::TryInto;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+async fn bind_router(
+    options: SocketOptions,
+) -> (zeromq::RouterSocket, mpsc::Receiver<SocketEvent>, String) {
+    let mut router = zeromq::RouterSocket::with_options(options);
+    let monitor = router.monitor();
+    let endpoint = router
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    (router, monitor, endpoint.to_string())
+}
+
+async fn connect_worker(endpoint: &str) -> zeromq::DealerSocket {
+    let identity: PeerIdentity = b"worker".to_vec().try_into().unwrap();
+    let options = SocketOptions::builder()
+        .peer_identity(identity)
+        .build()
+        .unwrap();
+    let mut dealer = zeromq::DealerSocket::with_options(options);
+    dealer.connect(endpoint).await.expect("Failed to connect");
+    dealer
+}
+
+/// Connects as a ZMTP 3.0 DEALER announcing `identity` in its READY command
+fn raw_dealer(endpoint: &str, identity: &[u8]) -> TcpStream {
+    let mut stream = TcpStream::connect(endpoint.trim_start_matches("tcp://")).unwrap();
+    let mut greeting = [0u8; 64];
+    greeting[0] = 0xff;
+    greeting[9] = 0x7f;
+    greeting[10] = 3;
+    greeting[12..16].copy_from_slice(b"NULL");
+    stream.write_all(&greeting).unwrap();
+    stream.read_exact(&mut greeting).unwrap();
+
+    let mut body = b"\x05READY".to_vec();
+    for (name, value) in &[
+        (&b"Socket-Type"[..], &b"DEALER"[..]),
+        (b"Identity", identity),
+    ] {
+        body.push(name.len() as u8);
+        body.extend_from_slice(name);
+        body.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        body.extend_from_slice(value);
+    }
+    let mut command = vec![0x06];
+    command.extend_from_slice(&(body.len() as u64).to_be_bytes());
+    command.extend_from_slice(&body);
+    stream.write_all(&command).unwrap();
+    stream
+}
+
+fn addressed(identity: &'static str, body: &'static str) -> ZmqMessage {
+    let mut message = ZmqMessage::from(identity);
+    message.push_back(body.into());
+    message
+}
+
+#[async_rt::test]
+async fn test_unroutable_messages() {
+    pretty_env_logger::try_init().ok();
+
+    let mut router = zeromq::RouterSocket::new();
+    router
+        .send(addressed("nobody", "Hello"))
+        .await
+        .expect("Unroutable messages are dropped by default");
+    match router.send(ZmqMessage::from("Hello")).await {
+        Err(ZmqError::ReturnToSender { .. }) => {}
+        other => panic!("Unexpected result {:?}", other),
+    }
+
+    let options = SocketOptions::builder()
+        .router_mandatory(true)
+        .build()
+        .unwrap();
+    let mut router = zeromq::RouterSocket::with_options(options);
+    match router.send(addressed("nobody", "Hello")).await {
+        Err(ZmqError::HostUnreachable(message)) => {
+            assert_eq!(message.get(0).unwrap().as_ref(), b"nobody");
+            assert_eq!(message.get(1).unwrap().as_ref(), b"Hello");
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[async_rt::test]
+async fn test_identity_collision() {
+    pretty_env_logger::try_init().ok();
+
+    let (mut router, mut monitor, endpoint) = bind_router(SocketOptions::default()).await;
+    let mut first = connect_worker(&endpoint).await;
+    let _second = connect_worker(&endpoint).await;
+    loop {
+        match monitor.next().await.unwrap() {
+            SocketEvent::AcceptFailed(ZmqError::Socket(_)) => break,
+            SocketEvent::AcceptFailed(e) => panic!("Unexpected error {:?}", e),
+            _ => {}
+        }
+    }
+
+    // The identity stays with the first peer
+    router.send(addressed("worker", "Hello")).await.unwrap();
+    let message = first.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), b"Hello");
+}
+
+#[async_rt::test]
+async fn test_router_handover() {
+    pretty_env_logger::try_init().ok();
+
+    let options = SocketOptions::builder()
+        .router_handover(true)
+        .build()
+        .unwrap();
+    let (mut router, _monitor, endpoint) = bind_router(options).await;
+    let mut first = connect_worker(&endpoint).await;
+    first.send(ZmqMessage::from("first")).await.unwrap();
+    assert_eq!(
+        router.recv().await.unwrap().get(1).unwrap().as_ref(),
+        b"first"
+    );
+
+    let mut second = connect_worker(&endpoint).await;
+    second.send(ZmqMessage::from("second")).await.unwrap();
+    let message = router.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), b"worker");
+    assert_eq!(message.get(1).unwrap().as_ref(), b"second");
+
+    // The identity now belongs to the second peer
+    router.send(addressed("worker", "Hello")).await.unwrap();
+    let message = second.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), b"Hello");
+}
+
+#[async_rt::test]
+async fn test_router_handover_outlives_old_connection() {
+    pretty_env_logger::try_init().ok();
+
+    // Slow to reconnect, so that the old peer doesn't take the identity back
+    let options = SocketOptions::builder()
+        .router_handover(true)
+        .reconnect_ivl(Duration::from_secs(10))
+        .build()
+        .unwrap();
+    let (mut router, _monitor, endpoint) = bind_router(options).await;
+    let identity: PeerIdentity = b"worker".to_vec().try_into().unwrap();
+    let options = SocketOptions::builder()
+        .peer_identity(identity)
+        .build()
+        .unwrap();
+    let mut first = zeromq::DealerSocket::with_options(options);
+    let mut first_monitor = first.monitor();
+    let first_endpoint = first
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    router
+        .connect(&first_endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    while !matches!(
+        first_monitor.next().await.unwrap(),
+        SocketEvent::Accepted(..)
+    ) {}
+    first.send(ZmqMessage::from("first")).await.unwrap();
+    assert_eq!(
+        router.recv().await.unwrap().get(1).unwrap().as_ref(),
+        b"first"
+    );
+
+    // Taking over closes the connection to the first peer, which the router
+    // notices while the second one is already in place
+    let mut second = connect_worker(&endpoint).await;
+    let served = async {
+        second.send(ZmqMessage::from("second")).await.unwrap();
+        assert_eq!(
+            router.recv().await.unwrap().get(1).unwrap().as_ref(),
+            b"second"
+        );
+        async_rt::task::sleep(Duration::from_millis(100)).await;
+        router.send(addressed("worker", "Hello")).await.unwrap();
+        let message = second.recv().await.unwrap();
+        assert_eq!(message.get(0).unwrap().as_ref(), b"Hello");
+    };
+    futures::select! {
+        _ = served.fuse() => {},
+        _ = async_rt::task::sleep(Duration::from_secs(2)).fuse() => {
+            panic!("The new peer was dropped along with the old connection");
+        },
+    }
+}
+
+#[async_rt::test]
+async fn test_probe_router() {
+    pretty_env_logger::try_init().ok();
+
+    let (mut router, _monitor, endpoint) = bind_router(SocketOptions::default()).await;
+    let options = SocketOptions::builder().probe_router(true).build().unwrap();
+    let mut dealer = zeromq::DealerSocket::with_options(options);
+    dealer.connect(&endpoint).await.expect("Failed to connect");
+
+    let probe = router.recv().await.unwrap();
+    assert_eq!(probe.len(), 2);
+    assert!(probe.get(1).unwrap().is_empty());
+}
+
+#[async_rt::test]
+async fn test_invalid_peer_identities() {
+    pretty_env_logger::try_init().ok();
+
+    let (mut router, mut monitor, endpoint) = bind_router(SocketOptions::default()).await;
+    for identity in &[vec![b'x'; 256], b"\0reserved".to_vec()] {
+        let (done, wait) = std::sync::mpsc::channel::<()>();
+        let (address, identity) = (endpoint.clone(), identity.clone());
+        std::thread::spawn(move || {
+            let _stream = raw_dealer(&address, &identity);
+            let _ = wait.recv();
+        });
+        loop {
+            match monitor.next().await.unwrap() {
+                SocketEvent::AcceptFailed(ZmqError::Other(reason)) => {
+                    assert!(reason.starts_with("ZMQ_IDENTITY"), "{}", reason);
+                    break;
+                }
+                SocketEvent::AcceptFailed(e) => panic!("Unexpected error {:?}", e),
+                SocketEvent::HandshakeSucceeded(..) => panic!("Invalid identity accepted"),
+                _ => {}
+            }
+        }
+        drop(done);
+    }
+
+    // The router is still up for well behaved peers
+    let mut worker = connect_worker(&endpoint).await;
+    worker.send(ZmqMessage::from("Hello")).await.unwrap();
+    let message = router.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), b"worker");
+}
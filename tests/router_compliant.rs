@@ -0,0 +1,156 @@
+mod compliance;
+use compliance::{get_monitor_event, setup_monitor};
+
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::util::PeerIdentity;
+use zeromq::{SocketOptions, ZmqMessage};
+
+use std::convert::TryInto;
+
+#[async_rt::test]
+async fn test_their_probe_our_router() {
+    pretty_env_logger::try_init().ok();
+
+    let mut our_router = zeromq::RouterSocket::new();
+    let endpoint = our_router
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let ctx = zmq::Context::new();
+    let their_dealer = ctx
+        .socket(zmq::DEALER)
+        .expect("Couldn't make dealer socket");
+    their_dealer.set_identity(b"theirs").unwrap();
+    their_dealer.set_probe_router(true).unwrap();
+    their_dealer
+        .connect(&endpoint.to_string())
+        .expect("Failed to connect");
+
+    let probe = our_router.recv().await.unwrap();
+    assert_eq!(probe.get(0).unwrap().as_ref(), b"theirs");
+    assert_eq!(probe.len(), 2);
+    assert!(probe.get(1).unwrap().is_empty());
+}
+
+#[async_rt::test]
+async fn test_our_probe_their_router() {
+    pretty_env_logger::try_init().ok();
+
+    let ctx = zmq::Context::new();
+    let their_router = ctx
+        .socket(zmq::ROUTER)
+        .expect("Couldn't make router socket");
+    their_router.set_router_mandatory(true).unwrap();
+    their_router
+        .bind("tcp://127.0.0.1:*")
+        .expect("Failed to bind");
+    let their_monitor = setup_monitor(&ctx, &their_router, "inproc://their-monitor");
+    let endpoint = their_router.get_last_endpoint().unwrap().unwrap();
+
+    let options = SocketOptions::builder().probe_router(true).build().unwrap();
+    let mut our_dealer = zeromq::DealerSocket::with_options(options);
+    our_dealer
+        .connect(&endpoint)
+        .await
+        .expect("Failed to connect");
+    assert_eq!(
+        zmq::SocketEvent::ACCEPTED,
+        get_monitor_event(&their_monitor).0
+    );
+
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        let probe = their_router.recv_multipart(0).unwrap();
+        // Thanks to the probe, they can reach us without hearing from us
+        // first
+        their_router
+            .send_multipart(vec![probe[0].clone(), b"Hello".to_vec()], 0)
+            .unwrap();
+        let _ = sender.send(probe);
+    });
+    let probe = receiver.await.unwrap();
+    assert_eq!(probe.len(), 2);
+    assert!(probe[1].is_empty());
+    let message = our_dealer.recv().await.unwrap();
+    assert_eq!(message.get(0).unwrap().as_ref(), b"Hello");
+}
+
+#[async_rt::test]
+async fn test_our_req_identity_their_router() {
+    pretty_env_logger::try_init().ok();
+
+    let ctx = zmq::Context::new();
+    let their_router = ctx
+        .socket(zmq::ROUTER)
+        .expect("Couldn't make router socket");
+    their_router
+        .bind("tcp://127.0.0.1:*")
+        .expect("Failed to bind");
+    let endpoint = their_router.get_last_endpoint().unwrap().unwrap();
+
+    let identity: PeerIdentity = b"our-req".to_vec().try_into().unwrap();
+    let options = SocketOptions::builder()
+        .peer_identity(identity)
+        .build()
+        .unwrap();
+    let mut our_req = zeromq::ReqSocket::with_options(options);
+    our_req.connect(&endpoint).await.expect("Failed to connect");
+    our_req.send(ZmqMessage::from("Hello")).await.unwrap();
+
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        let request = their_router.recv_multipart(0).unwrap();
+        their_router
+            .send_multipart(vec![b"our-req".to_vec(), Vec::new(), b"World".to_vec()], 0)
+            .unwrap();
+        let _ = sender.send(request);
+    });
+    let request = receiver.await.unwrap();
+    assert_eq!(
+        request,
+        vec![b"our-req".to_vec(), Vec::new(), b"Hello".to_vec()]
+    );
+    let reply = our_req.recv().await.unwrap();
+    assert_eq!(reply.get(0).unwrap().as_ref(), b"World");
+}
+
+#[async_rt::test]
+async fn test_our_metadata_their_router() {
+    pretty_env_logger::try_init().ok();
+
+    let ctx = zmq::Context::new();
+    let their_router = ctx
+        .socket(zmq::ROUTER)
+        .expect("Couldn't make router socket");
+    their_router
+        .bind("tcp://127.0.0.1:*")
+        .expect("Failed to bind");
+    let endpoint = their_router.get_last_endpoint().unwrap().unwrap();
+
+    let options = SocketOptions::builder()
+        .metadata("X-Service", "worker")
+        .build()
+        .unwrap();
+    let mut our_dealer = zeromq::DealerSocket::with_options(options);
+    our_dealer
+        .connect(&endpoint)
+        .await
+        .expect("Failed to connect");
+    our_dealer.send(ZmqMessage::from("Hello")).await.unwrap();
+
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    std::thread::spawn(move || {
+        let _identity = their_router.recv_msg(0).unwrap();
+        let mut message = their_router.recv_msg(0).unwrap();
+        let properties = (
+            message.gets("X-Service").map(str::to_owned),
+            message.gets("Socket-Type").map(str::to_owned),
+        );
+        let _ = sender.send(properties);
+    });
+    let (service, socket_type) = receiver.await.unwrap();
+    assert_eq!(service.as_deref(), Some("worker"));
+    assert_eq!(socket_type.as_deref(), Some("DEALER"));
+}
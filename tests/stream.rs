@@ -0,0 +1,123 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{StreamSocket, ZmqMessage};
+
+use bytes::Bytes;
+use futures::channel::oneshot;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+fn reply(routing_id: &Bytes, data: &'static str) -> ZmqMessage {
+    let mut message = ZmqMessage::from(data);
+    message.push_front(routing_id.clone());
+    message
+}
+
+#[async_rt::test]
+async fn test_stream_with_raw_tcp_client() {
+    pretty_env_logger::try_init().ok();
+
+    let mut our_stream = StreamSocket::new();
+    let endpoint = our_stream
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let addr = endpoint.to_string().replace("tcp://", "");
+
+    let (done_sender, done) = oneshot::channel();
+    std::thread::spawn(move || {
+        let mut client = TcpStream::connect(addr).expect("Failed to connect");
+        client.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+        let mut response = Vec::new();
+        // Reads until the connection gets closed on our side
+        client.read_to_end(&mut response).unwrap();
+        let _ = done_sender.send(response);
+    });
+
+    // Connect notification
+    let connected = our_stream.recv().await.unwrap();
+    assert_eq!(connected.len(), 2);
+    assert!(connected.get(1).unwrap().is_empty());
+    let routing_id = connected.get(0).unwrap().clone();
+
+    let mut request = Vec::new();
+    while !request.ends_with(b"\r\n\r\n") {
+        let message = our_stream.recv().await.unwrap();
+        assert_eq!(message.len(), 2);
+        assert_eq!(message.get(0).unwrap(), &routing_id);
+        request.extend_from_slice(message.get(1).unwrap());
+    }
+    assert_eq!(request, b"GET / HTTP/1.0\r\n\r\n");
+
+    our_stream
+        .send(reply(&routing_id, "HTTP/1.0 200 OK\r\n\r\n"))
+        .await
+        .unwrap();
+    our_stream.send(reply(&routing_id, "")).await.unwrap();
+
+    let response = done.await.unwrap();
+    assert_eq!(response, b"HTTP/1.0 200 OK\r\n\r\n");
+
+    // The connection is gone, nothing can be sent to it anymore
+    assert!(our_stream.send(reply(&routing_id, "late")).await.is_err());
+}
+
+#[async_rt::test]
+async fn test_stream_reports_disconnect() {
+    let mut our_stream = StreamSocket::new();
+    let endpoint = our_stream
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let addr = endpoint.to_string().replace("tcp://", "");
+
+    std::thread::spawn(move || {
+        let mut client = TcpStream::connect(addr).expect("Failed to connect");
+        client.write_all(b"bye").unwrap();
+    });
+
+    let connected = our_stream.recv().await.unwrap();
+    let routing_id = connected.get(0).unwrap().clone();
+    assert!(connected.get(1).unwrap().is_empty());
+
+    let data = our_stream.recv().await.unwrap();
+    assert_eq!(data.get(0).unwrap(), &routing_id);
+    assert_eq!(data.get(1).unwrap().as_ref(), b"bye");
+
+    let disconnected = our_stream.recv().await.unwrap();
+    assert_eq!(disconnected.get(0).unwrap(), &routing_id);
+    assert!(disconnected.get(1).unwrap().is_empty());
+}
+
+#[async_rt::test]
+async fn test_stream_connects_to_raw_tcp_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        let (mut conn, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 4];
+        conn.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"ping");
+        conn.write_all(b"pong").unwrap();
+    });
+
+    let mut our_stream = StreamSocket::new();
+    our_stream
+        .connect(&format!("tcp://{}", addr))
+        .await
+        .expect("Failed to connect");
+
+    let connected = our_stream.recv().await.unwrap();
+    assert!(connected.get(1).unwrap().is_empty());
+    let routing_id = connected.get(0).unwrap().clone();
+
+    our_stream.send(reply(&routing_id, "ping")).await.unwrap();
+    let mut response = Vec::new();
+    while response.len() < 4 {
+        let message = our_stream.recv().await.unwrap();
+        assert_eq!(message.get(0).unwrap(), &routing_id);
+        response.extend_from_slice(message.get(1).unwrap());
+    }
+    assert_eq!(response, b"pong");
+}
@@ -0,0 +1,151 @@
+#![cfg(feature = "tls-transport")]
+
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{Endpoint, SocketEvent, SocketOptions, ZmqMessage};
+
+use futures::StreamExt;
+use std::convert::TryInto;
+
+type Ca = rcgen::CertifiedIssuer<'static, rcgen::KeyPair>;
+
+fn ca() -> Ca {
+    let mut params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+    params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    rcgen::CertifiedIssuer::self_signed(params, rcgen::KeyPair::generate().unwrap()).unwrap()
+}
+
+/// Certificate valid for 127.0.0.1, whose subject is `CN=<name>`, along with
+/// its private key
+fn issue(ca: &Ca, name: &str) -> (String, String) {
+    let key = rcgen::KeyPair::generate().unwrap();
+    let mut params = rcgen::CertificateParams::new(vec!["127.0.0.1".to_string()]).unwrap();
+    params
+        .distinguished_name
+        .push(rcgen::DnType::CommonName, name);
+    let certificate = params.signed_by(&key, ca).unwrap();
+    (certificate.pem(), key.serialize_pem())
+}
+
+#[async_rt::test]
+async fn test_tls_req_rep() {
+    pretty_env_logger::try_init().ok();
+
+    let ca = ca();
+    let (server_cert, server_key) = issue(&ca, "server");
+    let (client_cert, client_key) = issue(&ca, "client");
+
+    // Servers with a CA require clients to present a certificate
+    let server_options = SocketOptions::builder()
+        .tls_certificate(server_cert, server_key)
+        .tls_ca(ca.pem())
+        .build()
+        .unwrap();
+    let mut rep = zeromq::RepSocket::with_options(server_options);
+    let mut monitor = rep.monitor();
+    let endpoint = rep
+        .bind("tls+tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    assert!(matches!(endpoint, Endpoint::TlsTcp(_, port) if port != 0));
+
+    let client_options = SocketOptions::builder()
+        .tls_certificate(client_cert, client_key)
+        .tls_ca(ca.pem())
+        .build()
+        .unwrap();
+    let mut req = zeromq::ReqSocket::with_options(client_options);
+    req.connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    let metadata = loop {
+        if let SocketEvent::HandshakeSucceeded(_, metadata) = monitor.next().await.unwrap() {
+            break metadata;
+        }
+    };
+    assert_eq!(metadata.peer_certificate_subject(), Some("CN=client"));
+    assert_eq!(metadata.peer_address(), Some("127.0.0.1"));
+
+    for i in 0..10 {
+        req.send(format!("Hello {}", i).into()).await.unwrap();
+        let request: String = rep.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(request, format!("Hello {}", i));
+        rep.send(ZmqMessage::from("World")).await.unwrap();
+        let reply = req.recv().await.unwrap();
+        let subject = reply.metadata().unwrap().peer_certificate_subject();
+        assert_eq!(subject, Some("CN=server"));
+        let reply: String = reply.try_into().unwrap();
+        assert_eq!(reply, "World");
+    }
+}
+
+#[async_rt::test]
+async fn test_tls_untrusted_server() {
+    pretty_env_logger::try_init().ok();
+
+    let (server_cert, server_key) = issue(&ca(), "server");
+    let server_options = SocketOptions::builder()
+        .tls_certificate(server_cert, server_key)
+        .build()
+        .unwrap();
+    let mut rep = zeromq::RepSocket::with_options(server_options);
+    let endpoint = rep
+        .bind("tls+tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let client_options = SocketOptions::builder().tls_ca(ca().pem()).build().unwrap();
+    let mut req = zeromq::ReqSocket::with_options(client_options);
+    assert!(req.connect(&endpoint.to_string()).await.is_err());
+}
+
+#[async_rt::test]
+async fn test_tls_client_certificate_required() {
+    pretty_env_logger::try_init().ok();
+
+    let ca = ca();
+    let (server_cert, server_key) = issue(&ca, "server");
+    let server_options = SocketOptions::builder()
+        .tls_certificate(server_cert, server_key)
+        .tls_ca(ca.pem())
+        .build()
+        .unwrap();
+    let mut rep = zeromq::RepSocket::with_options(server_options);
+    let mut monitor = rep.monitor();
+    let endpoint = rep
+        .bind("tls+tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let client_options = SocketOptions::builder().tls_ca(ca.pem()).build().unwrap();
+    let mut req = zeromq::ReqSocket::with_options(client_options);
+    assert!(req.connect(&endpoint.to_string()).await.is_err());
+    loop {
+        match monitor.next().await.unwrap() {
+            SocketEvent::AcceptFailed(_) => break,
+            SocketEvent::HandshakeSucceeded(..) => panic!("Clients need a certificate"),
+            _ => {}
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_tls_configuration() {
+    pretty_env_logger::try_init().ok();
+
+    // Binding needs a certificate, connecting needs a CA
+    let mut rep = zeromq::RepSocket::new();
+    assert!(rep.bind("tls+tcp://127.0.0.1:0").await.is_err());
+    assert!(SocketOptions::builder()
+        .tls_ca("not a certificate")
+        .build()
+        .is_err());
+
+    let ca = ca();
+    let (_, other_key) = issue(&ca, "other");
+    let (server_cert, _) = issue(&ca, "server");
+    assert!(SocketOptions::builder()
+        .tls_certificate(server_cert, other_key)
+        .build()
+        .is_err());
+}
@@ -0,0 +1,198 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{Endpoint, SocketEvent, SocketOptions, ZmqMessage};
+
+use futures::{FutureExt, StreamExt};
+use std::convert::TryInto;
+use std::time::Duration;
+
+async fn req_rep_round_trips(req: &mut zeromq::ReqSocket, rep: &mut zeromq::RepSocket) {
+    for i in 0..10 {
+        req.send(format!("Hello {}", i).into()).await.unwrap();
+        let request: String = rep.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(request, format!("Hello {}", i));
+        // Long enough to need an 8 byte length in ZMTP
+        rep.send(ZmqMessage::from("World".repeat(100)))
+            .await
+            .unwrap();
+        let reply: String = req.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(reply, "World".repeat(100));
+    }
+}
+
+#[async_rt::test]
+async fn test_ws_req_rep() {
+    pretty_env_logger::try_init().ok();
+
+    let mut rep = zeromq::RepSocket::new();
+    let mut monitor = rep.monitor();
+    let endpoint = rep
+        .bind("ws://127.0.0.1:0/zmq")
+        .await
+        .expect("Failed to bind");
+    match &endpoint {
+        Endpoint::Ws(_, port, path) => {
+            assert_ne!(*port, 0);
+            assert_eq!(path, "/zmq");
+        }
+        other => panic!("Unexpected endpoint {}", other),
+    }
+
+    let mut req = zeromq::ReqSocket::new();
+    req.connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    let metadata = loop {
+        if let SocketEvent::HandshakeSucceeded(_, metadata) = monitor.next().await.unwrap() {
+            break metadata;
+        }
+    };
+    assert_eq!(metadata.get_str("Socket-Type"), Some("REQ"));
+    assert_eq!(metadata.peer_address(), Some("127.0.0.1"));
+
+    req_rep_round_trips(&mut req, &mut rep).await;
+}
+
+#[async_rt::test]
+async fn test_ws_pub_sub() {
+    pretty_env_logger::try_init().ok();
+
+    let mut publisher = zeromq::PubSocket::new();
+    let endpoint = publisher
+        .bind("ws://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut subscriber = zeromq::SubSocket::new();
+    subscriber.subscribe("A").await.unwrap();
+    subscriber
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    // Give the subscription time to reach the publisher
+    async_rt::task::sleep(Duration::from_millis(100)).await;
+
+    publisher.send(ZmqMessage::from("B dropped")).await.unwrap();
+    publisher.send(ZmqMessage::from("A kept")).await.unwrap();
+    let message: String = subscriber.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(message, "A kept");
+}
+
+#[async_rt::test]
+async fn test_ws_refuses_unknown_path() {
+    pretty_env_logger::try_init().ok();
+
+    let mut rep = zeromq::RepSocket::new();
+    let endpoint = rep
+        .bind("ws://127.0.0.1:0/zmq")
+        .await
+        .expect("Failed to bind");
+    let port = match endpoint {
+        Endpoint::Ws(_, port, _) => port,
+        other => panic!("Unexpected endpoint {}", other),
+    };
+
+    let mut req = zeromq::ReqSocket::new();
+    assert!(req
+        .connect(&format!("ws://127.0.0.1:{}/other", port))
+        .await
+        .is_err());
+}
+
+#[async_rt::test]
+async fn test_ws_refuses_other_mechanism() {
+    pretty_env_logger::try_init().ok();
+
+    let options = SocketOptions::builder()
+        .plain_server(|_: &str, _: &str| true)
+        .build()
+        .unwrap();
+    let mut rep = zeromq::RepSocket::with_options(options);
+    let mut monitor = rep.monitor();
+    let endpoint = rep.bind("ws://127.0.0.1:0").await.expect("Failed to bind");
+
+    let mut req = zeromq::ReqSocket::new();
+    assert!(req.connect(&endpoint.to_string()).await.is_err());
+    loop {
+        match monitor.next().await.unwrap() {
+            SocketEvent::AcceptFailed(_) => break,
+            SocketEvent::HandshakeSucceeded(..) => panic!("NULL peers should be refused"),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(feature = "wss-transport")]
+#[async_rt::test]
+async fn test_wss_req_rep() {
+    pretty_env_logger::try_init().ok();
+
+    let mut ca_params = rcgen::CertificateParams::new(Vec::new()).unwrap();
+    ca_params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    let ca = rcgen::CertifiedIssuer::self_signed(ca_params, rcgen::KeyPair::generate().unwrap())
+        .unwrap();
+    let server_key = rcgen::KeyPair::generate().unwrap();
+    let server = rcgen::CertificateParams::new(vec!["127.0.0.1".to_string()])
+        .unwrap()
+        .signed_by(&server_key, &ca)
+        .unwrap();
+
+    let server_options = SocketOptions::builder()
+        .tls_certificate(server.pem(), server_key.serialize_pem())
+        .build()
+        .unwrap();
+    let mut rep = zeromq::RepSocket::with_options(server_options);
+    let endpoint = rep
+        .bind("wss://127.0.0.1:0/zmq")
+        .await
+        .expect("Failed to bind");
+    assert!(matches!(endpoint, Endpoint::Wss(..)));
+
+    // Servers are only trusted if signed by the CA
+    let client_options = SocketOptions::builder().tls_ca(ca.pem()).build().unwrap();
+    let mut req = zeromq::ReqSocket::with_options(client_options);
+    req.connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    req_rep_round_trips(&mut req, &mut rep).await;
+
+    let untrusted_options = SocketOptions::builder()
+        .tls_ca(server.pem())
+        .build()
+        .unwrap();
+    let mut untrusted = zeromq::ReqSocket::with_options(untrusted_options);
+    assert!(untrusted.connect(&endpoint.to_string()).await.is_err());
+}
+
+#[async_rt::test]
+async fn test_ws_silent_client_times_out() {
+    pretty_env_logger::try_init().ok();
+
+    let options = SocketOptions::builder()
+        .handshake_timeout(Some(Duration::from_millis(200)))
+        .build()
+        .unwrap();
+    let mut rep = zeromq::RepSocket::with_options(options);
+    let mut monitor = rep.monitor();
+    let endpoint = rep.bind("ws://127.0.0.1:0").await.expect("Failed to bind");
+    let port = match endpoint {
+        Endpoint::Ws(_, port, _) => port,
+        other => panic!("Unexpected endpoint {}", other),
+    };
+
+    // Opens the connection but never starts the WebSocket handshake
+    let _silent = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let timed_out = async {
+        loop {
+            if let SocketEvent::AcceptFailed(_) = monitor.next().await.unwrap() {
+                break;
+            }
+        }
+    };
+    futures::select! {
+        _ = timed_out.fuse() => {},
+        _ = async_rt::task::sleep(Duration::from_secs(5)).fuse() => {
+            panic!("The handshake should have timed out");
+        },
+    }
+}
@@ -0,0 +1,94 @@
+mod compliance;
+use compliance::{get_monitor_event, setup_monitor};
+
+use std::convert::TryInto;
+use std::time::Duration;
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::ZmqMessage;
+
+use futures::channel::oneshot;
+use futures::{select, FutureExt};
+
+#[async_rt::test]
+async fn test_their_rep_our_req() {
+    pretty_env_logger::try_init().ok();
+
+    let ctx = zmq::Context::new();
+    let their_rep = ctx.socket(zmq::REP).expect("Couldn't make rep socket");
+    their_rep
+        .bind("ws://127.0.0.1:*/zmq")
+        .expect("Failed to bind");
+    let endpoint = their_rep.get_last_endpoint().unwrap().unwrap();
+    let their_monitor = setup_monitor(&ctx, &their_rep, "inproc://their-ws-monitor");
+
+    let mut our_req = zeromq::ReqSocket::new();
+    our_req.connect(&endpoint).await.expect("Failed to connect");
+
+    let (events, events_done) = oneshot::channel();
+    let their_thread = std::thread::spawn(move || {
+        assert_eq!(
+            zmq::SocketEvent::ACCEPTED,
+            get_monitor_event(&their_monitor).0
+        );
+        assert_eq!(
+            zmq::SocketEvent::HANDSHAKE_SUCCEEDED,
+            get_monitor_event(&their_monitor).0
+        );
+        let _ = events.send(());
+        for i in 0..10 {
+            let request = their_rep.recv_msg(0).expect("Failed to recv");
+            assert_eq!(request.as_str().unwrap(), format!("Hello {}", i));
+            // Long enough to need an 8 byte length in ZMTP
+            their_rep
+                .send(&"World".repeat(100), 0)
+                .expect("Failed to send");
+        }
+    });
+    events_done.await.unwrap();
+
+    for i in 0..10 {
+        our_req
+            .send(format!("Hello {}", i).into())
+            .await
+            .expect("Failed to send");
+        let reply: String = our_req.recv().await.unwrap().try_into().unwrap();
+        assert_eq!(reply, "World".repeat(100));
+    }
+    their_thread.join().unwrap();
+}
+
+#[async_rt::test]
+async fn test_our_pub_their_sub() {
+    pretty_env_logger::try_init().ok();
+
+    let mut our_pub = zeromq::PubSocket::new();
+    let endpoint = our_pub
+        .bind("ws://127.0.0.1:0/feed")
+        .await
+        .expect("Failed to bind");
+
+    let (received, mut wait) = oneshot::channel();
+    let endpoint = endpoint.to_string();
+    std::thread::spawn(move || {
+        let ctx = zmq::Context::new();
+        let their_sub = ctx.socket(zmq::SUB).expect("Couldn't make sub socket");
+        their_sub.set_subscribe(b"A").unwrap();
+        their_sub.connect(&endpoint).expect("Failed to connect");
+        let message = their_sub.recv_msg(0).expect("Failed to recv");
+        let _ = received.send(message.as_str().unwrap().to_string());
+    });
+
+    // Publish until the subscription made it through the handshake
+    loop {
+        our_pub.send(ZmqMessage::from("B dropped")).await.unwrap();
+        our_pub.send(ZmqMessage::from("A kept")).await.unwrap();
+        select! {
+            message = wait => {
+                assert_eq!(message.unwrap(), "A kept");
+                break;
+            },
+            _ = async_rt::task::sleep(Duration::from_millis(50)).fuse() => {},
+        }
+    }
+}
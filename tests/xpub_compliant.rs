@@ -0,0 +1,149 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{SocketOptions, XPubSocket, ZmqMessage};
+
+use futures::channel::oneshot;
+use std::time::Duration;
+
+async fn setup_our_xpub() -> (XPubSocket, String) {
+    setup_our_xpub_with(SocketOptions::default()).await
+}
+
+async fn setup_our_xpub_with(options: SocketOptions) -> (XPubSocket, String) {
+    let mut our_xpub = XPubSocket::with_options(options);
+    let endpoint = our_xpub
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    (our_xpub, endpoint.to_string())
+}
+
+fn their_sub(ctx: &zmq::Context, endpoint: &str, topic: &[u8]) -> zmq::Socket {
+    let sub = ctx.socket(zmq::SUB).expect("Couldn't make sub socket");
+    sub.set_subscribe(topic).expect("Failed to subscribe");
+    sub.connect(endpoint).expect("Failed to connect");
+    sub
+}
+
+/// Receives on their socket without blocking our runtime
+async fn their_recv(sock: zmq::Socket) -> (zmq::Socket, Vec<u8>) {
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        let msg = sock.recv_bytes(0).expect("Failed to recv");
+        let _ = sender.send((sock, msg));
+    });
+    receiver.await.expect("Their recv thread died")
+}
+
+async fn our_recv(our_xpub: &mut XPubSocket) -> Vec<u8> {
+    our_xpub.recv().await.unwrap().get(0).unwrap().to_vec()
+}
+
+#[async_rt::test]
+async fn test_our_xpub_reports_new_subscriptions() {
+    let (mut our_xpub, endpoint) = setup_our_xpub().await;
+    let ctx = zmq::Context::new();
+
+    let first = their_sub(&ctx, &endpoint, b"A");
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x01A");
+    // A duplicate subscription doesn't change anything for the publisher
+    let second = their_sub(&ctx, &endpoint, b"A");
+    let other = their_sub(&ctx, &endpoint, b"B");
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x01B");
+
+    async_rt::task::sleep(Duration::from_millis(100)).await;
+    our_xpub.send(ZmqMessage::from("A message")).await.unwrap();
+    let (first, msg) = their_recv(first).await;
+    assert_eq!(msg, b"A message");
+    let (second, msg) = their_recv(second).await;
+    assert_eq!(msg, b"A message");
+
+    // Only the last unsubscription from a topic is reported
+    first.set_unsubscribe(b"A").unwrap();
+    second.set_unsubscribe(b"A").unwrap();
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x00A");
+
+    // Subscriptions of a subscriber that goes away are dropped as well
+    drop(other);
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x00B");
+}
+
+#[async_rt::test]
+async fn test_our_xpub_verbose() {
+    let (mut our_xpub, endpoint) = setup_our_xpub().await;
+    our_xpub.set_verbose(true);
+    let ctx = zmq::Context::new();
+
+    let first = their_sub(&ctx, &endpoint, b"A");
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x01A");
+    let second = their_sub(&ctx, &endpoint, b"A");
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x01A");
+
+    first.set_unsubscribe(b"A").unwrap();
+    second.set_unsubscribe(b"A").unwrap();
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x00A");
+
+    our_xpub.set_verboser(true);
+    let third = their_sub(&ctx, &endpoint, b"C");
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x01C");
+    let fourth = their_sub(&ctx, &endpoint, b"C");
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x01C");
+    third.set_unsubscribe(b"C").unwrap();
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x00C");
+    fourth.set_unsubscribe(b"C").unwrap();
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x00C");
+}
+
+#[async_rt::test]
+async fn test_our_xpub_manual() {
+    let (mut our_xpub, endpoint) = setup_our_xpub().await;
+    our_xpub.set_manual(true);
+    let ctx = zmq::Context::new();
+
+    let sub = their_sub(&ctx, &endpoint, b"B");
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x01B");
+
+    // Not applied yet, so this one is not delivered
+    our_xpub.send(ZmqMessage::from("B dropped")).await.unwrap();
+    our_xpub.subscribe("B").unwrap();
+    our_xpub
+        .send(ZmqMessage::from("B delivered"))
+        .await
+        .unwrap();
+
+    let (_sub, msg) = their_recv(sub).await;
+    assert_eq!(msg, b"B delivered");
+}
+
+#[async_rt::test]
+async fn test_our_xpub_welcome_msg() {
+    let (mut our_xpub, endpoint) = setup_our_xpub().await;
+    our_xpub.set_welcome_msg(Some(ZmqMessage::from("WELCOME")));
+    let ctx = zmq::Context::new();
+
+    let sub = their_sub(&ctx, &endpoint, b"WELCOME");
+    let (_sub, msg) = their_recv(sub).await;
+    assert_eq!(msg, b"WELCOME");
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x01WELCOME");
+}
+
+#[async_rt::test]
+async fn test_our_xpub_options() {
+    let options = SocketOptions::builder()
+        .xpub_verboser(true)
+        .xpub_welcome_msg(Some(ZmqMessage::from("WELCOME")))
+        .build()
+        .unwrap();
+    let (mut our_xpub, endpoint) = setup_our_xpub_with(options).await;
+    let ctx = zmq::Context::new();
+
+    let first = their_sub(&ctx, &endpoint, b"WELCOME");
+    let (first, msg) = their_recv(first).await;
+    assert_eq!(msg, b"WELCOME");
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x01WELCOME");
+    let _second = their_sub(&ctx, &endpoint, b"WELCOME");
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x01WELCOME");
+
+    first.set_unsubscribe(b"WELCOME").unwrap();
+    assert_eq!(our_recv(&mut our_xpub).await, b"\x00WELCOME");
+}
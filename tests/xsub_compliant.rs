@@ -0,0 +1,119 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{XPubSocket, XSubSocket, ZmqMessage};
+
+use futures::channel::oneshot;
+use futures::{select, FutureExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Returns (socket, bound_endpoint)
+fn setup_their_pub(ctx: &zmq::Context) -> (zmq::Socket, String) {
+    let their_pub = ctx.socket(zmq::PUB).expect("Couldn't make pub socket");
+    their_pub.bind("tcp://127.0.0.1:0").expect("Failed to bind");
+    let resolved_bind = their_pub.get_last_endpoint().unwrap().unwrap();
+    (their_pub, resolved_bind)
+}
+
+/// Publishes on `topic` and `other` alternately until `stop` is set
+fn run_their_pub(their_pub: zmq::Socket, stop: Arc<AtomicBool>) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let mut i = 0u32;
+        while !stop.load(Ordering::Relaxed) {
+            their_pub
+                .send(&format!("other {}", i), 0)
+                .expect("Failed to send");
+            their_pub
+                .send(&format!("topic {}", i), 0)
+                .expect("Failed to send");
+            i += 1;
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    })
+}
+
+#[async_rt::test]
+async fn test_their_pub_our_xsub() {
+    let ctx = zmq::Context::new();
+    let (their_pub, endpoint) = setup_their_pub(&ctx);
+
+    let mut our_xsub = XSubSocket::new();
+    our_xsub
+        .connect(&endpoint)
+        .await
+        .expect("Failed to connect");
+    our_xsub
+        .send(ZmqMessage::from("\x01topic"))
+        .await
+        .expect("Failed to subscribe");
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let their_handle = run_their_pub(their_pub, stop.clone());
+
+    for _ in 0..16 {
+        let msg = our_xsub.recv().await.expect("Failed to recv");
+        let payload = String::from_utf8(msg.get(0).unwrap().to_vec()).unwrap();
+        assert!(payload.starts_with("topic "), "{}", payload);
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    their_handle.join().unwrap();
+}
+
+#[async_rt::test]
+async fn test_xpub_xsub_proxy() {
+    let ctx = zmq::Context::new();
+    let (their_pub, pub_endpoint) = setup_their_pub(&ctx);
+
+    let mut our_xsub = XSubSocket::new();
+    our_xsub
+        .connect(&pub_endpoint)
+        .await
+        .expect("Failed to connect");
+    let mut our_xpub = XPubSocket::new();
+    let proxy_endpoint = our_xpub
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let (proxy_stop_sender, proxy_stop) = oneshot::channel::<()>();
+    let proxy = async_rt::task::spawn(async move {
+        let mut proxy_stop = proxy_stop.fuse();
+        loop {
+            select! {
+                publication = our_xsub.recv().fuse() => {
+                    our_xpub.send(publication.unwrap()).await.unwrap();
+                },
+                subscription = our_xpub.recv().fuse() => {
+                    our_xsub.send(subscription.unwrap()).await.unwrap();
+                },
+                _ = proxy_stop => break,
+            }
+        }
+    });
+
+    let their_sub = ctx.socket(zmq::SUB).expect("Couldn't make sub socket");
+    their_sub.set_subscribe(b"topic").unwrap();
+    their_sub
+        .connect(&proxy_endpoint.to_string())
+        .expect("Failed to connect");
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let their_handle = run_their_pub(their_pub, stop.clone());
+
+    let (received_sender, received) = oneshot::channel();
+    std::thread::spawn(move || {
+        for _ in 0..16 {
+            let msg = their_sub.recv_string(0).unwrap().unwrap();
+            assert!(msg.starts_with("topic "), "{}", msg);
+        }
+        let _ = received_sender.send(());
+    });
+    received.await.expect("Their sub failed");
+
+    stop.store(true, Ordering::Relaxed);
+    their_handle.join().unwrap();
+    proxy_stop_sender.send(()).unwrap();
+    proxy.await.unwrap();
+}
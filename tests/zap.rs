@@ -0,0 +1,213 @@
+use zeromq::__async_rt as async_rt;
+use zeromq::prelude::*;
+use zeromq::{
+    CurveKeyPair, PeerMetadata, SocketEvent, SocketOptions, ZapAuthenticator, ZapCredentials,
+    ZapHandler, ZapReply, ZapRequest, ZmqError, ZmqMessage,
+};
+
+use futures::channel::mpsc;
+use futures::StreamExt;
+use std::convert::TryInto;
+use std::sync::Mutex;
+
+/// Accepts everyone, recording the requests it got
+struct Recorder(Mutex<mpsc::UnboundedSender<ZapRequest>>);
+
+#[async_trait::async_trait]
+impl ZapHandler for Recorder {
+    async fn authenticate(&self, request: &ZapRequest) -> ZapReply {
+        let _ = self.0.lock().unwrap().unbounded_send(request.clone());
+        match &request.credentials {
+            ZapCredentials::Plain { username, .. } => ZapReply::accept(username.clone()),
+            _ => ZapReply::accept(""),
+        }
+    }
+}
+
+struct DenyAll;
+
+#[async_trait::async_trait]
+impl ZapHandler for DenyAll {
+    async fn authenticate(&self, _request: &ZapRequest) -> ZapReply {
+        ZapReply::deny("Go away")
+    }
+}
+
+async fn handshake_metadata(monitor: &mut mpsc::Receiver<SocketEvent>) -> PeerMetadata {
+    loop {
+        match monitor.next().await.unwrap() {
+            SocketEvent::HandshakeSucceeded(_, metadata) => return metadata,
+            SocketEvent::AcceptFailed(e) => panic!("Unexpected error {:?}", e),
+            _ => {}
+        }
+    }
+}
+
+async fn expect_denied(monitor: &mut mpsc::Receiver<SocketEvent>) {
+    loop {
+        match monitor.next().await.unwrap() {
+            SocketEvent::AcceptFailed(ZmqError::AuthenticationFailed(_)) => break,
+            SocketEvent::AcceptFailed(e) => panic!("Unexpected error {:?}", e),
+            SocketEvent::HandshakeSucceeded(..) => panic!("Peer should have been denied"),
+            _ => {}
+        }
+    }
+}
+
+#[async_rt::test]
+async fn test_plain_user_id() {
+    pretty_env_logger::try_init().ok();
+
+    let (sender, mut requests) = mpsc::unbounded();
+    let options = SocketOptions::builder()
+        .plain_server(|_: &str, password: &str| password == "secret")
+        .zap_domain("global")
+        .zap_handler(Recorder(Mutex::new(sender)))
+        .build()
+        .unwrap();
+    let mut rep_socket = zeromq::RepSocket::with_options(options);
+    let mut monitor = rep_socket.monitor();
+    let endpoint = rep_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let options = SocketOptions::builder()
+        .plain_client("admin", "secret")
+        .build()
+        .unwrap();
+    let mut req_socket = zeromq::ReqSocket::with_options(options);
+    req_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+
+    let metadata = handshake_metadata(&mut monitor).await;
+    assert_eq!(metadata.user_id(), Some("admin"));
+    assert_eq!(metadata.get_str("user-id"), Some("admin"));
+
+    let request = requests.next().await.unwrap();
+    assert_eq!(request.domain, "global");
+    assert_eq!(request.mechanism(), "PLAIN");
+    assert_eq!(request.ip(), Some("127.0.0.1".parse().unwrap()));
+    assert_eq!(
+        request.credentials,
+        ZapCredentials::Plain {
+            username: "admin".into(),
+            password: "secret".into()
+        }
+    );
+
+    req_socket.send(ZmqMessage::from("Hello")).await.unwrap();
+    let hello: String = rep_socket.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(hello, "Hello");
+}
+
+#[async_rt::test]
+async fn test_null_denied() {
+    pretty_env_logger::try_init().ok();
+
+    let options = SocketOptions::builder()
+        .zap_handler(DenyAll)
+        .build()
+        .unwrap();
+    let mut rep_socket = zeromq::RepSocket::with_options(options);
+    let mut monitor = rep_socket.monitor();
+    let endpoint = rep_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let mut req_socket = zeromq::ReqSocket::new();
+    let result = req_socket.connect(&endpoint.to_string()).await;
+    assert!(matches!(result, Err(ZmqError::AuthenticationFailed(_))));
+    expect_denied(&mut monitor).await;
+}
+
+#[async_rt::test]
+async fn test_ip_filters() {
+    pretty_env_logger::try_init().ok();
+
+    let localhost = "127.0.0.1".parse().unwrap();
+    let authenticators = vec![
+        ZapAuthenticator::new().deny_ip(localhost),
+        ZapAuthenticator::new().allow_ip("10.0.0.1".parse().unwrap()),
+    ];
+    for authenticator in authenticators {
+        let options = SocketOptions::builder()
+            .zap_handler(authenticator)
+            .build()
+            .unwrap();
+        let mut pull_socket = zeromq::PullSocket::with_options(options);
+        let mut monitor = pull_socket.monitor();
+        let endpoint = pull_socket
+            .bind("tcp://127.0.0.1:0")
+            .await
+            .expect("Failed to bind");
+
+        let mut push_socket = zeromq::PushSocket::new();
+        assert!(push_socket.connect(&endpoint.to_string()).await.is_err());
+        expect_denied(&mut monitor).await;
+    }
+
+    let options = SocketOptions::builder()
+        .zap_handler(ZapAuthenticator::new().allow_ip(localhost))
+        .build()
+        .unwrap();
+    let mut pull_socket = zeromq::PullSocket::with_options(options);
+    let endpoint = pull_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+    let mut push_socket = zeromq::PushSocket::new();
+    push_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+}
+
+#[async_rt::test]
+async fn test_curve_key_allow_list() {
+    pretty_env_logger::try_init().ok();
+
+    let server = CurveKeyPair::new();
+    let known = CurveKeyPair::new();
+    let options = SocketOptions::builder()
+        .curve_server(server.clone())
+        .zap_handler(ZapAuthenticator::new().allow_curve_key(known.public_key))
+        .build()
+        .unwrap();
+    let mut rep_socket = zeromq::RepSocket::with_options(options);
+    let mut monitor = rep_socket.monitor();
+    let endpoint = rep_socket
+        .bind("tcp://127.0.0.1:0")
+        .await
+        .expect("Failed to bind");
+
+    let options = SocketOptions::builder()
+        .curve_client(server.public_key, CurveKeyPair::new())
+        .build()
+        .unwrap();
+    let mut stranger = zeromq::ReqSocket::with_options(options);
+    assert!(stranger.connect(&endpoint.to_string()).await.is_err());
+    expect_denied(&mut monitor).await;
+
+    let options = SocketOptions::builder()
+        .curve_client(server.public_key, known.clone())
+        .build()
+        .unwrap();
+    let mut req_socket = zeromq::ReqSocket::with_options(options);
+    req_socket
+        .connect(&endpoint.to_string())
+        .await
+        .expect("Failed to connect");
+    let metadata = handshake_metadata(&mut monitor).await;
+    assert_eq!(
+        metadata.user_id(),
+        Some(zeromq::z85::encode(&known.public_key).unwrap().as_str())
+    );
+
+    req_socket.send(ZmqMessage::from("Hello")).await.unwrap();
+    let hello: String = rep_socket.recv().await.unwrap().try_into().unwrap();
+    assert_eq!(hello, "Hello");
+}